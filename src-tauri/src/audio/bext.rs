@@ -0,0 +1,361 @@
+use crate::prelude::*;
+
+// Field widths from EBU Tech 3285
+const DESCRIPTION_LEN: usize = 256;
+const ORIGINATOR_LEN: usize = 32;
+const ORIGINATOR_REFERENCE_LEN: usize = 32;
+const DATE_LEN: usize = 10;
+const TIME_LEN: usize = 8;
+const UMID_LEN: usize = 64;
+
+// Offsets of the fields after the text block
+const TIME_REFERENCE_OFFSET: usize = 338;
+const VERSION_OFFSET: usize = 346;
+const UMID_OFFSET: usize = 348;
+const LOUDNESS_OFFSET: usize = 412;
+const LOUDNESS_LEN: usize = 10;
+/// Size of the fixed part, everything before CodingHistory
+pub const BEXT_FIXED_LEN: usize = 602;
+
+/// EBU R 128 loudness fields of a version 2 bext chunk, stored in hundredths of a LU, LUFS or
+/// dBTP. 0x7FFF marks a value that wasn't measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BextLoudness {
+    pub loudness_value: i16,
+    pub loudness_range: i16,
+    pub max_true_peak_level: i16,
+    pub max_momentary_loudness: i16,
+    pub max_short_term_loudness: i16,
+}
+
+/// The Broadcast Wave `bext` chunk. The UMID is only stored from version 1 on and the loudness
+/// fields from version 2 on. Whatever older versions keep in their place is carried in
+/// `reserved`, so chunks are written back the way they were read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bext {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// yyyy-mm-dd
+    pub origination_date: String,
+    /// hh:mm:ss
+    pub origination_time: String,
+    /// Samples since midnight
+    pub time_reference: u64,
+    pub version: u16,
+    pub umid: Vec<u8>,
+    pub loudness: Option<BextLoudness>,
+    /// The 254 bytes after the version as read, which the UMID and loudness fields overwrite
+    /// when the version has them
+    #[serde(default)]
+    pub reserved: Vec<u8>,
+    pub coding_history: String,
+}
+
+impl Bext {
+    /// Reads a bext chunk's data. Chunks cut short of the fixed 602 bytes, which some
+    /// recorders write, read as if padded with zeros.
+    pub fn parse(data: &[u8]) -> R<Self> {
+        if data.len() < VERSION_OFFSET + 2 {
+            return Err(anyhow!("bext chunk too short: {} bytes", data.len()));
+        }
+        let mut fixed = data[..data.len().min(BEXT_FIXED_LEN)].to_vec();
+        fixed.resize(BEXT_FIXED_LEN, 0);
+
+        let mut cursor = Cursor::new(&fixed);
+        let mut text = |len: usize| -> R<String> {
+            let mut field = vec![0u8; len];
+            cursor.read_exact(&mut field)?;
+            Ok(read_text(&field))
+        };
+        let description = text(DESCRIPTION_LEN)?;
+        let originator = text(ORIGINATOR_LEN)?;
+        let originator_reference = text(ORIGINATOR_REFERENCE_LEN)?;
+        let origination_date = text(DATE_LEN)?;
+        let origination_time = text(TIME_LEN)?;
+
+        let mut cursor = Cursor::new(&fixed[TIME_REFERENCE_OFFSET..]);
+        let low = cursor.read_u32::<LittleEndian>()? as u64;
+        let high = cursor.read_u32::<LittleEndian>()? as u64;
+        let version = cursor.read_u16::<LittleEndian>()?;
+
+        let umid = if version >= 1 {
+            fixed[UMID_OFFSET..UMID_OFFSET + UMID_LEN].to_vec()
+        } else {
+            Vec::new()
+        };
+        let loudness = if version >= 2 {
+            let mut cursor = Cursor::new(&fixed[LOUDNESS_OFFSET..]);
+            Some(BextLoudness {
+                loudness_value: cursor.read_i16::<LittleEndian>()?,
+                loudness_range: cursor.read_i16::<LittleEndian>()?,
+                max_true_peak_level: cursor.read_i16::<LittleEndian>()?,
+                max_momentary_loudness: cursor.read_i16::<LittleEndian>()?,
+                max_short_term_loudness: cursor.read_i16::<LittleEndian>()?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Bext {
+            description,
+            originator,
+            originator_reference,
+            origination_date,
+            origination_time,
+            time_reference: (high << 32) | low,
+            version,
+            umid,
+            loudness,
+            reserved: fixed[UMID_OFFSET..].to_vec(),
+            coding_history: read_text(data.get(BEXT_FIXED_LEN..).unwrap_or_default()),
+        })
+    }
+
+    /// Serializes to chunk data. Text longer than its field is cut off, shorter text is padded
+    /// with zeros.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(BEXT_FIXED_LEN + self.coding_history.len());
+        write_text(&mut out, &self.description, DESCRIPTION_LEN);
+        write_text(&mut out, &self.originator, ORIGINATOR_LEN);
+        write_text(
+            &mut out,
+            &self.originator_reference,
+            ORIGINATOR_REFERENCE_LEN,
+        );
+        write_text(&mut out, &self.origination_date, DATE_LEN);
+        write_text(&mut out, &self.origination_time, TIME_LEN);
+        out.extend_from_slice(&(self.time_reference as u32).to_le_bytes());
+        out.extend_from_slice(&((self.time_reference >> 32) as u32).to_le_bytes());
+        out.extend_from_slice(&self.version.to_le_bytes());
+        let reserved = &self.reserved[..self.reserved.len().min(BEXT_FIXED_LEN - UMID_OFFSET)];
+        out.extend_from_slice(reserved);
+        out.resize(BEXT_FIXED_LEN, 0);
+
+        if self.version >= 1 {
+            let umid = &mut out[UMID_OFFSET..UMID_OFFSET + UMID_LEN];
+            let len = self.umid.len().min(UMID_LEN);
+            umid.fill(0);
+            umid[..len].copy_from_slice(&self.umid[..len]);
+        }
+        if self.version >= 2 {
+            let loudness = self.loudness.unwrap_or_default();
+            let values = [
+                loudness.loudness_value,
+                loudness.loudness_range,
+                loudness.max_true_peak_level,
+                loudness.max_momentary_loudness,
+                loudness.max_short_term_loudness,
+            ];
+            let fields = &mut out[LOUDNESS_OFFSET..LOUDNESS_OFFSET + LOUDNESS_LEN];
+            for (field, value) in fields.chunks_exact_mut(2).zip(values) {
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        out.extend_from_slice(self.coding_history.as_bytes());
+        out
+    }
+
+    /// `time_reference` as hh:mm:ss:ff
+    pub fn timecode(&self, sample_rate: u32, fps: u32) -> String {
+        if sample_rate == 0 || fps == 0 {
+            return String::new();
+        }
+        let seconds = self.time_reference / sample_rate as u64;
+        let frames = (self.time_reference % sample_rate as u64) * fps as u64 / sample_rate as u64;
+        format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            frames
+        )
+    }
+
+    /// Parses hh:mm:ss:ff (or hh:mm:ss) into `time_reference`.
+    pub fn set_timecode(&mut self, timecode: &str, sample_rate: u32, fps: u32) -> R<()> {
+        let parts = timecode
+            .split([':', ';'])
+            .map(|part| part.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow!("Invalid timecode: {}", timecode))?;
+        let (h, m, s, f) = match parts[..] {
+            [h, m, s] => (h, m, s, 0),
+            [h, m, s, f] => (h, m, s, f),
+            _ => return Err(anyhow!("Invalid timecode: {}", timecode)),
+        };
+        if m >= 60 || s >= 60 || fps == 0 || f >= fps as u64 {
+            return Err(anyhow!("Invalid timecode: {}", timecode));
+        }
+        let rate = sample_rate as u64;
+        self.time_reference = h
+            .checked_mul(3600)
+            .and_then(|seconds| seconds.checked_add(m * 60 + s))
+            .and_then(|seconds| seconds.checked_mul(rate))
+            .and_then(|samples| samples.checked_add(f * rate / fps as u64))
+            .ok_or_else(|| anyhow!("Timecode out of range: {}", timecode))?;
+        Ok(())
+    }
+
+    /// Every field as text, in chunk order, for showing and comparing chunks.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("Description", self.description.clone()),
+            ("Originator", self.originator.clone()),
+            ("OriginatorReference", self.originator_reference.clone()),
+            ("OriginationDate", self.origination_date.clone()),
+            ("OriginationTime", self.origination_time.clone()),
+            ("TimeReference", self.time_reference.to_string()),
+            ("Version", self.version.to_string()),
+            (
+                "UMID",
+                self.umid.iter().map(|b| format!("{:02X}", b)).collect(),
+            ),
+        ];
+        if let Some(loudness) = &self.loudness {
+            fields.extend([
+                ("LoudnessValue", loudness.loudness_value.to_string()),
+                ("LoudnessRange", loudness.loudness_range.to_string()),
+                ("MaxTruePeakLevel", loudness.max_true_peak_level.to_string()),
+                (
+                    "MaxMomentaryLoudness",
+                    loudness.max_momentary_loudness.to_string(),
+                ),
+                (
+                    "MaxShortTermLoudness",
+                    loudness.max_short_term_loudness.to_string(),
+                ),
+            ]);
+        }
+        fields.push(("CodingHistory", self.coding_history.clone()));
+        fields
+    }
+}
+
+// Fixed width fields end at the first zero byte, or fill the whole field
+fn read_text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+fn write_text(out: &mut Vec<u8>, text: &str, len: usize) {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    out.extend_from_slice(&text.as_bytes()[..end]);
+    out.resize(out.len() + len - end, 0);
+}
+
+impl MetadataChunk {
+    pub fn bext(&self) -> Option<Bext> {
+        match self {
+            MetadataChunk::Bext(data) => Bext::parse(data).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl metadata::Metadata {
+    pub fn bext(&self) -> Option<Bext> {
+        match self {
+            Self::Wav(chunks) | Self::Aiff(chunks) => chunks.iter().find_map(MetadataChunk::bext),
+            Self::Flac(_) => None,
+        }
+    }
+
+    /// Replaces the bext chunk, or adds one if there is none.
+    pub fn set_bext(&mut self, bext: &Bext) -> R<()> {
+        let chunks = match self {
+            Self::Wav(chunks) | Self::Aiff(chunks) => chunks,
+            Self::Flac(_) => return Err(anyhow!("FLAC files have no bext chunk")),
+        };
+        let data = bext.to_bytes();
+        match chunks
+            .iter_mut()
+            .find(|chunk| matches!(chunk, MetadataChunk::Bext(_)))
+        {
+            Some(chunk) => *chunk = MetadataChunk::Bext(data),
+            None => chunks.push(MetadataChunk::Bext(data)),
+        }
+        Ok(())
+    }
+}
+
+/// Reads the bext chunk of a WAV or AIFF file.
+pub fn read_bext(path: &Path) -> R<Option<Bext>> {
+    Ok(metadata::Metadata::from_file(path)?.bext())
+}
+
+/// Writes `bext` into the file at `path`, leaving its audio and other chunks as they are.
+pub fn write_bext(path: &Path, bext: &Bext) -> R<()> {
+    let mut metadata = metadata::Metadata::from_file(path)?;
+    metadata.set_bext(bext)?;
+    metadata.set_metadata(path.to_str().ok_or_else(|| anyhow!("Invalid path"))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fixed part with every byte set, so anything not carried over shows up as a zero
+    fn chunk(version: u16, coding_history: &str) -> Vec<u8> {
+        let mut data: Vec<u8> = (0..BEXT_FIXED_LEN).map(|i| (i % 251) as u8 + 1).collect();
+        data[..DESCRIPTION_LEN].fill(0);
+        data[..4].copy_from_slice(b"Door");
+        data[VERSION_OFFSET..UMID_OFFSET].copy_from_slice(&version.to_le_bytes());
+        data.extend(coding_history.as_bytes());
+        data
+    }
+
+    #[test]
+    fn every_version_round_trips() {
+        for version in 0..=2 {
+            let data = chunk(version, "A=PCM,F=48000,W=24,M=stereo\r\n");
+            let bext = Bext::parse(&data).unwrap();
+            assert_eq!(bext.version, version);
+            assert_eq!(bext.description, "Door");
+            assert_eq!(bext.umid.len(), if version >= 1 { UMID_LEN } else { 0 });
+            assert_eq!(bext.loudness.is_some(), version >= 2);
+            assert_eq!(bext.to_bytes(), data, "version {}", version);
+        }
+    }
+
+    #[test]
+    fn edited_fields_are_written_over_the_original_bytes() {
+        let data = chunk(2, "");
+        let mut bext = Bext::parse(&data).unwrap();
+        bext.description = "Window".to_string();
+        bext.umid = vec![0xAB; 32];
+        bext.loudness = Some(BextLoudness {
+            loudness_value: -2300,
+            ..Default::default()
+        });
+
+        let written = bext.to_bytes();
+        assert_eq!(written.len(), BEXT_FIXED_LEN);
+        let reread = Bext::parse(&written).unwrap();
+        assert_eq!(reread.description, "Window");
+        assert_eq!(reread.umid, [vec![0xAB; 32], vec![0; 32]].concat());
+        assert_eq!(reread.loudness.unwrap().loudness_value, -2300);
+        // Reserved bytes after the loudness fields are untouched
+        let reserved = LOUDNESS_OFFSET + LOUDNESS_LEN;
+        assert_eq!(written[reserved..], data[reserved..]);
+    }
+
+    #[test]
+    fn timecode_round_trips_and_rejects_overflow() {
+        let mut bext = Bext::default();
+        bext.set_timecode("01:02:03:12", 48000, 24).unwrap();
+        assert_eq!(bext.time_reference, 3723 * 48000 + 24000);
+        assert_eq!(bext.timecode(48000, 24), "01:02:03:12");
+
+        assert!(
+            bext.set_timecode("18446744073709551615:00:00:00", 48000, 24)
+                .is_err()
+        );
+        assert!(bext.set_timecode("01:60:00:00", 48000, 24).is_err());
+        assert_eq!(bext.time_reference, 3723 * 48000 + 24000);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::prelude::*;
+
+/// Default for `Preferences::channel_tolerance_db`
+pub const DEFAULT_CHANNEL_TOLERANCE_DB: f64 = -60.0;
+
+/// Which channels of a multichannel file carry no information of their own. Channels are
+/// numbered from 0 and every pair is listed lower channel first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelAnalysis {
+    pub channels: u16,
+    pub tolerance_db: f64,
+    /// Peak stays below the tolerance
+    pub silent: Vec<u16>,
+    /// Every sample is zero
+    #[serde(default)]
+    pub digital_silence: Vec<u16>,
+    /// Bit-exact copies
+    pub identical: Vec<(u16, u16)>,
+    /// Sum stays below the tolerance, so one is the other with its polarity flipped
+    pub inverted: Vec<(u16, u16)>,
+    /// Not identical, but the difference stays below the tolerance
+    pub near_identical: Vec<(u16, u16)>,
+    /// iXML track name of each channel, empty where the file names none
+    #[serde(default)]
+    pub track_names: Vec<String>,
+}
+
+impl ChannelAnalysis {
+    /// Every channel is a bit-exact copy of the first, the old all-or-nothing dual mono check.
+    pub fn is_dual_mono(&self) -> bool {
+        self.channels > 1
+            && self.identical.iter().filter(|(a, _)| *a == 0).count() == self.channels as usize - 1
+    }
+
+    pub fn summary(&self) -> String {
+        let pairs = |pairs: &[(u16, u16)], sep: &str| {
+            pairs
+                .iter()
+                .map(|(a, b)| format!("{}{}{}", a + 1, sep, b + 1))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut parts = Vec::new();
+        if !self.silent.is_empty() {
+            let silent: Vec<String> = self
+                .silent
+                .iter()
+                .map(|&c| match self.track_names.get(c as usize) {
+                    Some(name) if !name.is_empty() => format!("{} ({})", c + 1, name),
+                    _ => (c + 1).to_string(),
+                })
+                .collect();
+            parts.push(format!("silent {}", silent.join(",")));
+        }
+        if !self.identical.is_empty() {
+            parts.push(format!("identical {}", pairs(&self.identical, "=")));
+        }
+        if !self.inverted.is_empty() {
+            parts.push(format!("inverted {}", pairs(&self.inverted, "/")));
+        }
+        if !self.near_identical.is_empty() {
+            parts.push(format!("near {}", pairs(&self.near_identical, "~")));
+        }
+        parts.join("; ")
+    }
+
+    /// Channels that can be removed without losing anything: digitally silent channels, and all
+    /// but one of every group of identical channels. `quiet` also removes channels that are only
+    /// below the tolerance, which can still hold low level sound. The channel kept is the first
+    /// one with a track name, so a named boom survives over an unnamed copy of it, or else the
+    /// lowest. If that would remove every channel, the first named channel or channel 0 stays.
+    pub fn redundant_channels(&self, quiet: bool) -> Vec<u16> {
+        let named = |c: &u16| {
+            self.track_names
+                .get(*c as usize)
+                .is_some_and(|name| !name.is_empty())
+        };
+        let silent = if quiet {
+            &self.silent
+        } else {
+            &self.digital_silence
+        };
+        let mut redundant = silent.clone();
+        let mut grouped = vec![false; self.channels as usize];
+        for c in 0..self.channels {
+            if grouped.get(c as usize) != Some(&false) || silent.contains(&c) {
+                continue;
+            }
+            // Bit-exact copies are transitive, so the partners of `c` are the whole group
+            let mut group: Vec<u16> = self
+                .identical
+                .iter()
+                .filter_map(|&(a, b)| match (a == c, b == c) {
+                    (true, _) => Some(b),
+                    (_, true) => Some(a),
+                    _ => None,
+                })
+                .chain([c])
+                .collect();
+            group.sort_unstable();
+            group.dedup();
+            let keep = group.iter().copied().find(named).unwrap_or(group[0]);
+            for channel in group {
+                if let Some(grouped) = grouped.get_mut(channel as usize) {
+                    *grouped = true;
+                }
+                if channel != keep {
+                    redundant.push(channel);
+                }
+            }
+        }
+        redundant.sort_unstable();
+        redundant.dedup();
+        if redundant.len() >= self.channels as usize {
+            let keep = (0..self.channels).find(named).unwrap_or(0);
+            redundant.retain(|&c| c != keep);
+        }
+        redundant
+    }
+}
+
+/// Rewrites `path` without the channels in `drop`, keeping its sample format and its metadata
+/// chunks. An empty `drop` keeps only the first channel, as for dual mono files. The original is
+/// only replaced once the new file decodes. Returns the channels left.
+pub fn strip_channels_from_file(path: &Path, drop: &[u16]) -> R<u16> {
+    let output = path.to_str().ok_or_else(|| anyhow!("Invalid path"))?;
+    // Checked up front so unsupported formats fail before the decode
+    get_encoder(output)?;
+    let mut metadata = metadata::Metadata::from_file(path)?;
+    let mut buffer = decode_to_buffer(path)?;
+    let drop: Vec<u16> = if drop.is_empty() {
+        (1..buffer.channels).collect()
+    } else {
+        drop.to_vec()
+    };
+    buffer.strip_channels(&drop)?;
+    metadata.remove_tracks(&drop)?;
+    buffer.export_with_metadata(output, Some(&metadata))?;
+    Ok(buffer.channels)
+}
+
+/// Decodes `path` once and checks every channel and channel pair. `tolerance_db` is in dBFS.
+pub fn analyze_channels(path: &Path, tolerance_db: f64) -> R<ChannelAnalysis> {
+    let tolerance = 10f64.powf(tolerance_db / 20.0) * i32::MAX as f64;
+    let mut peaks: Vec<i64> = Vec::new();
+    // (a, b, peak of a - b, peak of a + b)
+    let mut pairs: Vec<(usize, usize, i64, i64)> = Vec::new();
+
+    let (_, channels) = for_each_decoded_packet(path, |samples, channels| {
+        if peaks.len() != channels {
+            peaks = vec![0; channels];
+            pairs = (0..channels)
+                .flat_map(|a| (a + 1..channels).map(move |b| (a, b, 0, 0)))
+                .collect();
+        }
+        for frame in samples.chunks_exact(channels) {
+            for (peak, &s) in peaks.iter_mut().zip(frame) {
+                *peak = (*peak).max((s as i64).abs());
+            }
+            for (a, b, diff, sum) in pairs.iter_mut() {
+                let (x, y) = (frame[*a] as i64, frame[*b] as i64);
+                *diff = (*diff).max((x - y).abs());
+                *sum = (*sum).max((x + y).abs());
+            }
+        }
+    })?;
+
+    let silent = |c: usize| (peaks[c] as f64) <= tolerance;
+    let mut analysis = ChannelAnalysis {
+        channels,
+        tolerance_db,
+        track_names: read_ixml(path)
+            .ok()
+            .flatten()
+            .map(|ixml| ixml.track_names(channels))
+            .unwrap_or_default(),
+        silent: (0..peaks.len())
+            .filter(|&c| silent(c))
+            .map(|c| c as u16)
+            .collect(),
+        digital_silence: (0..peaks.len())
+            .filter(|&c| peaks[c] == 0)
+            .map(|c| c as u16)
+            .collect(),
+        ..Default::default()
+    };
+    for &(a, b, diff, sum) in &pairs {
+        let pair = (a as u16, b as u16);
+        if diff == 0 {
+            analysis.identical.push(pair);
+        } else if silent(a) || silent(b) {
+            continue;
+        } else if (sum as f64) <= tolerance {
+            analysis.inverted.push(pair);
+        } else if (diff as f64) <= tolerance {
+            analysis.near_identical.push(pair);
+        }
+    }
+    Ok(analysis)
+}
+
+impl FileRecord {
+    /// Sets the channel algorithms from `channel_analysis`.
+    pub fn apply_channel_analysis(&mut self) {
+        for algorithm in [
+            A::DualMono,
+            A::SilentChannel,
+            A::DuplicateChannels,
+            A::InvertedChannels,
+            A::NearIdenticalChannels,
+        ] {
+            self.algorithm.remove(&algorithm);
+        }
+        let Some(analysis) = &self.channel_analysis else {
+            return;
+        };
+        let dual_mono = analysis.is_dual_mono();
+        let flags = [
+            (A::DualMono, dual_mono),
+            (A::SilentChannel, !analysis.silent.is_empty()),
+            (
+                A::DuplicateChannels,
+                !dual_mono && !analysis.identical.is_empty(),
+            ),
+            (A::InvertedChannels, !analysis.inverted.is_empty()),
+            (
+                A::NearIdenticalChannels,
+                !analysis.near_identical.is_empty(),
+            ),
+        ];
+        for (algorithm, found) in flags {
+            if found {
+                self.algorithm.insert(algorithm);
+            }
+        }
+        self.dual_mono = Some(dual_mono);
+    }
+}
+
+impl Database {
+    /// Fills in the channels to drop of every file without an explicit list, from
+    /// `ChannelAnalysis::redundant_channels` with `quiet` passed on. Dual mono verdicts without an
+    /// analysis keep the first channel; files with nothing redundant are left out.
+    pub fn channel_strips(&self, files: &[DualMono], quiet: bool) -> Vec<DualMono> {
+        let records: HashMap<usize, &FileRecord> = self.records.iter().map(|r| (r.id, r)).collect();
+        files
+            .iter()
+            .filter_map(|file| {
+                if !file.drop.is_empty() {
+                    return Some(file.clone());
+                }
+                let record = records.get(&file.id)?;
+                let drop = match &record.channel_analysis {
+                    Some(analysis) => analysis.redundant_channels(quiet),
+                    None if record.dual_mono == Some(true) => (1..record.channels as u16).collect(),
+                    None => Vec::new(),
+                };
+                (!drop.is_empty()).then(|| DualMono {
+                    drop,
+                    ..file.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peak(channels: u32) -> Vec<u8> {
+        let mut data = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        for c in 0..channels {
+            data.extend((c as f32 + 0.5).to_le_bytes());
+            data.extend((c * 10).to_le_bytes());
+        }
+        data
+    }
+
+    fn unknown(id: &str, data: Vec<u8>) -> MetadataChunk {
+        MetadataChunk::Unknown {
+            id: id.to_string(),
+            data,
+        }
+    }
+
+    fn write(
+        dir: &Path,
+        name: &str,
+        buffer: &AudioBuffer,
+        metadata: &metadata::Metadata,
+    ) -> PathBuf {
+        let path = dir.join(name);
+        buffer
+            .export_with_metadata(path.to_str().unwrap(), Some(metadata))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn strips_channels_losslessly_and_updates_per_channel_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let signal: Vec<f32> = (0..4800)
+            .map(|i| ((i * 7919) % 16_777_216) as f32 / 8_388_608.0 - 1.0)
+            .collect();
+        let buffer = AudioBuffer {
+            sample_rate: 48000,
+            channels: 3,
+            sample_format: SampleFormat::I24,
+            data: vec![signal.clone(), signal.clone(), vec![0.0; signal.len()]],
+        };
+        let metadata = metadata::Metadata::Wav(vec![
+            unknown("PEAK", peak(3)),
+            unknown("levl", vec![0; 16]),
+            unknown("chna", vec![0; 8]),
+            unknown("cue ", vec![0; 4]),
+        ]);
+        let path = write(dir.path(), "three.wav", &buffer, &metadata);
+
+        let analysis = analyze_channels(&path, DEFAULT_CHANNEL_TOLERANCE_DB).unwrap();
+        let drop = analysis.redundant_channels(false);
+        assert_eq!(drop, [1, 2]);
+        assert_eq!(strip_channels_from_file(&path, &drop).unwrap(), 1);
+
+        let stripped = decode_to_buffer(&path).unwrap();
+        assert_eq!(stripped.sample_format, SampleFormat::I24);
+        assert_eq!(stripped.data, [signal]);
+
+        let ids: Vec<(String, Vec<u8>)> = match metadata::Metadata::from_file(&path).unwrap() {
+            metadata::Metadata::Wav(chunks) => chunks
+                .iter()
+                .map(|chunk| (chunk.id(), chunk.data().to_vec()))
+                .collect(),
+            _ => panic!("not a WAV file"),
+        };
+        assert!(ids.contains(&("PEAK".to_string(), peak(1))));
+        assert!(ids.iter().any(|(id, _)| id == "MARK"));
+        assert!(!ids.iter().any(|(id, _)| id == "levl" || id == "chna"));
+    }
+
+    #[test]
+    fn refuses_formats_it_cannot_hold_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = AudioBuffer {
+            sample_rate: 48000,
+            channels: 2,
+            sample_format: SampleFormat::I32,
+            data: vec![vec![0.25; 480]; 2],
+        };
+        let path = write(
+            dir.path(),
+            "int32.wav",
+            &buffer,
+            &metadata::Metadata::Wav(Vec::new()),
+        );
+        assert!(decode_to_buffer(&path).is_err());
+        assert!(strip_channels_from_file(&path, &[1]).is_err());
+    }
+
+    #[test]
+    fn strips_only_files_with_redundant_channels() {
+        let analysis = |silent: Vec<u16>, identical: Vec<(u16, u16)>| ChannelAnalysis {
+            channels: 4,
+            digital_silence: silent.clone(),
+            silent,
+            identical,
+            ..Default::default()
+        };
+        let mut db = Database::default();
+        for (id, channel_analysis, dual_mono) in [
+            (1, Some(analysis(vec![3], vec![(0, 1)])), None),
+            (2, Some(analysis(vec![], vec![])), None),
+            (3, None, Some(true)),
+        ] {
+            db.records.push(FileRecord {
+                id,
+                channels: 4,
+                channel_analysis,
+                dual_mono,
+                ..Default::default()
+            });
+        }
+        let file = |id: usize, drop: Vec<u16>| DualMono {
+            id,
+            path: format!("/{}.wav", id),
+            drop,
+        };
+
+        let strips = db.channel_strips(
+            &[
+                file(1, vec![]),
+                file(2, vec![]),
+                file(3, vec![]),
+                file(4, vec![2]),
+            ],
+            false,
+        );
+        let drops: Vec<(usize, Vec<u16>)> = strips.into_iter().map(|f| (f.id, f.drop)).collect();
+        assert_eq!(drops, [(1, vec![1, 3]), (3, vec![1, 2, 3]), (4, vec![2])]);
+    }
+
+    #[test]
+    fn keeps_the_named_track_of_identical_channels() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let analysis = ChannelAnalysis {
+            channels: 4,
+            identical: vec![(0, 1), (0, 2), (1, 2)],
+            silent: vec![3],
+            digital_silence: vec![3],
+            track_names: names(&["", "Boom", "Boom copy", ""]),
+            ..Default::default()
+        };
+        assert_eq!(analysis.redundant_channels(false), [0, 2, 3]);
+
+        // Without names the lowest channel of a group stays
+        let unnamed = ChannelAnalysis {
+            track_names: Vec::new(),
+            ..analysis.clone()
+        };
+        assert_eq!(unnamed.redundant_channels(false), [1, 2, 3]);
+
+        // A file of nothing but silence keeps its named track
+        let silent = ChannelAnalysis {
+            channels: 3,
+            silent: vec![0, 1, 2],
+            digital_silence: vec![0, 1, 2],
+            identical: vec![(0, 1), (0, 2), (1, 2)],
+            track_names: names(&["", "Lav 1", ""]),
+            ..Default::default()
+        };
+        assert_eq!(silent.redundant_channels(false), [0, 2]);
+    }
+
+    #[test]
+    fn quiet_channels_are_only_stripped_on_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let signal: Vec<f32> = (0..4800).map(|i| ((i % 96) as f32 / 48.0) - 1.0).collect();
+        // Room tone around -80 dBFS, below the default tolerance but not silent
+        let hiss: Vec<f32> = (0..4800)
+            .map(|i| if i % 2 == 0 { 0.0001 } else { -0.0001 })
+            .collect();
+        let buffer = AudioBuffer {
+            sample_rate: 48000,
+            channels: 3,
+            sample_format: SampleFormat::I24,
+            data: vec![signal, hiss, vec![0.0; 4800]],
+        };
+        let path = write(
+            dir.path(),
+            "quiet.wav",
+            &buffer,
+            &metadata::Metadata::Wav(Vec::new()),
+        );
+
+        let analysis = analyze_channels(&path, DEFAULT_CHANNEL_TOLERANCE_DB).unwrap();
+        assert_eq!(analysis.silent, [1, 2]);
+        assert_eq!(analysis.digital_silence, [2]);
+        assert_eq!(analysis.redundant_channels(false), [2]);
+        assert_eq!(analysis.redundant_channels(true), [1, 2]);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of the audio alone. WAV and AIFF files hash their sample chunk straight from disk, so
+/// copies that only differ in bext, iXML, SMED or ID3 chunks match. Other formats are decoded
+/// first, which means a WAV and a FLAC of the same take do not share a checksum.
+pub fn audio_checksum(path: &Path) -> R<Arc<str>> {
+    let mut hasher = Sha256::new();
+    let file = File::open(path)?;
+    let mapped_file = unsafe { MmapOptions::new().map(&file)? };
+
+    match audio_payload(&mapped_file) {
+        Ok(payload) => {
+            hasher.update(&payload.format);
+            hasher.update(payload.samples);
+        }
+        Err(_) => {
+            let mut bytes = Vec::new();
+            let (sample_rate, channels) = for_each_decoded_packet(path, |samples, _| {
+                bytes.clear();
+                bytes.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+                hasher.update(&bytes);
+            })?;
+            hasher.update(sample_rate.to_le_bytes());
+            hasher.update(channels.to_le_bytes());
+        }
+    }
+
+    Ok(Arc::from(hex::encode(hasher.finalize()).as_str()))
+}
+
+impl FileRecord {
+    pub fn get_checksum(&mut self) -> Option<Arc<str>> {
+        match audio_checksum(&self.path) {
+            Ok(checksum) => {
+                self.checksum = Some(checksum.clone());
+                Some(checksum)
+            }
+            Err(e) => {
+                eprintln!("Failed to checksum {}: {}", self.get_filepath(), e);
+                None
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Hashes every record that has no cached checksum yet. Results are written to SCAN_TABLE by
+    /// `save_scan_state` once the search finishes.
+    pub async fn gather_checksums<E: StatusEmitter>(&mut self, app: &E) -> Result<(), String> {
+        let total_records = self
+            .records
+            .iter()
+            .filter(|record| record.checksum.is_none())
+            .count();
+        if total_records == 0 {
+            println!("No records need a checksum.");
+            return Ok(());
+        }
+        let completed = AtomicUsize::new(0);
+        let abort = self.abort.clone();
+
+        self.records
+            .par_iter_mut()
+            .filter(|record| record.checksum.is_none())
+            .for_each(|record| {
+                if abort.load(Ordering::SeqCst) || !record.path.exists() {
+                    return;
+                }
+                let new_completed = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                app.substatus("checksum", 0, record.get_filename());
+                app.status(
+                    "checksum",
+                    new_completed * 100 / total_records,
+                    &format!(
+                        "Generating Audio Checksums: ({}/{})",
+                        new_completed, total_records
+                    ),
+                );
+                let _ = record.get_checksum();
+            });
+
+        if abort.load(Ordering::SeqCst) {
+            println!("Aborting checksum scan - early exit");
+            return Err("Aborted".to_string());
+        }
+        Ok(())
+    }
+}
@@ -101,13 +101,18 @@ impl FileRecord {
 }
 
 impl Database {
-    pub async fn wave_search_chromaprint(
+    pub async fn wave_search_chromaprint<E: StatusEmitter>(
         &mut self,
         pref: &Preferences,
-        app: &AppHandle,
+        app: &E,
     ) -> Result<(), String> {
         println!("Starting Waveform Search");
-        self.gather_fingerprints(pref, app).await?;
+        match pref.waveform_search_type {
+            WaveformMatchType::Checksum => self.gather_checksums(app).await?,
+            // Landmarks are generated and cached by `landmark_match` itself
+            WaveformMatchType::Landmark => {}
+            _ => self.gather_fingerprints(pref, app).await?,
+        }
 
         match pref.waveform_search_type {
             WaveformMatchType::Subset => {
@@ -122,14 +127,22 @@ impl Database {
                 println!("Similar Match selected");
                 self.similar_match(pref, app).await?;
             }
+            WaveformMatchType::Checksum => {
+                println!("Checksum Match selected");
+                self.checksum_match(pref, app).await?;
+            }
+            WaveformMatchType::Landmark => {
+                println!("Landmark Match selected");
+                self.landmark_match(pref, app).await?;
+            }
         }
 
         Ok(())
     }
-    async fn gather_fingerprints(
+    pub async fn gather_fingerprints<E: StatusEmitter>(
         &mut self,
         pref: &Preferences,
-        app: &AppHandle,
+        app: &E,
     ) -> Result<(), String> {
         let mut batch_size: usize = pref.batch_size;
         println!("Batch size: {}", batch_size);
@@ -157,14 +170,17 @@ impl Database {
             return Err("Database connection pool not available".to_string());
         };
 
-        let mut record_ids_to_store: Vec<(usize, String)> = Vec::with_capacity(batch_size);
+        let mut record_ids_to_store: Vec<(usize, ScanState)> = Vec::with_capacity(batch_size);
+        let mut stored = Vec::new();
+        // Compare databases are read-only, their fingerprints go to the compare cache instead
+        let store = pref.store_waveforms && !self.is_compare;
 
         for chunk in self.records.chunks_mut(batch_size) {
             if self.abort.load(Ordering::SeqCst) {
                 println!("Aborting fingerprint scan - early exit");
                 return Err("Aborted".to_string());
             }
-            let local_ids: Vec<(usize, String)> = chunk
+            let local_ids: Vec<(usize, ScanState)> = chunk
                 .par_iter_mut()
                 .filter_map(|record| {
                     let path = PathBuf::from(record.get_filepath());
@@ -190,28 +206,37 @@ impl Database {
                             new_completed, total_records
                         ),
                     );
-                    let fingerprint_result = record.get_chromaprint_fingerprint();
+                    // A failed fingerprint is stored as empty and retried next time
+                    let _ = record.get_chromaprint_fingerprint();
 
-                    let fingerprint = fingerprint_result.unwrap_or("FAILED".to_string());
-
-                    Some((record.id, fingerprint))
+                    Some((record.id, record.scan_state()?))
                 })
                 .collect();
 
             record_ids_to_store.extend(local_ids);
 
-            if pref.store_waveforms && record_ids_to_store.len() >= pref.batch_size {
+            if store && record_ids_to_store.len() >= pref.batch_size {
                 // Store fingerprints in batches to avoid memory issues
-                store_fingerprints_batch_optimized(&pool, &record_ids_to_store, app).await;
-                record_ids_to_store.clear(); // Clear after storing
+                match store_scan_state(&pool, &record_ids_to_store, app).await {
+                    Ok(_) => stored.append(&mut record_ids_to_store),
+                    // Left for `save_scan_state` to retry
+                    Err(e) => {
+                        println!("Failed to store fingerprints: {}", e);
+                        record_ids_to_store.clear();
+                    }
+                }
             }
         }
 
-        if pref.store_waveforms {
+        if store {
             // Store fingerprints in batches to avoid memory issues
-            store_fingerprints_batch_optimized(&pool, &record_ids_to_store, app).await;
-            record_ids_to_store.clear(); // Clear after storing
+            match store_scan_state(&pool, &record_ids_to_store, app).await {
+                Ok(_) => stored.append(&mut record_ids_to_store),
+                Err(e) => println!("Failed to store fingerprints: {}", e),
+            }
         }
+        // Already written, so `save_scan_state` can skip them
+        self.scan_state.extend(stored);
 
         Ok(())
     }
@@ -395,7 +420,11 @@ impl Database {
     //     Ok(())
     // }
 
-    async fn subset_match(&mut self, pref: &Preferences, app: &AppHandle) -> Result<(), String> {
+    pub async fn subset_match<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), String> {
         app.substatus("subset", 0, "Starting audio subset detection...");
 
         self.records.sort_by(|a, b| {
@@ -418,10 +447,10 @@ impl Database {
             .records
             .par_iter()
             .filter_map(|record| {
-                if let Some(fp) = &record.fingerprint {
-                    if let Ok(decoded) = decode_chromaprint(fp) {
-                        return Some((record.id, decoded));
-                    }
+                if let Some(fp) = &record.fingerprint
+                    && let Ok(decoded) = decode_chromaprint(fp)
+                {
+                    return Some((record.id, decoded));
                 }
                 None
             })
@@ -462,14 +491,20 @@ impl Database {
                         None => return None,
                     };
 
-                    if is_fingerprint_subset(child_fp, parent_fp, subset_threshold) {
-                        Some(*k) // Return the key if we find a match
-                    } else {
-                        None // No match
-                    }
+                    find_fingerprint_subset(child_fp, parent_fp, subset_threshold)
+                        .map(|window| (*k, window)) // Return the key if we find a match
                 });
             match result {
-                Some(key) => {
+                Some((key, window)) => {
+                    let parent_id = self.records[key].id;
+                    self.records[i].subset = Some(SubsetOf {
+                        parent: parent_id,
+                        start: window.start as f64 * CHROMAPRINT_ITEM_SECONDS,
+                        end: (window.start + window.len) as f64 * CHROMAPRINT_ITEM_SECONDS,
+                        score: window.score,
+                    });
+                    self.records[key].join_group(parent_id, GroupRole::Keeper);
+                    self.records[i].join_group(parent_id, GroupRole::Duplicate);
                     self.records[key].algorithm.insert(Algorithm::Waveforms);
                     self.records[key].algorithm.insert(Algorithm::Keep);
                     // This is a child file
@@ -715,41 +750,62 @@ impl Database {
     //     Ok(())
     // }
 
-    async fn exact_match(&mut self, pref: &Preferences, app: &AppHandle) -> Result<(), String> {
+    pub async fn exact_match<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), String> {
         println!("Starting Exact Audio fingerprint analysis");
-        app.substatus("grouping", 0, "Grouping identical audio fingerprints...");
+        self.group_identical(pref, app, |record| {
+            record.fingerprint.clone().filter(|fp| &**fp != "FAILED")
+        });
+        app.substatus("complete", 100, "Exact Audio fingerprint analysis complete");
 
-        let records_without_fingerprints: Vec<FileRecord> = self
-            .records
-            .iter()
-            .filter(|record| {
-                record.fingerprint.is_none()
-                    || record
-                        .fingerprint
-                        .as_ref()
-                        .is_some_and(|fp| &**fp == "FAILED")
-            })
-            .cloned()
-            .collect();
+        Ok(())
+    }
+
+    pub async fn checksum_match<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), String> {
+        println!("Starting Bit-exact Audio checksum analysis");
+        self.group_identical(pref, app, |record| record.checksum.clone());
+        app.substatus(
+            "complete",
+            100,
+            "Bit-exact Audio checksum analysis complete",
+        );
+
+        Ok(())
+    }
+
+    // Groups records whose `key` is equal, records without a key are left untouched
+    fn group_identical<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+        key: impl Fn(&FileRecord) -> Option<Arc<str>>,
+    ) {
+        app.substatus("grouping", 0, "Grouping identical audio...");
 
+        let mut records_without_key: Vec<FileRecord> = Vec::new();
         let mut file_groups: HashMap<Arc<str>, Vec<FileRecord>> =
             HashMap::with_capacity(self.records.len() / 2);
 
-        // Group records by waveform
+        // Group records by key
         for (i, record) in self.records.iter().enumerate() {
             if i % RECORD_DIVISOR == 0 || i == 0 || i == self.records.len() - 1 {
                 app.substatus(
                     "grouping",
                     (i + 1) * 100 / self.records.len(),
-                    &format!("Grouping by fingerprint: {}/{}", i + 1, self.records.len()),
+                    &format!("Grouping identical audio: {}/{}", i + 1, self.records.len()),
                 );
             }
 
-            if let Some(fingerprint) = &record.fingerprint {
-                file_groups
-                    .entry(fingerprint.clone())
-                    .or_default()
-                    .push(record.clone());
+            match key(record) {
+                Some(key) => file_groups.entry(key).or_default().push(record.clone()),
+                None => records_without_key.push(record.clone()),
             }
         }
 
@@ -774,6 +830,7 @@ impl Database {
                     return records;
                 }
                 pref.sort_vec(&mut records);
+                FileRecord::set_group(&mut records);
 
                 records.iter_mut().enumerate().for_each(|(i, record)| {
                     if i > 0 {
@@ -787,15 +844,15 @@ impl Database {
             .collect();
 
         let mut final_records = processed_records;
-        final_records.extend(records_without_fingerprints);
+        final_records.extend(records_without_key);
         self.records = final_records;
-
-        app.substatus("complete", 100, "Exact Audio fingerprint analysis complete");
-
-        Ok(())
     }
 
-    async fn similar_match(&mut self, pref: &Preferences, app: &AppHandle) -> Result<(), String> {
+    pub async fn similar_match<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), String> {
         println!("Starting Similar Audio fingerprint analysis");
         let threshold = pref.similarity_threshold / 100.0;
         app.substatus("similarity", 0, "Starting similarity analysis...");
@@ -880,6 +937,7 @@ impl Database {
 
                 // Sort and mark duplicates
                 pref.sort_vec(&mut records);
+                FileRecord::set_group(&mut records);
 
                 for (j, mut record) in records.into_iter().enumerate() {
                     processed_ids.insert(record.id);
@@ -927,20 +985,19 @@ impl Database {
                             );
                         }
 
-                        if let Some(raw_fp) = &record.fingerprint {
-                            if let Ok(fp_bytes) = general_purpose::STANDARD.decode(raw_fp.as_ref())
-                            {
-                                // Decode fingerprint...
-                                let mut fp = Vec::with_capacity(fp_bytes.len() / 4);
-                                for chunk in fp_bytes.chunks_exact(4) {
-                                    if chunk.len() == 4 {
-                                        let mut array = [0u8; 4];
-                                        array.copy_from_slice(chunk);
-                                        fp.push(u32::from_le_bytes(array));
-                                    }
+                        if let Some(raw_fp) = &record.fingerprint
+                            && let Ok(fp_bytes) = general_purpose::STANDARD.decode(raw_fp.as_ref())
+                        {
+                            // Decode fingerprint...
+                            let mut fp = Vec::with_capacity(fp_bytes.len() / 4);
+                            for chunk in fp_bytes.chunks_exact(4) {
+                                if chunk.len() == 4 {
+                                    let mut array = [0u8; 4];
+                                    array.copy_from_slice(chunk);
+                                    fp.push(u32::from_le_bytes(array));
                                 }
-                                return Some(fp);
                             }
+                            return Some(fp);
                         }
                         None
                     })
@@ -953,8 +1010,12 @@ impl Database {
                     "Comparing fingerprints for similarities...",
                 );
 
-                // Second pass - build groups with similarity with progress updates
+                // Second pass - build groups with similarity with progress updates. Each record
+                // joins the group of the first earlier record it is similar to.
+                let mut index =
+                    FingerprintIndex::new(&decoded_fps, threshold, pref.fast_similarity);
                 let mut groups: HashMap<usize, Vec<(usize, Vec<u32>)>> = HashMap::new();
+                let mut group_of = vec![0; total_records];
                 let mut next_group_id = 0;
 
                 // Process in smaller batches and report progress
@@ -967,28 +1028,24 @@ impl Database {
 
                     let idx = chromaprint_records[i].id;
                     if let Some(ref fp_i) = decoded_fps[i] {
-                        // Comparison logic...
-                        let mut found_group = None;
-
-                        for (&group_id, group_members) in &groups {
-                            for (_, group_fp) in group_members {
-                                let similarity = calculate_similarity_simd(fp_i, group_fp);
-                                if similarity >= threshold {
-                                    found_group = Some(group_id);
-                                    break;
-                                }
-                            }
-                            if found_group.is_some() {
-                                break;
+                        let found = index.find_match(i, fp_i, |j| {
+                            decoded_fps[j].as_ref().is_some_and(|fp_j| {
+                                calculate_similarity_simd(fp_i, fp_j) >= threshold
+                            })
+                        });
+
+                        let group_id = match found {
+                            Some(j) => group_of[j],
+                            None => {
+                                next_group_id += 1;
+                                next_group_id - 1
                             }
-                        }
-
-                        if let Some(group_id) = found_group {
-                            groups.get_mut(&group_id).unwrap().push((idx, fp_i.clone()));
-                        } else {
-                            groups.insert(next_group_id, vec![(idx, fp_i.clone())]);
-                            next_group_id += 1;
-                        }
+                        };
+                        group_of[i] = group_id;
+                        groups
+                            .entry(group_id)
+                            .or_default()
+                            .push((idx, fp_i.clone()));
                     }
                 }
 
@@ -1047,6 +1104,7 @@ impl Database {
                                 .collect();
 
                             pref.sort_vec(&mut group_records);
+                            FileRecord::set_group(&mut group_records);
 
                             for (j, mut record) in group_records.into_iter().enumerate() {
                                 processed_ids.insert(record.id);
@@ -1066,6 +1124,7 @@ impl Database {
                                 .collect();
 
                             pref.sort_vec(&mut group_records);
+                            FileRecord::set_group(&mut group_records);
 
                             for (j, mut record) in group_records.into_iter().enumerate() {
                                 processed_ids.insert(record.id);
@@ -1258,9 +1317,23 @@ pub fn calculate_similarity(fp1: &[u32], fp2: &[u32]) -> f64 {
     best_similarity
 }
 
-fn is_fingerprint_subset(shorter_fp: &[u32], longer_fp: &[u32], threshold: f64) -> bool {
+/// Length of one Chromaprint item: 4096 sample frames with 2/3 overlap at 11025 Hz
+const CHROMAPRINT_ITEM_SECONDS: f64 = 4096.0 / 3.0 / 11025.0;
+
+// Best matching stretch of the longer fingerprint, in Chromaprint items
+struct SubsetWindow {
+    start: usize,
+    len: usize,
+    score: f64,
+}
+
+fn find_fingerprint_subset(
+    shorter_fp: &[u32],
+    longer_fp: &[u32],
+    threshold: f64,
+) -> Option<SubsetWindow> {
     if shorter_fp.is_empty() || longer_fp.is_empty() || shorter_fp.len() > longer_fp.len() {
-        return false;
+        return None;
     }
 
     // Apply a dynamic threshold based on fingerprint length
@@ -1299,7 +1372,11 @@ fn is_fingerprint_subset(shorter_fp: &[u32], longer_fp: &[u32], threshold: f64)
         (shorter_fp.len() as f64 * 0.8) as usize, // 20% shorter
     ];
 
-    let mut best_similarity = 0.0;
+    let mut best = SubsetWindow {
+        start: 0,
+        len: shorter_fp.len(),
+        score: 0.0,
+    };
 
     // Try each window size
     for &window_size in &window_sizes {
@@ -1321,35 +1398,53 @@ fn is_fingerprint_subset(shorter_fp: &[u32], longer_fp: &[u32], threshold: f64)
                 calculate_flexible_similarity(shorter_fp, window)
             };
 
-            best_similarity = f64::max(best_similarity, similarity);
+            if similarity > best.score {
+                best = SubsetWindow {
+                    start: window_start,
+                    len: window_size,
+                    score: similarity,
+                };
+            }
 
             // Early exit for strong matches
-            if best_similarity > 0.9 {
-                return true;
+            if best.score > 0.9 {
+                return Some(best);
             }
         }
     }
 
-    best_similarity >= adjusted_threshold
+    (best.score >= adjusted_threshold).then_some(best)
 }
 
 // Add this new function specifically for very short fingerprints
-fn feature_based_match(short_fp: &[u32], long_fp: &[u32], threshold: f64) -> bool {
+fn feature_based_match(short_fp: &[u32], long_fp: &[u32], threshold: f64) -> Option<SubsetWindow> {
     // Extract distinctive bit patterns from short fingerprint
     let short_patterns: Vec<u32> = short_fp.windows(2).map(|w| w[0] & w[1]).collect();
 
-    // Count how many of these patterns appear in the long fingerprint
-    let mut matches = 0;
-    for window in long_fp.windows(2) {
-        let pattern = window[0] & window[1];
-        if short_patterns.contains(&pattern) {
-            matches += 1;
-        }
-    }
+    // Mark where these patterns appear in the long fingerprint
+    let hits: Vec<bool> = long_fp
+        .windows(2)
+        .map(|window| short_patterns.contains(&(window[0] & window[1])))
+        .collect();
+    let matches = hits.iter().filter(|&&hit| hit).count();
 
     // Calculate match percentage
     let match_ratio = matches as f64 / short_patterns.len() as f64;
-    match_ratio >= threshold
+    if short_patterns.is_empty() || match_ratio < threshold {
+        return None;
+    }
+
+    // The clip is placed where its patterns are densest
+    let start = hits
+        .windows(short_patterns.len())
+        .enumerate()
+        .max_by_key(|(i, window)| (window.iter().filter(|&&hit| hit).count(), usize::MAX - i))
+        .map_or(0, |(i, _)| i);
+    Some(SubsetWindow {
+        start,
+        len: short_fp.len(),
+        score: match_ratio.min(1.0),
+    })
 }
 
 // Compare fingerprints of different lengths by aligning features
@@ -1438,108 +1533,197 @@ fn approximate_lcs(seq1: &[u16], seq2: &[u16]) -> usize {
 }
 // Helper function to decode a Chromaprint fingerprint from base64 to u32 vector
 
-async fn store_fingerprints_batch_optimized(
-    pool: &SqlitePool,
-    fingerprints: &[(usize, String)],
-    app: &AppHandle,
-) {
-    if fingerprints.is_empty() {
-        println!("No fingerprints to store");
-        return;
+impl Database {
+    /// For each record, the first record of `compare` whose Chromaprint fingerprint is at least
+    /// `threshold` similar, with its similarity. `approximate` trades completeness for speed,
+    /// see `FingerprintIndex`.
+    pub fn match_fingerprints_against(
+        &self,
+        compare: &Database,
+        threshold: f64,
+        approximate: bool,
+    ) -> Vec<Option<(usize, f64)>> {
+        let decode = |record: &FileRecord| {
+            let fp = record
+                .fingerprint
+                .as_ref()
+                .filter(|fp| !fp.is_empty() && &***fp != "FAILED" && !fp.starts_with("PCM:"))?;
+            decode_chromaprint(fp).ok()
+        };
+        // Compare records first, so every index below `offset` is a compare record
+        let mut fingerprints: Vec<Option<Vec<u32>>> =
+            compare.records.par_iter().map(decode).collect();
+        let offset = fingerprints.len();
+        fingerprints.par_extend(self.records.par_iter().map(decode));
+
+        let mut index = FingerprintIndex::new(&fingerprints, threshold, approximate);
+        (0..self.records.len())
+            .map(|i| {
+                let fp = fingerprints[offset + i].as_ref()?;
+                let similarity = |j: usize| {
+                    fingerprints[j]
+                        .as_ref()
+                        .map_or(0.0, |other| calculate_similarity_simd(fp, other))
+                };
+                let j = index
+                    .find_match(offset + i, fp, |j| j < offset && similarity(j) >= threshold)?;
+                Some((j, similarity(j)))
+            })
+            .collect()
     }
+}
 
-    println!("Storing {} fingerprints in database", fingerprints.len());
-    app.substatus(
-        "db-storage",
-        0,
-        &format!("Storing {} fingerprints in database...", fingerprints.len()),
-    );
+/// How a query file lines up with a library file, times in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QueryRelation {
+    Similar,
+    /// The query was found inside the longer library file
+    QueryInside {
+        start: f64,
+        end: f64,
+    },
+    /// The library file was found inside the longer query
+    LibraryInside {
+        start: f64,
+        end: f64,
+    },
+}
 
-    match pool.begin().await {
-        Ok(mut tx) => {
-            let _ = sqlx::query("PRAGMA journal_mode = WAL")
-                .execute(&mut *tx)
-                .await;
-            let _ = sqlx::query("PRAGMA synchronous = NORMAL")
-                .execute(&mut *tx)
-                .await;
-
-            let total = fingerprints.len();
-            let mut success_count = 0;
-            let mut error_count = 0;
-
-            for (i, (id, fingerprint)) in fingerprints.iter().enumerate() {
-                if i % 25 == 0 || i == total - 1 {
-                    app.substatus(
-                        "db-storage",
-                        (i + 1) * 100 / total,
-                        &format!("Storing fingerprints: {}/{}", i + 1, total),
-                    );
-                }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoundMatch {
+    pub id: usize,
+    pub path: Arc<str>,
+    pub filename: Arc<str>,
+    pub duration: Arc<str>,
+    pub score: f64,
+    pub relation: QueryRelation,
+}
 
-                let result = sqlx::query(&format!(
-                    "UPDATE {} SET _fingerprint = ? WHERE rowid = ?",
-                    TABLE
-                ))
-                .bind(fingerprint)
-                .bind(*id as i64)
-                .execute(&mut *tx)
-                .await;
-
-                match result {
-                    Ok(result) => {
-                        if result.rows_affected() == 0 {
-                            println!(
-                                "WARNING: No rows affected when updating fingerprints for ID {}",
-                                id
-                            );
-                        } else {
-                            success_count += 1;
-                        }
-                    }
-                    Err(e) => {
-                        println!("ERROR updating fingerprints for ID {}: {}", id, e);
-                        error_count += 1;
-                    }
-                }
-            }
-            app.substatus("db-storage", 90, "Committing changes to database...");
+// Fingerprints at least this close in length are compared whole instead of as a subset
+const QUERY_SIMILAR_RATIO: f64 = 0.9;
 
-            match tx.commit().await {
-                Ok(_) => {
-                    println!(
-                        "Transaction committed successfully: {} fingerprints updated, {} errors",
-                        success_count, error_count
-                    );
+fn compare_query(query: &[u32], stored: &[u32], threshold: f64) -> Option<(f64, QueryRelation)> {
+    let (shorter, longer) = if query.len() <= stored.len() {
+        (query, stored)
+    } else {
+        (stored, query)
+    };
+    if (shorter.len() as f64) >= longer.len() as f64 * QUERY_SIMILAR_RATIO {
+        let score = calculate_similarity(query, stored);
+        return (score >= threshold).then_some((score, QueryRelation::Similar));
+    }
 
-                    let checkpoint_result = sqlx::query("PRAGMA wal_checkpoint(FULL)")
-                        .execute(pool)
-                        .await;
+    let window = find_fingerprint_subset(shorter, longer, threshold)?;
+    let start = window.start as f64 * CHROMAPRINT_ITEM_SECONDS;
+    let end = (window.start + window.len) as f64 * CHROMAPRINT_ITEM_SECONDS;
+    let relation = if query.len() <= stored.len() {
+        QueryRelation::QueryInside { start, end }
+    } else {
+        QueryRelation::LibraryInside { start, end }
+    };
+    Some((window.score.min(1.0), relation))
+}
 
-                    if let Err(e) = checkpoint_result {
-                        println!("WARNING: Checkpoint failed: {}", e);
-                    } else {
-                        println!("Database checkpoint successful");
+impl Database {
+    /// Fingerprints any audio file, in the library or not, and ranks every record with a stored
+    /// fingerprint by how closely it matches. Records that were never fingerprinted, or whose
+    /// file changed since, are not searched.
+    pub async fn find_similar_sounds<E: StatusEmitter>(
+        &self,
+        path: &Path,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<Vec<SoundMatch>, String> {
+        app.substatus("query", 0, "Fingerprinting query file...");
+        let raw = ffcodex_lib::get_fingerprint(&path.to_string_lossy())
+            .map_err(|e| format!("Failed to fingerprint {}: {}", path.display(), e))?;
+        if raw.is_empty() || raw == "FAILED" {
+            return Err(format!("Failed to fingerprint {}", path.display()));
+        }
+        let query = decode_chromaprint(&raw)?;
+
+        let Some(pool) = self.get_pool().await else {
+            return Err("Database connection pool not available".to_string());
+        };
+        app.substatus("query", 10, "Loading stored fingerprints...");
+        ensure_scan_table(&pool)
+            .await
+            .map_err(|e| format!("Failed to load fingerprints: {}", e))?;
+        let sql = format!(
+            "SELECT a.id, m.filepath, m.duration, a.fingerprint, a.identity FROM {} a JOIN {} m ON m.rowid = a.id WHERE a.fingerprint IS NOT NULL",
+            SCAN_TABLE, TABLE
+        );
+        let rows = sqlx::query(&sql)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| format!("Failed to load fingerprints: {}", e))?;
+        if rows.is_empty() {
+            return Err("No stored fingerprints. Run a waveform search first.".to_string());
+        }
+
+        let threshold = pref.similarity_threshold / 100.0;
+        let total = rows.len();
+        let completed = AtomicUsize::new(0);
+        let stale = AtomicUsize::new(0);
+        let mut matches: Vec<SoundMatch> = rows
+            .par_iter()
+            .filter_map(|row| {
+                if self.abort.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                if done.is_multiple_of(RECORD_DIVISOR) {
+                    app.substatus(
+                        "query",
+                        10 + done * 90 / total,
+                        &format!("Comparing fingerprints: {}/{}", done, total),
+                    );
+                }
+                let path: &str = row.get(1);
+                // Same rule as `refresh_scan_state`: rows migrated without identity are trusted
+                if let Some(identity) = row.get::<Option<&str>, _>(4) {
+                    let current =
+                        FileIdentity::of(Path::new(path)).map(|i| i.hash(Path::new(path)));
+                    if current.as_deref() != Some(identity) {
+                        stale.fetch_add(1, Ordering::SeqCst);
+                        return None;
                     }
                 }
-                Err(e) => println!("ERROR: Transaction failed to commit: {}", e),
-            }
-            app.substatus(
-                "db-storage",
-                100,
-                &format!(
-                    "Database update complete: {} fingerprints stored",
-                    success_count
-                ),
-            );
-        }
-        Err(e) => {
-            println!("ERROR: Failed to start transaction: {}", e);
-            app.substatus(
-                "db-storage",
-                100,
-                &format!("ERROR: Failed to start transaction: {}", e),
-            );
+                let fingerprint: &str = row.get(3);
+                let stored = decode_chromaprint(fingerprint).ok()?;
+                let (score, relation) = compare_query(&query, &stored, threshold)?;
+                Some(SoundMatch {
+                    id: row.get::<i64, _>(0) as usize,
+                    path: Arc::from(path),
+                    filename: Arc::from(
+                        Path::new(path)
+                            .file_name()
+                            .map(|name| name.to_string_lossy())
+                            .unwrap_or_default()
+                            .as_ref(),
+                    ),
+                    duration: Arc::from(row.get::<Option<&str>, _>(2).unwrap_or_default()),
+                    score,
+                    relation,
+                })
+            })
+            .collect();
+        if self.abort.load(Ordering::SeqCst) {
+            return Err("Aborted".to_string());
         }
+
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        let stale = stale.into_inner();
+        let message = if stale > 0 {
+            format!(
+                "{} matching sounds found, skipped {} changed files. Search again to refingerprint them.",
+                matches.len(),
+                stale
+            )
+        } else {
+            format!("{} matching sounds found", matches.len())
+        };
+        app.substatus("query", 100, &message);
+        Ok(matches)
     }
 }
@@ -1,10 +1,13 @@
-pub use crate::prelude::*;
+use crate::prelude::*;
 
 use rubato::{
     Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
 };
 use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::codecs::{
+    CODEC_TYPE_PCM_F32BE, CODEC_TYPE_PCM_F32LE, CODEC_TYPE_PCM_F64BE, CODEC_TYPE_PCM_F64LE,
+    DecoderOptions,
+};
 use symphonia::core::errors::Error;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
@@ -140,6 +143,71 @@ pub fn decode_interleaved(path: &Path) -> DecodedAudioInterleaved {
     }
 }
 
+/// Streams the default track as interleaved samples in their native integer precision, so the
+/// output is bit-exact for PCM and lossless sources. Each packet comes with its channel count.
+/// Returns the sample rate and channel count of the track.
+pub fn for_each_decoded_packet(
+    path: &Path,
+    mut on_packet: impl FnMut(&[i32], usize),
+) -> R<(u32, u16)> {
+    let file = Box::new(File::open(path)?);
+    let mss = MediaSourceStream::new(file, Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("Failed to probe media format")?;
+    let mut format = probed.format;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow!("No default track found"))?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("Failed to create decoder")?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or_default();
+    let channels = track
+        .codec_params
+        .channels
+        .map(|c| c.count() as u16)
+        .unwrap_or(1);
+
+    let mut sample_buf: Option<SampleBuffer<i32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(err) => {
+                ignore_end_of_stream_error(Err(err))?;
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        // Unlike playback, a packet that fails to decode fails the whole file
+        let audio_buf = decoder.decode(&packet)?;
+        let packet_channels = audio_buf.spec().channels.count();
+        let buf = sample_buf.get_or_insert_with(|| {
+            SampleBuffer::new(audio_buf.capacity() as u64, *audio_buf.spec())
+        });
+        buf.copy_interleaved_ref(audio_buf);
+        on_packet(buf.samples(), packet_channels);
+    }
+
+    Ok((sample_rate, channels))
+}
+
 fn ignore_end_of_stream_error(result: R<(), Error>) -> R<(), Error> {
     match result {
         Err(Error::IoError(err))
@@ -271,30 +339,50 @@ pub fn decode_separated(path: &Path) -> DecodedAudioSeparated {
     }
 }
 
-pub fn decode_to_buffer(path: &Path) -> AudioBuffer {
-    let file = Box::new(File::open(path).unwrap());
+/// Decodes `path` into an `AudioBuffer` that holds every sample exactly. Formats `f32` samples
+/// can't represent, 32-bit integer and 64-bit float, are refused rather than rounded.
+pub fn decode_to_buffer(path: &Path) -> R<AudioBuffer> {
+    let file = Box::new(File::open(path)?);
     let mss = MediaSourceStream::new(file, Default::default());
-    let hint = Hint::new();
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
     let format_opts: FormatOptions = Default::default();
     let metadata_opts: MetadataOptions = Default::default();
     let decoder_opts: DecoderOptions = Default::default();
     let probed = symphonia::default::get_probe()
         .format(&hint, mss, &format_opts, &metadata_opts)
-        .unwrap();
+        .context("Failed to probe media format")?;
     let mut format = probed.format;
-    let track = format.default_track().unwrap();
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow!("No default track found"))?;
     let mut decoder = symphonia::default::get_codecs()
         .make(&track.codec_params, &decoder_opts)
-        .unwrap();
+        .context("Failed to create decoder")?;
     let track_id = track.id;
     let sample_rate = track.codec_params.sample_rate.unwrap_or(48000);
     let bit_depth = track.codec_params.bits_per_sample.unwrap_or(16);
-    let sample_format = match bit_depth {
-        8 => SampleFormat::U8,
-        16 => SampleFormat::I16,
-        24 => SampleFormat::I24,
-        32 => SampleFormat::I32,
-        _ => SampleFormat::F32,
+    let codec = track.codec_params.codec;
+    let sample_format = if [CODEC_TYPE_PCM_F32LE, CODEC_TYPE_PCM_F32BE].contains(&codec) {
+        SampleFormat::F32
+    } else if [CODEC_TYPE_PCM_F64LE, CODEC_TYPE_PCM_F64BE].contains(&codec) {
+        return Err(anyhow!(
+            "64-bit float audio can't be rewritten without loss"
+        ));
+    } else {
+        match bit_depth {
+            8 => SampleFormat::U8,
+            16 => SampleFormat::I16,
+            24 => SampleFormat::I24,
+            32 => {
+                return Err(anyhow!(
+                    "32-bit integer audio can't be rewritten without loss"
+                ));
+            }
+            bits => return Err(anyhow!("Unsupported bit depth: {}", bits)),
+        }
     };
     let channels = track
         .codec_params
@@ -320,11 +408,8 @@ pub fn decode_to_buffer(path: &Path) -> AudioBuffer {
         let packet = match format.next_packet() {
             Ok(packet) => packet,
             Err(err) => {
-                if ignore_end_of_stream_error(Err(err)).is_ok() {
-                    break;
-                } else {
-                    continue;
-                }
+                ignore_end_of_stream_error(Err(err))?;
+                break;
             }
         };
 
@@ -353,23 +438,17 @@ pub fn decode_to_buffer(path: &Path) -> AudioBuffer {
                     }
                 }
             }
-            Err(Error::DecodeError(_)) => continue,
-            Err(err) => {
-                if ignore_end_of_stream_error(Err(err)).is_ok() {
-                    break;
-                } else {
-                    continue;
-                }
-            }
+            // A skipped packet would silently shorten the audio
+            Err(err) => return Err(err.into()),
         }
     }
 
-    AudioBuffer {
+    Ok(AudioBuffer {
         data,
         sample_rate,
         channels,
         sample_format,
-    }
+    })
 }
 
 pub fn are_channels_identical(path: &Path) -> bool {
@@ -562,8 +641,8 @@ pub fn resample_interleaved(
     let mut resampled_samples = Vec::with_capacity(resampled_len * channels);
 
     for frame in 0..resampled_len {
-        for channel in 0..channels {
-            resampled_samples.push(output_frames[channel][frame]);
+        for channel in output_frames.iter().take(channels) {
+            resampled_samples.push(channel[frame]);
         }
     }
 
@@ -1,28 +1,19 @@
 use crate::prelude::*;
-pub use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+pub use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use flacenc::component::BitRepr;
 use flacenc::error::Verify;
 pub use memmap2::MmapOptions;
 pub use metadata::*;
 pub use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
-// Sample normalization constants
-const I16_MAX_F: f32 = 32767.0;
-const I24_MAX_F: f32 = 8388607.0;
-const I32_MAX_F: f32 = 2147483647.0;
-
 // Standard bit depths
 const BIT_DEPTH_8: u16 = 8;
 const BIT_DEPTH_16: u16 = 16;
 const BIT_DEPTH_24: u16 = 24;
 const BIT_DEPTH_32: u16 = 32;
 
-// Sample normalization constants
-const U8_OFFSET: f32 = 128.0;
-const U8_SCALE: f32 = 127.0;
-
-//Bit Operations
-const BYTE_MASK: i32 = 0xFF; // Mask for extracting a single byte
+// Unsigned 8 bit samples are centered on this value
+const U8_OFFSET: i32 = 128;
 
 // Format tags
 const FORMAT_PCM: u16 = 1;
@@ -40,6 +31,9 @@ const STANDARD_FMT_CHUNK_SIZE: u32 = 16;
 // Chunk Identifiers
 const FORM_CHUNK_ID: &[u8; 4] = b"FORM";
 const AIFF_FORMAT_ID: &[u8; 4] = b"AIFF";
+const AIFC_FORMAT_ID: &[u8; 4] = b"AIFC";
+const AIFC_VERSION_CHUNK_ID: &[u8; 4] = b"FVER";
+const AIFC_VERSION_1: u32 = 0xA280_5140;
 const AIF_FMT_CHUNK_ID: &[u8; 4] = b"COMM";
 const AIF_DATA_CHUNK_ID: &[u8; 4] = b"SSND";
 
@@ -59,10 +53,10 @@ pub fn get_encoder(file_path: &str) -> R<Box<dyn Encoder>> {
         .and_then(|ext| ext.to_str())
         .ok_or_else(|| anyhow::anyhow!("Invalid file extension"))?;
 
-    match extension {
+    match extension.to_lowercase().as_str() {
         "wav" => Ok(Box::new(WavCodec)),
         "flac" => Ok(Box::new(FlacCodec)),
-        // "aif" => Ok(Box::new(AifCodec)),
+        "aif" | "aiff" => Ok(Box::new(AifCodec)),
         // "mp3" => Ok(Box::new(Mp3Codec)),
         _ => Err(anyhow::anyhow!(
             "No Encoder found for extension: {}",
@@ -150,7 +144,12 @@ impl Encoder for WavCodec {
         let start_data = output.position();
 
         let mut interleaved_bytes = Vec::new();
-        encode_samples(&mut interleaved_bytes, buffer, bits_per_sample)?;
+        encode_samples::<LittleEndian, _>(
+            &mut interleaved_bytes,
+            buffer,
+            bits_per_sample,
+            U8_OFFSET,
+        )?;
 
         output.write_all(&interleaved_bytes)?;
 
@@ -184,19 +183,12 @@ impl Encoder for FlacCodec {
 
         let num_samples = buffer.data[0].len();
 
-        // Pre-calculate conversion factors outside of the loop for better performance
-        let scale_factor = match bits_per_sample {
-            8 => 127.0,
-            16 => I16_MAX_F,
-            24 => I24_MAX_F,
-            32 => I32_MAX_F,
-            _ => {
-                return Err(anyhow!(
-                    "Unsupported bit depth for FLAC encoding: {}",
-                    bits_per_sample
-                ));
-            }
-        };
+        if !matches!(bits_per_sample, 8 | 16 | 24 | 32) {
+            return Err(anyhow!(
+                "Unsupported bit depth for FLAC encoding: {}",
+                bits_per_sample
+            ));
+        }
 
         // Create the interleaved samples vector using either parallel or sequential approach
         let interleaved_samples = if num_samples > 100_000 {
@@ -214,9 +206,7 @@ impl Encoder for FlacCodec {
                     // Process samples in this chunk
                     for i in chunk_indices {
                         for ch in 0..channels {
-                            let sample = buffer.data[ch][i];
-                            let val = (sample * scale_factor).round() as i32;
-                            local_buffer.push(val);
+                            local_buffer.push(quantize(buffer.data[ch][i], bits_per_sample));
                         }
                     }
 
@@ -229,9 +219,7 @@ impl Encoder for FlacCodec {
             let mut samples = Vec::with_capacity(num_samples * channels);
             for i in 0..num_samples {
                 for ch in 0..channels {
-                    let sample = buffer.data[ch][i];
-                    let val = (sample * scale_factor).round() as i32;
-                    samples.push(val);
+                    samples.push(quantize(buffer.data[ch][i], bits_per_sample));
                 }
             }
             samples
@@ -275,7 +263,14 @@ impl Encoder for FlacCodec {
     }
 }
 
-fn encode_samples<W: Write>(out: &mut W, buffer: &AudioBuffer, bits_per_sample: u16) -> R<()> {
+/// Interleaves `buffer` into `out`. WAV stores 8 bit samples unsigned around `u8_offset`, AIFF
+/// stores them signed with an offset of 0.
+fn encode_samples<B: ByteOrder, W: Write>(
+    out: &mut W,
+    buffer: &AudioBuffer,
+    bits_per_sample: u16,
+    u8_offset: i32,
+) -> R<()> {
     // Ensure channel count doesn't exceed available data channels
     let available_channels = buffer.data.len();
     let channels = std::cmp::min(buffer.channels as usize, available_channels);
@@ -288,28 +283,21 @@ fn encode_samples<W: Write>(out: &mut W, buffer: &AudioBuffer, bits_per_sample:
             let sample = buffer.data[ch][i];
             match bits_per_sample {
                 BIT_DEPTH_8 => {
-                    let val = ((sample * U8_SCALE + U8_OFFSET).clamp(0.0, 255.0)) as u8;
-                    out.write_u8(val)?;
+                    let val = quantize(sample, BIT_DEPTH_8) + u8_offset;
+                    out.write_u8(val as u8)?;
                 }
                 BIT_DEPTH_16 => {
-                    let val = (sample.clamp(-1.0, 1.0) * I16_MAX_F) as i16;
-                    out.write_i16::<LittleEndian>(val)?;
+                    let val = quantize(sample, BIT_DEPTH_16) as i16;
+                    out.write_i16::<B>(val)?;
                 }
                 BIT_DEPTH_24 => {
-                    let val = (sample.clamp(-1.0, 1.0) * I24_MAX_F) as i32;
-                    let bytes = [
-                        (val & BYTE_MASK) as u8,
-                        ((val >> 8) & BYTE_MASK) as u8,
-                        ((val >> 16) & BYTE_MASK) as u8,
-                    ];
-                    out.write_all(&bytes)?;
+                    out.write_i24::<B>(quantize(sample, BIT_DEPTH_24))?;
                 }
                 BIT_DEPTH_32 => {
                     if buffer.sample_format == SampleFormat::F32 {
-                        out.write_f32::<LittleEndian>(sample)?;
+                        out.write_f32::<B>(sample)?;
                     } else {
-                        let val = (sample.clamp(-1.0, 1.0) * I32_MAX_F) as i32;
-                        out.write_i32::<LittleEndian>(val)?;
+                        out.write_i32::<B>(quantize(sample, BIT_DEPTH_32))?;
                     }
                 }
                 _ => return Err(anyhow!("Unsupported bit depth")),
@@ -320,6 +308,14 @@ fn encode_samples<W: Write>(out: &mut W, buffer: &AudioBuffer, bits_per_sample:
     Ok(())
 }
 
+/// Converts a float sample to an integer of `bits` width. Uses the same 2^(bits - 1) scale as the
+/// decoder, so 8, 16 and 24 bit audio survives a decode and encode unchanged.
+fn quantize(sample: f32, bits: u16) -> i32 {
+    let scale = (1i64 << (bits - 1)) as f32;
+    // Casting saturates, which covers the top of the 32 bit range
+    (sample * scale).round().clamp(-scale, scale - 1.0) as i32
+}
+
 // Helper function to get bits per sample from SampleFormat
 fn get_bits_per_sample(format: SampleFormat) -> u16 {
     match format {
@@ -334,16 +330,29 @@ pub struct AifCodec;
 impl Encoder for AifCodec {
     fn encode(&self, buffer: &AudioBuffer) -> R<Vec<u8>> {
         let mut output = Cursor::new(Vec::new());
+        // Float samples need AIFF-C, plain AIFF only holds integers
+        let is_float = buffer.sample_format == SampleFormat::F32;
+        let channels = buffer.data.len() as u16;
 
         // Write FORM header
         output.write_all(FORM_CHUNK_ID)?;
         output.write_u32::<BigEndian>(0)?; // Placeholder for file size
-        output.write_all(AIFF_FORMAT_ID)?;
+        output.write_all(if is_float {
+            AIFC_FORMAT_ID
+        } else {
+            AIFF_FORMAT_ID
+        })?;
+
+        if is_float {
+            output.write_all(AIFC_VERSION_CHUNK_ID)?;
+            output.write_u32::<BigEndian>(4)?;
+            output.write_u32::<BigEndian>(AIFC_VERSION_1)?;
+        }
 
-        // Write COMM chunk
+        // Write COMM chunk: 18 bytes, plus the compression type and its name for AIFF-C
         output.write_all(AIF_FMT_CHUNK_ID)?;
-        output.write_u32::<BigEndian>(18)?; // COMM chunk size
-        output.write_u16::<BigEndian>(buffer.channels)?;
+        output.write_u32::<BigEndian>(if is_float { 24 } else { 18 })?;
+        output.write_u16::<BigEndian>(channels)?;
 
         // Write number of sample frames
         let num_frames = if buffer.data.is_empty() {
@@ -353,20 +362,19 @@ impl Encoder for AifCodec {
         };
         output.write_u32::<BigEndian>(num_frames)?;
 
-        // Get bit depth from format
-        let bits_per_sample = match buffer.sample_format {
-            SampleFormat::F32 => 32,
-            SampleFormat::I16 => 16,
-            SampleFormat::I24 => 24,
-            SampleFormat::I32 => 32,
-            SampleFormat::U8 => 8,
-        };
+        let bits_per_sample = buffer.sample_format.bits_per_sample();
         output.write_u16::<BigEndian>(bits_per_sample)?;
 
         // Write extended 80-bit IEEE 754 format for sample rate
         // This is required by AIFF spec
         write_ieee_extended_simple(&mut output, buffer.sample_rate as f64)?;
 
+        if is_float {
+            output.write_all(b"fl32")?;
+            // Empty pascal string, padded to an even length
+            output.write_all(&[0, 0])?;
+        }
+
         // Write SSND chunk header
         output.write_all(AIF_DATA_CHUNK_ID)?;
         let ssnd_chunk_size_pos = output.position();
@@ -377,12 +385,15 @@ impl Encoder for AifCodec {
         let start_data = output.position();
 
         let mut interleaved_bytes = Vec::new();
-        encode_samples(&mut interleaved_bytes, buffer, bits_per_sample)?;
+        encode_samples::<BigEndian, _>(&mut interleaved_bytes, buffer, bits_per_sample, 0)?;
         output.write_all(&interleaved_bytes)?;
 
         let end_data = output.position();
         let data_size = (end_data - start_data) as u32;
         let ssnd_chunk_size = data_size + 8; // Add 8 bytes for offset and block size
+        if ssnd_chunk_size % 2 == 1 {
+            output.write_all(&[0])?; // padding
+        }
 
         // Fill in SSND chunk size
         let mut out = output.into_inner();
@@ -398,53 +409,22 @@ impl Encoder for AifCodec {
 }
 
 // Helper function to write IEEE 80-bit extended float (required for AIFF)
-fn write_ieee_extended<W: Write>(writer: &mut W, mut value: f64) -> R<()> {
+fn write_ieee_extended<W: Write>(writer: &mut W, value: f64) -> R<()> {
     let mut buffer = [0u8; 10];
-
-    if value < 0.0 {
-        buffer[0] = 0x80;
-        value = -value;
-    } else {
-        buffer[0] = 0;
-    }
-
-    // Handle special cases
-    if value == 0.0 {
+    if value == 0.0 || !value.is_finite() {
         return writer.write_all(&buffer).map_err(|e| anyhow::anyhow!(e));
     }
 
-    // Compute exponent and mantissa
-    let mut exponent: i16 = 16383; // Bias
+    let sign: u16 = if value < 0.0 { 0x8000 } else { 0 };
+    let value = value.abs();
 
-    // Get normalized fraction and exponent
-    let mut fraction = value;
-    while fraction >= 1.0 {
-        fraction /= 2.0;
-        exponent += 1;
-    }
-
-    while fraction < 0.5 {
-        fraction *= 2.0;
-        exponent -= 1;
-    }
+    // value = mantissa / 2^63 * 2^exponent, with the integer bit stored explicitly
+    let exponent = value.log2().floor() as i32;
+    let mantissa = (value / 2f64.powi(exponent) * 2f64.powi(63)) as u64;
+    let biased = (exponent + 16383) as u16 | sign;
 
-    // Convert to fixed point mantissa
-    fraction *= 2.0; // Shift left to get 1.fraction
-    let mantissa: u64 = ((fraction - 1.0) * 9007199254740992.0) as u64; // 2^53, corrected to subtract implicit 1
-
-    // Fill buffer
-    buffer[0] |= ((exponent >> 8) & 0x7F) as u8;
-    buffer[1] = (exponent & 0xFF) as u8;
-
-    // Fill the mantissa - ensure correct byte order (big endian)
-    buffer[2] = ((mantissa >> 56) & 0xFF) as u8;
-    buffer[3] = ((mantissa >> 48) & 0xFF) as u8;
-    buffer[4] = ((mantissa >> 40) & 0xFF) as u8;
-    buffer[5] = ((mantissa >> 32) & 0xFF) as u8;
-    buffer[6] = ((mantissa >> 24) & 0xFF) as u8;
-    buffer[7] = ((mantissa >> 16) & 0xFF) as u8;
-    buffer[8] = ((mantissa >> 8) & 0xFF) as u8;
-    buffer[9] = (mantissa & 0xFF) as u8;
+    buffer[0..2].copy_from_slice(&biased.to_be_bytes());
+    buffer[2..10].copy_from_slice(&mantissa.to_be_bytes());
 
     writer.write_all(&buffer).map_err(|e| anyhow::anyhow!(e))
 }
@@ -0,0 +1,273 @@
+use crate::prelude::*;
+
+/// Fingerprint prefixes, in Chromaprint items (about 8 per second), that get their own bands.
+/// Prints are compared over the shorter of the two, so a pair meets in the longest tier both
+/// prints cover.
+const TIERS: [usize; 2] = [8, 64];
+const BANDS_PER_TIER: usize = 48;
+/// Chance that a pair right at the threshold shares no band, assuming its bit errors are spread
+/// evenly. Bands only sample the first `tier` items while the similarity covers the whole
+/// overlap, so pairs whose differences bunch up at the start are missed more often.
+const MISS_RATE: f64 = 0.001;
+/// Narrower bands collide with nearly everything and save no comparisons
+const MIN_BAND_BITS: usize = 8;
+/// Smaller searches are compared exhaustively, which is fast enough and exact
+pub const INDEX_MIN_RECORDS: usize = 2000;
+
+struct Band {
+    tier: usize,
+    bits: Vec<(usize, u32)>, // (item, bit) sampled from the first `tier` items
+    buckets: Vec<(u32, u32)>, // (key, record) sorted by key, then record
+}
+
+impl Band {
+    fn key(&self, fp: &[u32]) -> u32 {
+        self.bits
+            .iter()
+            .enumerate()
+            .fold(0, |key, (n, &(item, bit))| {
+                key | (((fp[item] >> bit) & 1) << n)
+            })
+    }
+}
+
+/// Picks the earlier records worth running the full similarity against. By default every earlier
+/// record is a candidate, which is exact but still compares every pair. Lengths and durations
+/// can't narrow that down, because the similarity only covers the overlap of the shorter print,
+/// so a short clip can match the start of a file of any length.
+///
+/// With `approximate` set, large searches use bit sampling LSH instead: two prints only become
+/// candidates when they agree on every sampled bit of at least one band, and the band width is
+/// derived from the threshold so pairs at the threshold collide with probability
+/// `1 - MISS_RATE`. That is much faster on big libraries but can miss matches, and finds some
+/// earlier match rather than the first one.
+pub struct FingerprintIndex {
+    lengths: Vec<usize>,
+    bands: Vec<Band>,
+    short: Vec<usize>, // prints shorter than every tier, compared against everything
+    exhaustive: bool,
+    seen: Vec<usize>,
+}
+
+impl FingerprintIndex {
+    pub fn new(fingerprints: &[Option<Vec<u32>>], threshold: f64, approximate: bool) -> Self {
+        let lengths: Vec<usize> = fingerprints
+            .iter()
+            .map(|fp| fp.as_ref().map_or(0, |fp| fp.len()))
+            .collect();
+        let width = band_width(threshold);
+        let mut index = Self {
+            short: (0..lengths.len())
+                .filter(|&i| lengths[i] > 0 && lengths[i] < TIERS[0])
+                .collect(),
+            lengths,
+            bands: Vec::new(),
+            exhaustive: !approximate
+                || fingerprints.len() < INDEX_MIN_RECORDS
+                || width < MIN_BAND_BITS,
+            seen: vec![usize::MAX; fingerprints.len()],
+        };
+        if index.exhaustive {
+            return index;
+        }
+
+        // Fixed seed so the same library always produces the same groups
+        let mut state = 0;
+        for tier in TIERS {
+            for _ in 0..BANDS_PER_TIER {
+                let mut bits = Vec::with_capacity(width);
+                while bits.len() < width {
+                    let r = splitmix64(&mut state);
+                    let sample = ((r % tier as u64) as usize, ((r >> 32) % 32) as u32);
+                    if !bits.contains(&sample) {
+                        bits.push(sample);
+                    }
+                }
+                index.bands.push(Band {
+                    tier,
+                    bits,
+                    buckets: Vec::new(),
+                });
+            }
+        }
+
+        index.bands.par_iter_mut().for_each(|band| {
+            band.buckets = fingerprints
+                .iter()
+                .enumerate()
+                .filter_map(|(i, fp)| {
+                    let fp = fp.as_ref().filter(|fp| fp.len() >= band.tier)?;
+                    Some((band.key(fp), i as u32))
+                })
+                .collect();
+            band.buckets.sort_unstable();
+        });
+        println!(
+            "Built fingerprint index: {} bands of {} bits",
+            index.bands.len(),
+            width
+        );
+        index
+    }
+
+    /// Returns an earlier record (`< i`) for which `matches` holds. Exhaustive searches return
+    /// the first one, approximate ones only ask about candidates that share a band with `fp`.
+    pub fn find_match(
+        &mut self,
+        i: usize,
+        fp: &[u32],
+        matches: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        if self.exhaustive || self.lengths[i] < TIERS[0] {
+            return (0..i).find(|&j| matches(j));
+        }
+        if let Some(&j) = self
+            .short
+            .iter()
+            .take_while(|&&j| j < i)
+            .find(|&&j| matches(j))
+        {
+            return Some(j);
+        }
+
+        for band in self.bands.iter().filter(|b| self.lengths[i] >= b.tier) {
+            let key = band.key(fp);
+            let start = band.buckets.partition_point(|&(k, _)| k < key);
+            for &(k, j) in &band.buckets[start..] {
+                let j = j as usize;
+                if k != key || j >= i {
+                    break;
+                }
+                if self.seen[j] == i {
+                    continue;
+                }
+                self.seen[j] = i;
+                if matches(j) {
+                    return Some(j);
+                }
+            }
+        }
+        None
+    }
+}
+
+// Widest band that still keeps the miss rate at the threshold below MISS_RATE
+fn band_width(threshold: f64) -> usize {
+    if threshold >= 1.0 {
+        return 32;
+    }
+    if threshold <= 0.0 {
+        return 0;
+    }
+    let per_band = 1.0 - MISS_RATE.powf(1.0 / BANDS_PER_TIER as f64);
+    (per_band.ln() / threshold.ln()).floor().clamp(0.0, 32.0) as usize
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f64 = 0.8;
+
+    // Same measure as `calculate_similarity_simd`: matching bits over the shorter print
+    fn similarity(a: &[u32], b: &[u32]) -> f64 {
+        let len = a.len().min(b.len());
+        let differing: u32 = a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum();
+        1.0 - differing as f64 / (len * 32) as f64
+    }
+
+    // Random prints of 4 to 200 items, every fifth one a copy of an earlier print with 2 to 12%
+    // of its bits flipped, cut to a random length
+    fn library(count: usize) -> Vec<Option<Vec<u32>>> {
+        let mut state = 42;
+        let mut prints: Vec<Option<Vec<u32>>> = Vec::with_capacity(count);
+        for i in 0..count {
+            let len = 4 + (splitmix64(&mut state) % 197) as usize;
+            let print = if i > 0 && i % 5 == 0 {
+                let source = prints[(splitmix64(&mut state) % i as u64) as usize]
+                    .clone()
+                    .unwrap();
+                let flip_rate = 2 + splitmix64(&mut state) % 11;
+                source
+                    .iter()
+                    .take(len.max(4))
+                    .map(|&item| {
+                        (0..32).fold(item, |item, bit| {
+                            if splitmix64(&mut state) % 100 < flip_rate {
+                                item ^ (1 << bit)
+                            } else {
+                                item
+                            }
+                        })
+                    })
+                    .collect()
+            } else {
+                (0..len).map(|_| splitmix64(&mut state) as u32).collect()
+            };
+            prints.push(Some(print));
+        }
+        prints
+    }
+
+    fn brute_force(prints: &[Option<Vec<u32>>]) -> Vec<Option<usize>> {
+        (0..prints.len())
+            .map(|i| {
+                let fp = prints[i].as_ref().unwrap();
+                (0..i).find(|&j| similarity(fp, prints[j].as_ref().unwrap()) >= THRESHOLD)
+            })
+            .collect()
+    }
+
+    fn search(prints: &[Option<Vec<u32>>], approximate: bool) -> Vec<Option<usize>> {
+        let mut index = FingerprintIndex::new(prints, THRESHOLD, approximate);
+        (0..prints.len())
+            .map(|i| {
+                let fp = prints[i].as_ref().unwrap();
+                index.find_match(i, fp, |j| {
+                    similarity(fp, prints[j].as_ref().unwrap()) >= THRESHOLD
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_search_matches_brute_force() {
+        let prints = library(INDEX_MIN_RECORDS + 500);
+        assert_eq!(search(&prints, false), brute_force(&prints));
+    }
+
+    #[test]
+    fn approximate_search_finds_the_copies() {
+        let prints = library(INDEX_MIN_RECORDS + 500);
+        let expected = brute_force(&prints);
+        let found = search(&prints, true);
+
+        let mut missed = 0;
+        for (i, (expected, found)) in expected.iter().zip(&found).enumerate() {
+            if let Some(j) = found {
+                // Anything found is a real match, though not necessarily the first one
+                assert!(*j < i);
+                let fp = prints[i].as_ref().unwrap();
+                assert!(similarity(fp, prints[*j].as_ref().unwrap()) >= THRESHOLD);
+            } else if expected.is_some() {
+                missed += 1;
+            }
+        }
+        let matched = expected.iter().flatten().count();
+        assert!(matched >= INDEX_MIN_RECORDS / 5);
+        assert!(
+            missed * 100 <= matched,
+            "missed {} of {} matches",
+            missed,
+            matched
+        );
+    }
+}
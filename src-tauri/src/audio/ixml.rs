@@ -0,0 +1,598 @@
+use crate::prelude::*;
+use quick_xml::escape::partial_escape;
+use quick_xml::events::{BytesCData, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::io::Write;
+
+/// A node of a parsed XML document. `Raw` holds markup that is written back exactly as read:
+/// the declaration, processing instructions and doctype.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+    CData(String),
+    Comment(String),
+    Raw(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+    /// Read as `<NAME/>` rather than `<NAME></NAME>`, which recorders use interchangeably
+    pub self_closing: bool,
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        XmlElement {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut XmlElement> {
+        self.children.iter_mut().find_map(|node| match node {
+            XmlNode::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    /// The named child, added at the end if there isn't one yet.
+    pub fn child_or_insert(&mut self, name: &str) -> &mut XmlElement {
+        if self.child(name).is_none() {
+            self.children.push(XmlNode::Element(XmlElement::new(name)));
+        }
+        self.child_mut(name).expect("child was just added")
+    }
+
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                XmlNode::Text(text) | XmlNode::CData(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    /// Replaces the text of the element, leaving child elements alone.
+    pub fn set_text(&mut self, text: &str) {
+        self.children
+            .retain(|node| !matches!(node, XmlNode::Text(_) | XmlNode::CData(_)));
+        self.children.insert(0, XmlNode::Text(text.to_string()));
+    }
+
+    fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(XmlElement::text)
+            .filter(|text| !text.is_empty())
+    }
+
+    fn write<W: Write>(&self, writer: &mut Writer<W>) -> R<()> {
+        let mut start = BytesStart::new(self.name.as_str());
+        for (key, value) in &self.attributes {
+            start.push_attribute((key.as_str(), value.as_str()));
+        }
+        if self.children.is_empty() && self.self_closing {
+            writer.write_event(Event::Empty(start))?;
+            return Ok(());
+        }
+        writer.write_event(Event::Start(start))?;
+        write_nodes(writer, &self.children)?;
+        writer.write_event(Event::End(BytesStart::new(self.name.as_str()).to_end()))?;
+        Ok(())
+    }
+}
+
+fn write_nodes<W: Write>(writer: &mut Writer<W>, nodes: &[XmlNode]) -> R<()> {
+    for node in nodes {
+        match node {
+            XmlNode::Element(element) => element.write(writer)?,
+            // Quotes only need escaping in attributes, and recorders leave them as they are
+            XmlNode::Text(text) => {
+                writer.write_event(Event::Text(BytesText::from_escaped(partial_escape(text))))?
+            }
+            XmlNode::CData(text) => writer.write_event(Event::CData(BytesCData::new(text)))?,
+            XmlNode::Comment(text) => {
+                writer.write_event(Event::Comment(BytesText::from_escaped(text)))?
+            }
+            XmlNode::Raw(raw) => writer.get_mut().write_all(raw.as_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+/// One entry of the iXML TRACK_LIST
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IXmlTrack {
+    /// Recorder input the track came from, counted from 1
+    pub channel_index: Option<u16>,
+    /// Position of the track in the file's audio, counted from 1
+    pub interleave_index: Option<u16>,
+    pub name: String,
+    pub function: String,
+}
+
+/// A parsed iXML document. Everything read is kept, so elements this type has no accessor for
+/// survive an edit and `to_xml`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IXml {
+    pub nodes: Vec<XmlNode>,
+}
+
+impl IXml {
+    pub fn parse(xml: &str) -> R<Self> {
+        let mut reader = Reader::from_str(xml);
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut nodes = Vec::new();
+
+        loop {
+            let node = match reader.read_event()? {
+                Event::Start(start) => {
+                    stack.push(read_element(&start));
+                    continue;
+                }
+                Event::End(_) => {
+                    let element = stack
+                        .pop()
+                        .ok_or_else(|| anyhow!("Unbalanced end tag in iXML"))?;
+                    XmlNode::Element(element)
+                }
+                Event::Empty(start) => XmlNode::Element(XmlElement {
+                    self_closing: true,
+                    ..read_element(&start)
+                }),
+                Event::Text(text) => XmlNode::Text(
+                    text.unescape()
+                        .map(|text| text.to_string())
+                        .unwrap_or_else(|_| String::from_utf8_lossy(&text).to_string()),
+                ),
+                Event::CData(text) => XmlNode::CData(String::from_utf8_lossy(&text).to_string()),
+                Event::Comment(text) => {
+                    XmlNode::Comment(String::from_utf8_lossy(&text).to_string())
+                }
+                Event::Decl(decl) => {
+                    XmlNode::Raw(format!("<?{}?>", String::from_utf8_lossy(&decl)))
+                }
+                Event::PI(text) => XmlNode::Raw(format!("<?{}?>", String::from_utf8_lossy(&text))),
+                Event::DocType(text) => {
+                    XmlNode::Raw(format!("<!DOCTYPE {}>", String::from_utf8_lossy(&text)))
+                }
+                Event::Eof => break,
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => nodes.push(node),
+            }
+        }
+
+        if !stack.is_empty() {
+            return Err(anyhow!("Unclosed element in iXML"));
+        }
+        let ixml = IXml { nodes };
+        if ixml.root().is_none() {
+            return Err(anyhow!("iXML has no root element"));
+        }
+        Ok(ixml)
+    }
+
+    pub fn to_xml(&self) -> R<String> {
+        let mut writer = Writer::new(Vec::new());
+        write_nodes(&mut writer, &self.nodes)?;
+        Ok(String::from_utf8(writer.into_inner())?)
+    }
+
+    /// The BWFXML element
+    pub fn root(&self) -> Option<&XmlElement> {
+        self.nodes.iter().find_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn root_mut(&mut self) -> Option<&mut XmlElement> {
+        self.nodes.iter_mut().find_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Text of a top level element such as PROJECT or TAPE
+    pub fn value(&self, name: &str) -> Option<String> {
+        self.root()?.child_text(name)
+    }
+
+    /// Sets a top level element, adding it if the document doesn't have it yet.
+    pub fn set_value(&mut self, name: &str, value: &str) -> R<()> {
+        let root = self
+            .root_mut()
+            .ok_or_else(|| anyhow!("iXML has no root element"))?;
+        root.child_or_insert(name).set_text(value);
+        Ok(())
+    }
+
+    pub fn project(&self) -> Option<String> {
+        self.value("PROJECT")
+    }
+
+    pub fn scene(&self) -> Option<String> {
+        self.value("SCENE")
+    }
+
+    pub fn take(&self) -> Option<String> {
+        self.value("TAKE")
+    }
+
+    pub fn note(&self) -> Option<String> {
+        self.value("NOTE")
+    }
+
+    pub fn tracks(&self) -> Vec<IXmlTrack> {
+        let Some(list) = self.root().and_then(|root| root.child("TRACK_LIST")) else {
+            return Vec::new();
+        };
+        list.elements()
+            .filter(|element| element.name == "TRACK")
+            .map(|track| IXmlTrack {
+                channel_index: track
+                    .child_text("CHANNEL_INDEX")
+                    .and_then(|i| i.parse().ok()),
+                interleave_index: track
+                    .child_text("INTERLEAVE_INDEX")
+                    .and_then(|i| i.parse().ok()),
+                name: track.child_text("NAME").unwrap_or_default(),
+                function: track.child_text("FUNCTION").unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Track names by channel, numbered from 0 like the rest of the channel code. Channels
+    /// without a named track are empty.
+    pub fn track_names(&self, channels: u16) -> Vec<String> {
+        let mut names = vec![String::new(); channels as usize];
+        for (position, track) in self.tracks().into_iter().enumerate() {
+            // Files without INTERLEAVE_INDEX list their tracks in channel order
+            let channel = track
+                .interleave_index
+                .map_or(position, |i| (i as usize).saturating_sub(1));
+            if let Some(name) = names.get_mut(channel) {
+                *name = track.name;
+            }
+        }
+        names
+    }
+
+    /// Removes the TRACK_LIST entries of the channels in `drop`, numbered from 0, and renumbers
+    /// the INTERLEAVE_INDEX of the tracks that follow them.
+    pub fn remove_tracks(&mut self, drop: &[u16]) {
+        let Some(list) = self
+            .root_mut()
+            .and_then(|root| root.child_mut("TRACK_LIST"))
+        else {
+            return;
+        };
+        let mut position = 0;
+        let mut kept = 0;
+        let mut children = Vec::with_capacity(list.children.len());
+        for node in std::mem::take(&mut list.children) {
+            let mut track = match node {
+                XmlNode::Element(element) if element.name == "TRACK" => element,
+                other => {
+                    children.push(other);
+                    continue;
+                }
+            };
+            let interleave = track
+                .child_text("INTERLEAVE_INDEX")
+                .and_then(|i| i.parse::<u16>().ok());
+            let channel = interleave.map_or(position, |i| i.saturating_sub(1));
+            position += 1;
+            if drop.contains(&channel) {
+                // Take the indentation in front of the track with it
+                if matches!(children.last(), Some(XmlNode::Text(text)) if text.trim().is_empty()) {
+                    children.pop();
+                }
+                continue;
+            }
+            if interleave.is_some() {
+                let shift = drop.iter().filter(|&&c| c < channel).count() as u16;
+                track
+                    .child_or_insert("INTERLEAVE_INDEX")
+                    .set_text(&(channel + 1 - shift).to_string());
+            }
+            kept += 1;
+            children.push(XmlNode::Element(track));
+        }
+        list.children = children;
+        if let Some(count) = list.child_mut("TRACK_COUNT") {
+            count.set_text(&kept.to_string());
+        }
+    }
+
+    /// Fields of the USER block. Recorders write it either as child elements or as text with
+    /// one `KEY=VALUE` or `KEY: VALUE` pair per line; both are read.
+    pub fn user_fields(&self) -> Vec<(String, String)> {
+        self.block_fields("USER")
+    }
+
+    /// Fields of a top level block such as USER or a vendor's own block.
+    pub fn block_fields(&self, name: &str) -> Vec<(String, String)> {
+        let Some(block) = self.root().and_then(|root| root.child(name)) else {
+            return Vec::new();
+        };
+        let mut fields: Vec<(String, String)> = block
+            .elements()
+            .map(|element| (element.name.clone(), element.text()))
+            .collect();
+        for line in block.text().lines() {
+            if let Some((key, value)) = line.split_once('=').or_else(|| line.split_once(':')) {
+                let key = key.trim();
+                if !key.is_empty() {
+                    fields.push((key.to_string(), value.trim().to_string()));
+                }
+            }
+        }
+        fields
+    }
+
+    /// Every element that holds text, keyed by its path below the root, e.g.
+    /// `TRACK_LIST.TRACK.NAME`.
+    pub fn text_fields(&self) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        if let Some(root) = self.root() {
+            for element in root.elements() {
+                collect_text_fields(element, &element.name, &mut fields);
+            }
+        }
+        fields
+    }
+}
+
+fn collect_text_fields(element: &XmlElement, path: &str, fields: &mut Vec<(String, String)>) {
+    let text = element.text();
+    if !text.is_empty() {
+        fields.push((path.to_string(), text));
+    }
+    for child in element.elements() {
+        collect_text_fields(child, &format!("{}.{}", path, child.name), fields);
+    }
+}
+
+fn read_element(start: &BytesStart) -> XmlElement {
+    XmlElement {
+        name: String::from_utf8_lossy(start.name().as_ref()).to_string(),
+        attributes: start
+            .attributes()
+            .flatten()
+            .map(|attribute| {
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+                let value = attribute
+                    .unescape_value()
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|_| String::from_utf8_lossy(&attribute.value).to_string());
+                (key, value)
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Reads just the iXML chunk of a WAV or AIFF file, without extracting the rest of its metadata.
+pub fn read_ixml(path: &Path) -> R<Option<IXml>> {
+    let file = fs::File::open(path)?;
+    let mapped_file = unsafe { MmapOptions::new().map(&file)? };
+    let (_, chunks) = walk_chunks(&mapped_file)?;
+    chunks
+        .iter()
+        .find(|(id, _)| id == b"iXML")
+        .map(|(_, data)| IXml::parse(&String::from_utf8_lossy(data)))
+        .transpose()
+}
+
+impl metadata::Metadata {
+    /// The parsed iXML chunk, if there is one and it is well formed.
+    pub fn ixml(&self) -> Option<IXml> {
+        match self {
+            Self::Wav(chunks) | Self::Aiff(chunks) => chunks.iter().find_map(|chunk| match chunk {
+                MetadataChunk::IXml(xml) => IXml::parse(xml).ok(),
+                _ => None,
+            }),
+            Self::Flac(_) => None,
+        }
+    }
+
+    /// Replaces the iXML chunk, or adds one if there is none.
+    pub fn set_ixml(&mut self, ixml: &IXml) -> R<()> {
+        let chunks = match self {
+            Self::Wav(chunks) | Self::Aiff(chunks) => chunks,
+            Self::Flac(_) => return Err(anyhow!("FLAC files have no iXML chunk")),
+        };
+        let xml = ixml.to_xml()?;
+        match chunks
+            .iter_mut()
+            .find(|chunk| matches!(chunk, MetadataChunk::IXml(_)))
+        {
+            Some(chunk) => *chunk = MetadataChunk::IXml(xml),
+            None => chunks.push(MetadataChunk::IXml(xml)),
+        }
+        Ok(())
+    }
+
+    /// Drops the TRACK_LIST entries of removed channels, see `IXml::remove_tracks`, and the
+    /// PEAK entries of those channels. Chunks laid out per channel that can't be updated (levl
+    /// envelopes and ADM chna/axml) are dropped, as is ds64, which only describes the old data.
+    /// iXML chunks that don't parse are left alone.
+    pub fn remove_tracks(&mut self, drop: &[u16]) -> R<()> {
+        if let Some(mut ixml) = self.ixml() {
+            ixml.remove_tracks(drop);
+            self.set_ixml(&ixml)?;
+        }
+        if let Self::Wav(chunks) | Self::Aiff(chunks) = self {
+            chunks.retain_mut(|chunk| match chunk {
+                MetadataChunk::Unknown { id, data } => match id.as_str() {
+                    "PEAK" => remove_peak_channels(data, drop),
+                    "levl" | "chna" | "axml" | "ds64" => false,
+                    _ => true,
+                },
+                _ => true,
+            });
+        }
+        Ok(())
+    }
+}
+
+// PEAK is a version and timestamp followed by a value and position per channel, 8 bytes each.
+// Returns false when the chunk doesn't have that layout and is better dropped.
+fn remove_peak_channels(data: &mut Vec<u8>, drop: &[u16]) -> bool {
+    const HEADER: usize = 8;
+    const ENTRY: usize = 8;
+    if data.len() < HEADER || !(data.len() - HEADER).is_multiple_of(ENTRY) {
+        return false;
+    }
+    let entries: Vec<u8> = data[HEADER..]
+        .chunks_exact(ENTRY)
+        .enumerate()
+        .filter(|(channel, _)| !drop.contains(&(*channel as u16)))
+        .flat_map(|(_, entry)| entry.to_vec())
+        .collect();
+    data.truncate(HEADER);
+    data.extend(entries);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Laid out the way Sound Devices recorders write it
+    const SOUND_DEVICES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<BWFXML>
+	<IXML_VERSION>1.61</IXML_VERSION>
+	<PROJECT>Night Exteriors</PROJECT>
+	<SCENE>42A</SCENE>
+	<TAKE>3</TAKE>
+	<TAPE>231017</TAPE>
+	<CIRCLED>FALSE</CIRCLED>
+	<NOTE>Wind gusts &amp; traffic, director's pick</NOTE>
+	<SPEED>
+		<MASTER_SPEED>24000/1001</MASTER_SPEED>
+		<TIMECODE_RATE>24000/1001</TIMECODE_RATE>
+		<TIMECODE_FLAG>NDF</TIMECODE_FLAG>
+		<FILE_SAMPLE_RATE>48000</FILE_SAMPLE_RATE>
+		<TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO>1978416000</TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO>
+	</SPEED>
+	<HISTORY>
+		<ORIGINAL_FILENAME>42A_T03.WAV</ORIGINAL_FILENAME>
+		<PARENT_FILENAME></PARENT_FILENAME>
+	</HISTORY>
+	<TRACK_LIST>
+		<TRACK_COUNT>3</TRACK_COUNT>
+		<TRACK>
+			<CHANNEL_INDEX>1</CHANNEL_INDEX>
+			<INTERLEAVE_INDEX>1</INTERLEAVE_INDEX>
+			<NAME>Boom</NAME>
+			<FUNCTION></FUNCTION>
+		</TRACK>
+		<TRACK>
+			<CHANNEL_INDEX>2</CHANNEL_INDEX>
+			<INTERLEAVE_INDEX>2</INTERLEAVE_INDEX>
+			<NAME>Lav 1</NAME>
+			<FUNCTION></FUNCTION>
+		</TRACK>
+		<TRACK>
+			<CHANNEL_INDEX>4</CHANNEL_INDEX>
+			<INTERLEAVE_INDEX>3</INTERLEAVE_INDEX>
+			<NAME>Lav 2</NAME>
+			<FUNCTION></FUNCTION>
+		</TRACK>
+	</TRACK_LIST>
+	<USER>MIC_1=MKH50
+MIC_2=COS-11D
+</USER>
+</BWFXML>
+"#;
+
+    // Zaxcom style: CRLF line ends, self-closing empty elements, no INTERLEAVE_INDEX, and a
+    // vendor block
+    const ZAXCOM: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n<BWFXML>\r\n  \
+        <IXML_VERSION>1.5</IXML_VERSION>\r\n  <PROJECT>Pilot</PROJECT>\r\n  \
+        <SCENE>7</SCENE>\r\n  <TAKE>12</TAKE>\r\n  <NOTE/>\r\n  <TRACK_LIST>\r\n    \
+        <TRACK_COUNT>2</TRACK_COUNT>\r\n    <TRACK><CHANNEL_INDEX>1</CHANNEL_INDEX>\
+        <NAME>MIX L</NAME><FUNCTION>MIX-L</FUNCTION></TRACK>\r\n    \
+        <TRACK><CHANNEL_INDEX>2</CHANNEL_INDEX><NAME>MIX R</NAME><FUNCTION>MIX-R</FUNCTION>\
+        </TRACK>\r\n  </TRACK_LIST>\r\n  <ZAXCOM>\r\n    <SERIAL>NOMAD-0042</SERIAL>\r\n    \
+        <ZAXNET value=\"on\"/>\r\n  </ZAXCOM>\r\n</BWFXML>\r\n";
+
+    #[test]
+    fn recorder_ixml_round_trips() {
+        for xml in [SOUND_DEVICES, ZAXCOM] {
+            let ixml = IXml::parse(xml).unwrap();
+            assert_eq!(ixml.to_xml().unwrap(), xml);
+        }
+    }
+
+    #[test]
+    fn reads_recorder_fields() {
+        let ixml = IXml::parse(SOUND_DEVICES).unwrap();
+        assert_eq!(ixml.project().as_deref(), Some("Night Exteriors"));
+        assert_eq!(ixml.scene().as_deref(), Some("42A"));
+        assert_eq!(ixml.take().as_deref(), Some("3"));
+        assert_eq!(
+            ixml.note().as_deref(),
+            Some("Wind gusts & traffic, director's pick")
+        );
+        assert_eq!(ixml.track_names(3), ["Boom", "Lav 1", "Lav 2"]);
+        assert_eq!(ixml.tracks()[2].channel_index, Some(4));
+        assert_eq!(
+            ixml.user_fields(),
+            [
+                ("MIC_1".to_string(), "MKH50".to_string()),
+                ("MIC_2".to_string(), "COS-11D".to_string())
+            ]
+        );
+
+        let ixml = IXml::parse(ZAXCOM).unwrap();
+        assert_eq!(ixml.note(), None);
+        assert_eq!(ixml.track_names(2), ["MIX L", "MIX R"]);
+        assert_eq!(
+            ixml.block_fields("ZAXCOM")[0],
+            ("SERIAL".to_string(), "NOMAD-0042".to_string())
+        );
+    }
+
+    #[test]
+    fn edits_leave_the_rest_of_the_document_alone() {
+        let mut ixml = IXml::parse(SOUND_DEVICES).unwrap();
+        ixml.set_value("NOTE", "Clean").unwrap();
+        ixml.remove_tracks(&[1]);
+        let xml = ixml.to_xml().unwrap();
+
+        let expected = SOUND_DEVICES
+            .replace("Wind gusts &amp; traffic, director's pick", "Clean")
+            .replace("<TRACK_COUNT>3<", "<TRACK_COUNT>2<")
+            .replace(
+                "\t\t<TRACK>\n\t\t\t<CHANNEL_INDEX>2</CHANNEL_INDEX>\n\t\t\t\
+                 <INTERLEAVE_INDEX>2</INTERLEAVE_INDEX>\n\t\t\t<NAME>Lav 1</NAME>\n\t\t\t\
+                 <FUNCTION></FUNCTION>\n\t\t</TRACK>\n",
+                "",
+            )
+            .replace("<INTERLEAVE_INDEX>3<", "<INTERLEAVE_INDEX>2<");
+        assert_eq!(xml, expected);
+        assert_eq!(ixml.track_names(2), ["Boom", "Lav 2"]);
+    }
+}
@@ -0,0 +1,536 @@
+use crate::prelude::*;
+use rustfft::{FftPlanner, num_complex::Complex};
+
+/// Landmark hashes per record, stored with the `FileIdentity` hash of the file they came from.
+pub const LANDMARK_TABLE: &str = "smdbc_landmarks";
+
+const SAMPLE_RATE: u32 = 11025;
+const WINDOW_SIZE: usize = 1024;
+const HOP_SIZE: usize = 512;
+const FRAME_SECONDS: f64 = HOP_SIZE as f64 / SAMPLE_RATE as f64;
+/// Upper edges, in FFT bins, of the frequency bands peaks are picked from
+const BANDS: [usize; 6] = [10, 20, 40, 80, 160, 511];
+/// A peak has to be the loudest in its band this many frames either side
+const PEAK_NEIGHBOURHOOD: usize = 2;
+/// Anything quieter is treated as silence
+const MIN_MAGNITUDE: f32 = 0.5;
+/// Later peaks paired with each anchor, no further than TARGET_FRAMES ahead (fits 6 bits)
+const FAN_OUT: usize = 3;
+const TARGET_FRAMES: u32 = 63;
+/// Hashes shared this widely say nothing about which records match
+const MAX_POSTINGS: usize = 1000;
+const MIN_MATCHES: u32 = 8;
+/// Share of the shorter file's landmarks that have to line up for a match. A clip cut off its
+/// parent's frame grid keeps around half of them, unrelated sounds next to none. Landmark
+/// matching doesn't use the similarity threshold, which is calibrated for Chromaprint.
+const MIN_SHARED: f64 = 0.2;
+/// A match this much shorter than its parent is reported as a subset
+const SUBSET_RATIO: f64 = 0.9;
+
+/// A pair of spectral peaks: both frequencies and their distance in frames, anchored at `time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Landmark {
+    pub hash: u32,
+    pub time: u32,
+}
+
+/// Constellation fingerprint of a file: spectral peaks paired into time-invariant hashes, so a
+/// clip can be found anywhere inside a longer recording.
+pub fn generate_landmarks(path: &Path) -> R<Vec<Landmark>> {
+    let samples = load_mono(path)?;
+    let peaks = find_peaks(&compute_spectrogram(&samples));
+
+    let mut landmarks = Vec::new();
+    for (i, &(time, freq)) in peaks.iter().enumerate() {
+        for &(target_time, target_freq) in peaks[i + 1..]
+            .iter()
+            .skip_while(|(t, _)| *t == time)
+            .take_while(|(t, _)| *t - time <= TARGET_FRAMES)
+            .take(FAN_OUT)
+        {
+            landmarks.push(Landmark {
+                hash: (freq << 15) | (target_freq << 6) | (target_time - time),
+                time,
+            });
+        }
+    }
+    Ok(landmarks)
+}
+
+// Mixed down to mono and resampled to SAMPLE_RATE
+fn load_mono(path: &Path) -> R<Vec<f32>> {
+    let mut mono = Vec::new();
+    let (sample_rate, _) = for_each_decoded_packet(path, |samples, channels| {
+        let channels = channels.max(1);
+        mono.extend(samples.chunks(channels).map(|frame| {
+            frame
+                .iter()
+                .map(|&s| s as f32 / i32::MAX as f32)
+                .sum::<f32>()
+                / channels as f32
+        }));
+    })?;
+    if mono.is_empty() {
+        return Err(anyhow!("No audio decoded"));
+    }
+
+    let audio = DecodedAudioInterleaved {
+        samples: mono,
+        sample_rate,
+        channels: 1,
+    };
+    Ok(resample_interleaved(audio, SAMPLE_RATE).samples)
+}
+
+fn compute_spectrogram(samples: &[f32]) -> Vec<Vec<f32>> {
+    let fft = FftPlanner::new().plan_fft_forward(WINDOW_SIZE);
+    let hann: Vec<f32> = (0..WINDOW_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / WINDOW_SIZE as f32).cos())
+        .collect();
+
+    let mut window = vec![Complex::new(0.0, 0.0); WINDOW_SIZE];
+    (0..samples.len())
+        .step_by(HOP_SIZE)
+        .map(|start| {
+            // Zero-pad the last window
+            for (i, bin) in window.iter_mut().enumerate() {
+                let sample = samples.get(start + i).copied().unwrap_or_default();
+                *bin = Complex::new(sample * hann[i], 0.0);
+            }
+            fft.process(&mut window);
+            window[..WINDOW_SIZE / 2].iter().map(|c| c.norm()).collect()
+        })
+        .collect()
+}
+
+// (frame, bin) of every peak, in time order
+fn find_peaks(spectrogram: &[Vec<f32>]) -> Vec<(u32, u32)> {
+    // Loudest bin of each band in each frame
+    let band_maxima: Vec<Vec<(usize, f32)>> = spectrogram
+        .iter()
+        .map(|spectrum| {
+            let mut low = 1; // skip DC
+            BANDS
+                .iter()
+                .map(|&high| {
+                    let (bin, magnitude) = (low..high).map(|bin| (bin, spectrum[bin])).fold(
+                        (low, 0.0),
+                        |best, candidate| {
+                            if candidate.1 > best.1 {
+                                candidate
+                            } else {
+                                best
+                            }
+                        },
+                    );
+                    low = high;
+                    (bin, magnitude)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peaks = Vec::new();
+    for (time, maxima) in band_maxima.iter().enumerate() {
+        let mean = maxima.iter().map(|(_, m)| m).sum::<f32>() / maxima.len() as f32;
+        for (band, &(bin, magnitude)) in maxima.iter().enumerate() {
+            if magnitude < MIN_MAGNITUDE || magnitude < mean {
+                continue;
+            }
+            let first = time.saturating_sub(PEAK_NEIGHBOURHOOD);
+            let last = (time + PEAK_NEIGHBOURHOOD).min(band_maxima.len() - 1);
+            let loudest = (first..=last).all(|t| {
+                let other = band_maxima[t][band].1;
+                // Ties go to the earlier frame
+                t == time || other < magnitude || (other == magnitude && t > time)
+            });
+            if loudest {
+                peaks.push((time as u32, bin as u32));
+            }
+        }
+    }
+    peaks
+}
+
+async fn ensure_landmark_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let query = format!(
+        "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, identity TEXT, landmarks BLOB)",
+        LANDMARK_TABLE
+    );
+    sqlx::query(&query).execute(pool).await?;
+    Ok(())
+}
+
+async fn load_landmarks(
+    pool: &SqlitePool,
+) -> Result<HashMap<usize, (Arc<str>, Vec<Landmark>)>, sqlx::Error> {
+    ensure_landmark_table(pool).await?;
+    let query = format!("SELECT id, identity, landmarks FROM {}", LANDMARK_TABLE);
+    let rows = sqlx::query(&query).fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let id = row.get::<i64, _>(0) as usize;
+            let identity = Arc::from(row.get::<Option<&str>, _>(1)?);
+            let landmarks = row
+                .get::<&[u8], _>(2)
+                .chunks_exact(8)
+                .map(|chunk| Landmark {
+                    hash: u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                    time: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                })
+                .collect();
+            Some((id, (identity, landmarks)))
+        })
+        .collect())
+}
+
+async fn store_landmarks<E: StatusEmitter>(
+    pool: &SqlitePool,
+    rows: &[(usize, Arc<str>, &[Landmark])],
+    app: &E,
+) -> Result<(), sqlx::Error> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    ensure_landmark_table(pool).await?;
+
+    let query = format!(
+        "INSERT OR REPLACE INTO {} (id, identity, landmarks) VALUES (?, ?, ?)",
+        LANDMARK_TABLE
+    );
+    let mut tx = pool.begin().await?;
+    for (count, (id, identity, landmarks)) in rows.iter().enumerate() {
+        if count.is_multiple_of(RECORD_DIVISOR) {
+            app.substatus(
+                "db-storage",
+                count * 100 / rows.len(),
+                &format!("Storing landmarks: {}/{}", count, rows.len()),
+            );
+        }
+        let blob: Vec<u8> = landmarks
+            .iter()
+            .flat_map(|l| l.hash.to_le_bytes().into_iter().chain(l.time.to_le_bytes()))
+            .collect();
+        sqlx::query(&query)
+            .bind(*id as i64)
+            .bind(&**identity)
+            .bind(blob)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    app.substatus("db-storage", 100, "Landmarks stored");
+    Ok(())
+}
+
+impl Database {
+    pub async fn clear_landmarks(&self) -> Result<(), sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(());
+        };
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", LANDMARK_TABLE))
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Groups records whose landmarks line up at a consistent time offset. Longer files are
+    /// matched first, so a clip that only covers part of a recording joins it as a subset with
+    /// its position in the parent.
+    pub async fn landmark_match<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), String> {
+        println!("Starting Landmark Audio analysis");
+        app.substatus("landmarks", 0, "Loading stored landmarks...");
+
+        let pool = self.get_pool().await;
+        let mut cached = match &pool {
+            Some(pool) if pref.fetch_waveforms => load_landmarks(pool).await.unwrap_or_default(),
+            _ => HashMap::new(),
+        };
+        let mut landmarks: Vec<Option<Vec<Landmark>>> = self
+            .records
+            .iter()
+            .map(|record| {
+                let (identity, landmarks) = cached.remove(&record.id)?;
+                (record.identity.as_ref() == Some(&identity)).then_some(landmarks)
+            })
+            .collect();
+
+        // Analyze everything without a usable cached result
+        let missing: Vec<usize> = (0..landmarks.len())
+            .filter(|&i| landmarks[i].is_none())
+            .collect();
+        let completed = AtomicUsize::new(0);
+        let abort = self.abort.clone();
+        let computed: Vec<(usize, Option<Vec<Landmark>>)> = missing
+            .par_iter()
+            .map(|&i| {
+                let record = &self.records[i];
+                if abort.load(Ordering::SeqCst) || !record.path.exists() {
+                    return (i, None);
+                }
+                let new_completed = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                app.substatus("landmarks", 0, record.get_filename());
+                app.status(
+                    "landmarks",
+                    new_completed * 100 / missing.len(),
+                    &format!(
+                        "Generating Audio Landmarks: ({}/{})",
+                        new_completed,
+                        missing.len()
+                    ),
+                );
+                match generate_landmarks(&record.path) {
+                    Ok(landmarks) => (i, Some(landmarks)),
+                    Err(e) => {
+                        eprintln!("Failed to analyze {}: {}", record.get_filepath(), e);
+                        (i, None)
+                    }
+                }
+            })
+            .collect();
+        if abort.load(Ordering::SeqCst) {
+            println!("Aborting landmark scan - early exit");
+            return Err("Aborted".to_string());
+        }
+
+        if let Some(pool) = pool.as_ref().filter(|_| pref.store_waveforms) {
+            let rows: Vec<(usize, Arc<str>, &[Landmark])> = computed
+                .iter()
+                .filter_map(|(i, marks)| {
+                    let record = &self.records[*i];
+                    Some((record.id, record.identity.clone()?, marks.as_deref()?))
+                })
+                .collect();
+            let _ = store_landmarks(pool, &rows, app).await;
+        }
+        for (i, marks) in computed {
+            landmarks[i] = marks;
+        }
+
+        app.substatus("landmarks", 0, "Indexing landmarks...");
+        let mut postings: HashMap<u32, Vec<(u32, u32)>> = HashMap::new();
+        for (i, marks) in landmarks.iter().enumerate() {
+            for mark in marks.iter().flatten() {
+                postings
+                    .entry(mark.hash)
+                    .or_default()
+                    .push((i as u32, mark.time));
+            }
+        }
+        postings.retain(|_, records| records.len() <= MAX_POSTINGS);
+
+        // Longest first, so parents are grouped before their clips
+        let durations: Vec<f64> = self
+            .records
+            .iter()
+            .map(|r| r.get_duration().unwrap_or(0.0))
+            .collect();
+        let mut order: Vec<usize> = (0..self.records.len()).collect();
+        order.sort_by(|&a, &b| durations[b].total_cmp(&durations[a]));
+
+        let mut position = vec![usize::MAX; order.len()];
+        let mut group_of: Vec<Option<usize>> = vec![None; order.len()];
+        let mut subsets: HashMap<usize, SubsetOf> = HashMap::new();
+        for (pos, &i) in order.iter().enumerate() {
+            if pos.is_multiple_of(RECORD_DIVISOR) {
+                app.substatus(
+                    "landmarks",
+                    pos * 100 / order.len(),
+                    &format!("Matching landmarks: {}/{}", pos, order.len()),
+                );
+            }
+            position[i] = pos;
+            let Some(marks) = landmarks[i].as_ref().filter(|m| !m.is_empty()) else {
+                continue;
+            };
+
+            // Votes per earlier record and time offset into it
+            let mut votes: HashMap<(u32, i64), u32> = HashMap::new();
+            for mark in marks {
+                for &(j, time) in postings.get(&mark.hash).into_iter().flatten() {
+                    if position[j as usize] < pos {
+                        *votes
+                            .entry((j, time as i64 - mark.time as i64))
+                            .or_default() += 1;
+                    }
+                }
+            }
+
+            let mut best: Option<(usize, i64, f64)> = None;
+            for (&(j, offset), &count) in &votes {
+                // Frame grids rarely line up exactly, so neighbouring offsets count too
+                let count = count
+                    + votes.get(&(j, offset - 1)).unwrap_or(&0)
+                    + votes.get(&(j, offset + 1)).unwrap_or(&0);
+                let j = j as usize;
+                let shorter = marks
+                    .len()
+                    .min(landmarks[j].as_ref().map_or(0, |m| m.len()));
+                // Share of landmarks that line up, reported as is
+                let score = count as f64 / shorter.max(1) as f64;
+                if count < MIN_MATCHES || score < MIN_SHARED {
+                    continue;
+                }
+                if best.is_none_or(|(best_j, best_offset, best_score)| {
+                    score > best_score
+                        || (score == best_score && (j, offset) < (best_j, best_offset))
+                }) {
+                    best = Some((j, offset, score));
+                }
+            }
+
+            let Some((j, offset, score)) = best else {
+                continue;
+            };
+            group_of[i] = Some(group_of[j].unwrap_or(j));
+            group_of[j] = group_of[i];
+            if durations[i] < durations[j] * SUBSET_RATIO {
+                let start = (offset.max(0) as f64 * FRAME_SECONDS).min(durations[j]);
+                subsets.insert(
+                    i,
+                    SubsetOf {
+                        parent: self.records[j].id,
+                        start,
+                        end: (start + durations[i]).min(durations[j]),
+                        score,
+                    },
+                );
+            }
+        }
+
+        app.substatus("marking", 0, "Marking duplicate audio files...");
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_index: HashMap<usize, usize> = HashMap::new();
+        for &i in &order {
+            if let Some(root) = group_of[i] {
+                let index = *group_index.entry(root).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[index].push(i);
+            }
+        }
+
+        let mut processed_records = Vec::with_capacity(self.records.len());
+        for group in &groups {
+            let mut records: Vec<FileRecord> = group
+                .iter()
+                .map(|&i| {
+                    let mut record = self.records[i].clone();
+                    record.subset = subsets.get(&i).copied();
+                    record
+                })
+                .collect();
+            pref.sort_vec(&mut records);
+            // Never keep a clip over the recording it was cut from
+            records.sort_by_key(|record| record.subset.is_some());
+            FileRecord::set_group(&mut records);
+            for (j, mut record) in records.into_iter().enumerate() {
+                record.algorithm.insert(Algorithm::Waveforms);
+                if j > 0 {
+                    record.algorithm.remove(&A::Keep);
+                }
+                processed_records.push(record);
+            }
+        }
+        processed_records.extend(
+            (0..self.records.len())
+                .filter(|&i| group_of[i].is_none())
+                .map(|i| self.records[i].clone()),
+        );
+        self.records = processed_records;
+
+        app.substatus("complete", 100, "Landmark Audio analysis complete");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    // A tenth of a second per note, each note three random partials
+    fn melody(seed: u64, seconds: f64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as f64 / (1u64 << 31) as f64
+        };
+        let note = RATE as usize / 10;
+        let mut samples = Vec::new();
+        while samples.len() < (seconds * RATE as f64) as usize {
+            let partials: Vec<f64> = (0..3).map(|_| 200.0 + next() * 3800.0).collect();
+            samples.extend((0..note).map(|n| {
+                let t = n as f64 / RATE as f64;
+                partials
+                    .iter()
+                    .map(|f| (2.0 * std::f64::consts::PI * f * t).sin() as f32 * 0.25)
+                    .sum::<f32>()
+            }));
+        }
+        samples
+    }
+
+    fn record(dir: &Path, id: usize, name: &str, samples: Vec<f32>) -> FileRecord {
+        let path = dir.join(name);
+        let seconds = samples.len() as f64 / RATE as f64;
+        let buffer = AudioBuffer {
+            sample_rate: RATE,
+            channels: 1,
+            sample_format: SampleFormat::I16,
+            data: vec![samples],
+        };
+        WavCodec
+            .encode_file(&buffer, path.to_str().unwrap())
+            .unwrap();
+        FileRecord {
+            id,
+            path,
+            duration: Arc::from(format!("0:{:06.3}", seconds)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_a_clip_inside_its_parent_and_rejects_other_sounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent = melody(1, 20.0);
+        // Starts between analysis frames, so the clip's frame grid doesn't line up with the parent's
+        let start = (7.3 * RATE as f64) as usize + 37;
+        let clip = parent[start..start + 5 * RATE as usize].to_vec();
+
+        let mut db = Database {
+            records: vec![
+                record(dir.path(), 1, "parent.wav", parent),
+                record(dir.path(), 2, "clip.wav", clip),
+                record(dir.path(), 3, "other.wav", melody(2, 8.0)),
+            ],
+            ..Default::default()
+        };
+        for record in &mut db.records {
+            record.algorithm.insert(A::Keep);
+        }
+        db.landmark_match(&Preferences::default(), &NoopEmitter)
+            .await
+            .unwrap();
+
+        let find = |id: usize| db.records.iter().find(|r| r.id == id).unwrap();
+        let subset = find(2).subset.expect("clip matched as a subset");
+        assert_eq!(subset.parent, 1);
+        assert!(subset.score >= MIN_SHARED && subset.score < 1.0);
+        assert!((subset.start - 7.3).abs() < 2.0 * FRAME_SECONDS);
+        assert!((subset.end - 12.3).abs() < 2.0 * FRAME_SECONDS);
+        assert_eq!(find(1).get_group(), find(2).get_group());
+        assert!(find(3).get_group().is_none());
+        assert!(find(3).subset.is_none());
+    }
+}
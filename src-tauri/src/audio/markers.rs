@@ -0,0 +1,204 @@
+use crate::prelude::*;
+
+const CUE_POINT_LEN: usize = 24;
+
+/// A named position in sample frames, from an AIFF MARK chunk or a WAV cue chunk and its labels
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marker {
+    pub id: u32,
+    pub position: u32,
+    pub name: String,
+}
+
+/// Markers of a file. `adtl` holds the note and ltxt entries of a WAV associated data list,
+/// which AIFF has no place for, so they only survive WAV to WAV.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Markers {
+    pub markers: Vec<Marker>,
+    pub adtl: Vec<u8>,
+}
+
+/// An entry of an AIFF COMT chunk. `marker` is the id of the marker it belongs to, 0 for none.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    /// Seconds since 1904-01-01, as on classic Mac OS
+    pub timestamp: u32,
+    pub marker: i16,
+    pub text: String,
+}
+
+impl Markers {
+    /// Reads an AIFF MARK chunk: a count, then an id, position and Pascal string per marker.
+    pub fn parse_aiff(data: &[u8]) -> R<Self> {
+        let mut cursor = Cursor::new(data);
+        let count = cursor.read_u16::<BigEndian>()?;
+        let mut markers = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = cursor.read_i16::<BigEndian>()? as u32;
+            let position = cursor.read_u32::<BigEndian>()?;
+            let len = cursor.read_u8()? as usize;
+            let mut name = vec![0u8; len];
+            cursor.read_exact(&mut name)?;
+            // The count byte and text together take up an even number of bytes
+            if len.is_multiple_of(2) {
+                cursor.read_u8()?;
+            }
+            markers.push(Marker {
+                id,
+                position,
+                name: String::from_utf8_lossy(&name).to_string(),
+            });
+        }
+        Ok(Markers {
+            markers,
+            adtl: Vec::new(),
+        })
+    }
+
+    pub fn to_aiff(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend((self.markers.len() as u16).to_be_bytes());
+        for marker in &self.markers {
+            out.extend((marker.id as i16).to_be_bytes());
+            out.extend(marker.position.to_be_bytes());
+            let name = &marker.name.as_bytes()[..marker.name.len().min(255)];
+            out.push(name.len() as u8);
+            out.extend(name);
+            if name.len() % 2 == 0 {
+                out.push(0);
+            }
+        }
+        out
+    }
+
+    /// Reads a WAV cue chunk, naming the markers from the labl entries of `adtl`, the data of
+    /// a LIST chunk of type adtl.
+    pub fn parse_wav(cue: &[u8], adtl: Option<&[u8]>) -> R<Self> {
+        let mut cursor = Cursor::new(cue);
+        let count = cursor.read_u32::<LittleEndian>()? as usize;
+        if cue.len() < 4 + count * CUE_POINT_LEN {
+            return Err(anyhow!("cue chunk too short for {} points", count));
+        }
+        let mut markers = Vec::with_capacity(count);
+        for point in cue[4..4 + count * CUE_POINT_LEN].chunks_exact(CUE_POINT_LEN) {
+            markers.push(Marker {
+                id: u32::from_le_bytes(point[0..4].try_into()?),
+                // Sample offset into the data chunk, which is what players seek to
+                position: u32::from_le_bytes(point[20..24].try_into()?),
+                name: String::new(),
+            });
+        }
+
+        let mut other = Vec::new();
+        for (id, data) in adtl.map(list_entries).unwrap_or_default() {
+            if &id == b"labl" && data.len() >= 4 {
+                let cue_id = u32::from_le_bytes(data[0..4].try_into()?);
+                let name = read_zstring(&data[4..]);
+                if let Some(marker) = markers.iter_mut().find(|m| m.id == cue_id) {
+                    marker.name = name;
+                }
+            } else {
+                write_wav_entry(&mut other, &id, data);
+            }
+        }
+        Ok(Markers {
+            markers,
+            adtl: other,
+        })
+    }
+
+    /// The cue chunk data, and the data of a LIST chunk of type adtl when there is anything to
+    /// put in one.
+    pub fn to_wav(&self) -> (Vec<u8>, Option<Vec<u8>>) {
+        let mut cue = Vec::with_capacity(4 + self.markers.len() * CUE_POINT_LEN);
+        cue.extend((self.markers.len() as u32).to_le_bytes());
+        for marker in &self.markers {
+            cue.extend(marker.id.to_le_bytes());
+            cue.extend(marker.position.to_le_bytes());
+            cue.extend(b"data");
+            cue.extend(0u32.to_le_bytes()); // chunk start
+            cue.extend(0u32.to_le_bytes()); // block start
+            cue.extend(marker.position.to_le_bytes());
+        }
+
+        let mut adtl = b"adtl".to_vec();
+        for marker in self.markers.iter().filter(|m| !m.name.is_empty()) {
+            let mut label = marker.id.to_le_bytes().to_vec();
+            label.extend(marker.name.as_bytes());
+            label.push(0);
+            write_wav_entry(&mut adtl, b"labl", &label);
+        }
+        adtl.extend(&self.adtl);
+        (cue, (adtl.len() > 4).then_some(adtl))
+    }
+}
+
+impl Comment {
+    /// Reads an AIFF COMT chunk: a count, then a timestamp, marker id and counted text each.
+    pub fn parse_aiff(data: &[u8]) -> R<Vec<Self>> {
+        let mut cursor = Cursor::new(data);
+        let count = cursor.read_u16::<BigEndian>()?;
+        let mut comments = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let timestamp = cursor.read_u32::<BigEndian>()?;
+            let marker = cursor.read_i16::<BigEndian>()?;
+            let len = cursor.read_u16::<BigEndian>()? as usize;
+            let mut text = vec![0u8; len];
+            cursor.read_exact(&mut text)?;
+            if len % 2 == 1 {
+                cursor.read_u8()?;
+            }
+            comments.push(Comment {
+                timestamp,
+                marker,
+                text: String::from_utf8_lossy(&text).to_string(),
+            });
+        }
+        Ok(comments)
+    }
+
+    pub fn to_aiff(comments: &[Comment]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend((comments.len() as u16).to_be_bytes());
+        for comment in comments {
+            out.extend(comment.timestamp.to_be_bytes());
+            out.extend(comment.marker.to_be_bytes());
+            let text = &comment.text.as_bytes()[..comment.text.len().min(u16::MAX as usize)];
+            out.extend((text.len() as u16).to_be_bytes());
+            out.extend(text);
+            if text.len() % 2 == 1 {
+                out.push(0);
+            }
+        }
+        out
+    }
+}
+
+// Entries of a LIST chunk after its type, laid out like RIFF chunks
+fn list_entries(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut entries = Vec::new();
+    let mut pos = 4;
+    while pos + 8 <= data.len() {
+        let id: [u8; 4] = data[pos..pos + 4].try_into().unwrap_or_default();
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap_or_default());
+        let start = pos + 8;
+        let end = start.saturating_add(size as usize).min(data.len());
+        entries.push((id, &data[start..end]));
+        pos = end + size as usize % 2;
+    }
+    entries
+}
+
+fn write_wav_entry(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend(id);
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+fn read_zstring(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
@@ -5,9 +5,107 @@ use metaflac::{Block, Tag};
 const FMT_CHUNK_ID: &[u8; 4] = b"fmt ";
 const DATA_CHUNK_ID: &[u8; 4] = b"data";
 
+// AIFF chunks that describe the audio rather than tag it
+const AIFF_AUDIO_CHUNK_IDS: [&[u8; 4]; 3] = [b"COMM", b"SSND", b"FVER"];
+// AIFF chunks holding a single text string
+const AIFF_TEXT_CHUNK_IDS: [&[u8; 4]; 4] = [b"NAME", b"AUTH", b"(c) ", b"ANNO"];
+// WAV chunks that always come from the metadata being embedded, never from the file itself
+const WAV_METADATA_CHUNK_IDS: [&[u8; 4]; 9] = [
+    b"bext", b"iXML", b"SMED", b"SMRD", b"SMPL", b"id3 ", b"ID3 ", b"APIC", b"cue ",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Wav,
+    Aiff,
+}
+
+pub type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits a RIFF/WAVE or FORM/AIFF file into its top level chunks. Chunk sizes running past the
+/// end of the file are clamped so truncated recordings can still be read.
+pub fn walk_chunks(input: &[u8]) -> R<(Container, Vec<Chunk<'_>>)> {
+    if input.len() < 12 {
+        return Err(anyhow!("File too short"));
+    }
+    let container = match (&input[0..4], &input[8..12]) {
+        (b"RIFF", b"WAVE") => Container::Wav,
+        (b"FORM", b"AIFF" | b"AIFC") => Container::Aiff,
+        _ => return Err(anyhow!("Not a WAV or AIFF file")),
+    };
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= input.len() {
+        let id: [u8; 4] = input[pos..pos + 4].try_into()?;
+        let size: [u8; 4] = input[pos + 4..pos + 8].try_into()?;
+        let size = match container {
+            Container::Wav => u32::from_le_bytes(size),
+            Container::Aiff => u32::from_be_bytes(size),
+        } as usize;
+        let start = pos + 8;
+        let end = start.saturating_add(size).min(input.len());
+        chunks.push((id, &input[start..end]));
+        // Padding: chunks are aligned to even sizes
+        pos = end + size % 2;
+    }
+    Ok((container, chunks))
+}
+
+/// The sample format and sample bytes of a WAV or AIFF file, with every other chunk left out.
+pub struct AudioPayload<'a> {
+    pub format: Vec<u8>,
+    pub samples: &'a [u8],
+}
+
+pub fn audio_payload(input: &[u8]) -> R<AudioPayload<'_>> {
+    let (container, chunks) = walk_chunks(input)?;
+    let find = |wanted: &[u8; 4]| {
+        chunks
+            .iter()
+            .find(|(id, _)| id == wanted)
+            .map(|(_, data)| *data)
+    };
+
+    match container {
+        Container::Wav => {
+            let fmt = find(FMT_CHUNK_ID)
+                .filter(|fmt| fmt.len() >= 16)
+                .ok_or_else(|| anyhow!("Missing fmt chunk"))?;
+            let samples = find(DATA_CHUNK_ID).ok_or_else(|| anyhow!("Missing data chunk"))?;
+            // WAVE_FORMAT_EXTENSIBLE keeps the real format tag at the start of its sub format
+            let tag = if fmt[0..2] == [0xFE, 0xFF] && fmt.len() >= 26 {
+                &fmt[24..26]
+            } else {
+                &fmt[0..2]
+            };
+            // Byte rate and block align follow from channels, sample rate and bit depth
+            let format = [tag, &fmt[2..8], &fmt[14..16]].concat();
+            Ok(AudioPayload { format, samples })
+        }
+        Container::Aiff => {
+            let comm = find(b"COMM")
+                .filter(|comm| comm.len() >= 18)
+                .ok_or_else(|| anyhow!("Missing COMM chunk"))?;
+            let ssnd = find(b"SSND")
+                .filter(|ssnd| ssnd.len() >= 8)
+                .ok_or_else(|| anyhow!("Missing SSND chunk"))?;
+            let offset = u32::from_be_bytes(ssnd[0..4].try_into()?) as usize;
+            // Channels, sample size and sample rate, plus the compression type for AIFC
+            let mut format = [&comm[0..2], &comm[6..18]].concat();
+            format.extend_from_slice(comm.get(18..22).unwrap_or_default());
+            Ok(AudioPayload {
+                format,
+                samples: ssnd.get(8 + offset..).unwrap_or_default(),
+            })
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Metadata {
     Wav(Vec<MetadataChunk>),
+    Aiff(Vec<MetadataChunk>),
     Flac(metaflac::Tag),
 }
 
@@ -19,28 +117,42 @@ impl Default for Metadata {
 
 impl Metadata {
     pub fn get_metadata(path: &Path) -> Self {
-        if let Some(path) = path.extension() {
-            let p = path.to_str().unwrap_or_default();
-            match path.to_str() {
-                Some("wav") => {
-                    let c = WavCodec;
-                    c.extract_metadata_from_file(p).unwrap_or_default()
-                }
-                Some("flac") => {
-                    let c = FlacCodec;
-                    c.extract_metadata_from_file(p).unwrap_or_default()
-                }
-                _ => Metadata::default(),
-            }
-        } else {
-            Metadata::default()
+        Self::from_file(path).unwrap_or_default()
+    }
+
+    pub fn from_file(path: &Path) -> R<Self> {
+        let p = path.to_str().ok_or_else(|| anyhow!("Invalid path"))?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        match extension.to_lowercase().as_str() {
+            "wav" => WavCodec.extract_metadata_from_file(p),
+            "aif" | "aiff" => AifCodec.extract_metadata_from_file(p),
+            "flac" => FlacCodec.extract_metadata_from_file(p),
+            _ => Err(anyhow!("No metadata support for extension: {}", extension)),
         }
     }
+    /// Chunk based metadata is written in the format of the file at `p`, so WAV chunks can go
+    /// into an AIFF file and the other way around. Chunks only one of the formats knows, like
+    /// LIST or INST and APPL, are left out when the formats differ.
     pub fn set_metadata(&self, p: &str) -> R<()> {
+        let is_aiff = Path::new(p)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e.to_lowercase().as_str(), "aif" | "aiff"));
         match self {
-            Metadata::Wav(chunks) => {
-                let c = WavCodec;
-                c.embed_metadata_to_file(p, &Metadata::Wav(chunks.clone()))?;
+            Metadata::Wav(chunks) if is_aiff => {
+                AifCodec.embed_metadata_to_file(p, &Metadata::Aiff(portable(chunks)))?;
+            }
+            Metadata::Aiff(chunks) if !is_aiff => {
+                WavCodec.embed_metadata_to_file(p, &Metadata::Wav(portable(chunks)))?;
+            }
+            Metadata::Aiff(_) => {
+                AifCodec.embed_metadata_to_file(p, self)?;
+            }
+            Metadata::Wav(_) => {
+                WavCodec.embed_metadata_to_file(p, self)?;
             }
             Metadata::Flac(tag) => {
                 let c = FlacCodec;
@@ -52,6 +164,21 @@ impl Metadata {
     }
 }
 
+fn portable(chunks: &[MetadataChunk]) -> Vec<MetadataChunk> {
+    chunks
+        .iter()
+        .filter(|chunk| {
+            !matches!(
+                chunk,
+                MetadataChunk::Unknown { .. }
+                    | MetadataChunk::Comments(_)
+                    | MetadataChunk::Application { .. }
+            )
+        })
+        .cloned()
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub enum MetadataChunk {
     Bext(Vec<u8>),
@@ -70,6 +197,15 @@ pub enum MetadataChunk {
         key: String,
         value: String,
     },
+    /// AIFF MARK, or WAV cue with the labels of its adtl list
+    Markers(Markers),
+    /// AIFF COMT
+    Comments(Vec<Comment>),
+    /// AIFF APPL, data private to the application named by `signature`
+    Application {
+        signature: String,
+        data: Vec<u8>,
+    },
     Unknown {
         id: String,
         data: Vec<u8>,
@@ -86,9 +222,13 @@ impl MetadataChunk {
             MetadataChunk::APE(_) => "APE".to_string(),
             MetadataChunk::Picture { .. } => "Picture".to_string(),
             MetadataChunk::TextTag { key, .. } => key.clone(),
+            MetadataChunk::Markers(_) => "MARK".to_string(),
+            MetadataChunk::Comments(_) => "COMT".to_string(),
+            MetadataChunk::Application { .. } => "APPL".to_string(),
             MetadataChunk::Unknown { id, .. } => id.clone(),
         }
     }
+    /// Raw chunk data. Empty for markers and comments, whose bytes depend on the container.
     pub fn data(&self) -> &[u8] {
         match self {
             MetadataChunk::Bext(data) => data,
@@ -98,23 +238,23 @@ impl MetadataChunk {
             MetadataChunk::APE(data) => data,
             MetadataChunk::Picture { data, .. } => data,
             MetadataChunk::TextTag { value, .. } => value.as_bytes(),
+            MetadataChunk::Markers(_) | MetadataChunk::Comments(_) => &[],
+            MetadataChunk::Application { data, .. } => data,
             MetadataChunk::Unknown { data, .. } => data,
         }
     }
 
     pub fn as_text_tags(&self) -> Vec<(String, String)> {
         match self {
-            Self::IXml(xml) => {
-                let mut tags = Vec::new();
-                for line in xml.lines() {
-                    if let Some(idx) = line.find('=') {
-                        let key = line[0..idx].trim().to_string();
-                        let value = line[idx + 1..].trim().to_string();
-                        tags.push((key, value));
-                    }
-                }
-                tags
-            }
+            Self::IXml(xml) => match IXml::parse(xml) {
+                Ok(ixml) => ixml.text_fields(),
+                // Not XML at all, older versions stored KEY=VALUE lines here
+                Err(_) => xml
+                    .lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                    .collect(),
+            },
             Self::TextTag { key, value } => {
                 vec![(key.clone(), value.clone())]
             }
@@ -184,7 +324,7 @@ impl WavCodec {
 
     pub fn embed_metadata_to_file(&self, file_path: &str, metadata: &Metadata) -> R<()> {
         let chunks = match metadata {
-            Metadata::Wav(chunks) => chunks,
+            Metadata::Wav(chunks) | Metadata::Aiff(chunks) => chunks,
             _ => return Err(anyhow!("Unsupported metadata format")),
         };
 
@@ -205,37 +345,33 @@ impl WavCodec {
             );
         }
 
-        // Write the data back to the file
-        std::fs::write(file_path, new_data)?;
-        Ok(())
+        drop(mapped_file);
+
+        replace_file(file_path, &new_data)
     }
     fn extract_metadata_chunks(&self, input: &[u8]) -> R<Vec<MetadataChunk>> {
-        let mut cursor = Cursor::new(input);
-
-        let mut header = [0u8; 12];
-        cursor.read_exact(&mut header)?;
-
-        if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        let (container, walked) = walk_chunks(input)?;
+        if container != Container::Wav {
             return Err(anyhow!("Not a WAV file"));
         }
 
-        let mut chunks = Vec::new();
-        while cursor.position() < input.len() as u64 {
-            let mut id = [0u8; 4];
-            if cursor.read(&mut id)? < 4 {
-                break;
-            }
-
-            let size = cursor.read_u32::<LittleEndian>()?;
+        // Markers are the cue chunk named by the labels of the adtl list
+        let find = |wanted: fn(&Chunk) -> bool| walked.iter().find(|c| wanted(c)).map(|c| c.1);
+        let adtl = find(is_adtl);
+        let markers =
+            find(|(id, _)| id == b"cue ").and_then(|cue| Markers::parse_wav(cue, adtl).ok());
 
+        let mut chunks = Vec::new();
+        for chunk in &walked {
+            let (id, data) = *chunk;
             // Skip the 'data' chunk and 'fmt ' chunk - they're not metadata
             if &id == DATA_CHUNK_ID || &id == FMT_CHUNK_ID {
-                cursor.seek(SeekFrom::Current(size as i64 + (size % 2) as i64))?;
                 continue;
             }
-
-            let mut data = vec![0u8; size as usize];
-            cursor.read_exact(&mut data)?;
+            if markers.is_some() && (&id == b"cue " || is_adtl(chunk)) {
+                continue;
+            }
+            let data = data.to_vec();
 
             let chunk = match &id {
                 b"bext" => MetadataChunk::Bext(data),
@@ -243,19 +379,14 @@ impl WavCodec {
                     let xml = String::from_utf8_lossy(&data).to_string();
 
                     // Also extract individual text tags for better format conversion
-                    for line in xml.lines() {
-                        if let Some(idx) = line.find('=') {
-                            let key = line[0..idx].trim().to_string();
-                            let value = line[idx + 1..].trim().to_string();
-
-                            // Only add if it's a valid key-value pair
-                            if !key.is_empty() {
-                                chunks.push(MetadataChunk::TextTag { key, value });
-                            }
+                    let ixml = MetadataChunk::IXml(xml);
+                    for (key, value) in ixml.as_text_tags() {
+                        if !key.is_empty() {
+                            chunks.push(MetadataChunk::TextTag { key, value });
                         }
                     }
 
-                    MetadataChunk::IXml(xml)
+                    ixml
                 }
                 // Recognize ID3 chunk if present in WAV
                 b"id3 " | b"ID3 " => MetadataChunk::ID3(data),
@@ -278,7 +409,8 @@ impl WavCodec {
                         data,
                     }
                 }
-                b"SMED" | b"SMRD" | b"SMPL" => MetadataChunk::Soundminer(data),
+                // SMRD and SMPL stay Unknown so they are written back under their own ids
+                b"SMED" => MetadataChunk::Soundminer(data),
                 _ => MetadataChunk::Unknown {
                     id: String::from_utf8_lossy(&id).to_string(),
                     data,
@@ -286,24 +418,22 @@ impl WavCodec {
             };
 
             chunks.push(chunk);
-
-            // Padding: chunks are aligned to even sizes
-            if size % 2 == 1 {
-                cursor.seek(SeekFrom::Current(1))?;
-            }
         }
+        chunks.extend(markers.map(MetadataChunk::Markers));
 
         Ok(chunks)
     }
 
+    /// Keeps the audio chunks of `input`, and any other chunk that `chunks` has no replacement
+    /// for, then appends `chunks`. Metadata chunks, markers and their labels always come from
+    /// `chunks`.
     fn embed_metadata_chunks(&self, input: &[u8], chunks: &[MetadataChunk]) -> R<Vec<u8>> {
-        let mut cursor = Cursor::new(input);
-        let mut output = Cursor::new(Vec::new());
-
-        // Copy the RIFF/WAVE header
-        let mut riff_header = [0u8; 12];
-        cursor.read_exact(&mut riff_header)?;
-        output.write_all(&riff_header)?;
+        let (container, walked) = walk_chunks(input)?;
+        if container != Container::Wav {
+            return Err(anyhow!("Not a WAV file"));
+        }
+        let mut output = Cursor::new(Vec::with_capacity(input.len()));
+        output.write_all(&input[0..12])?;
 
         // Read the original channel count from the input file
         let mut original_cursor = Cursor::new(input);
@@ -318,28 +448,31 @@ impl WavCodec {
         let mut text_tags = Vec::new();
         let mut other_chunks = Vec::new();
 
+        // Other chunks the new metadata brings along, which replace the file's own
+        let replaced: Vec<Chunk> = chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                MetadataChunk::Unknown { id, data } => {
+                    Some((id.as_bytes().try_into().ok()?, data.as_slice()))
+                }
+                _ => None,
+            })
+            .collect();
+
         // When reading and writing non-metadata chunks, preserve the original fmt chunk
         let mut fmt_chunk_found = false;
 
         // First collect all chunks from source audio
-        while cursor.position() < input.len() as u64 {
-            let mut id = [0u8; 4];
-            if cursor.read(&mut id)? < 4 {
-                break;
-            }
-
-            let size = cursor.read_u32::<LittleEndian>()?;
-            let mut data = vec![0u8; size as usize];
-            cursor.read_exact(&mut data)?;
-
-            let id_str = String::from_utf8_lossy(&id).to_string();
+        for chunk in &walked {
+            let (id, data) = *chunk;
 
             // Handle fmt chunk specially to preserve channel count
             if &id == FMT_CHUNK_ID {
                 fmt_chunk_found = true;
+                let mut data = data.to_vec();
 
                 // We need to preserve the fmt chunk but ensure it has the correct channel count
-                if original_channels == 1 {
+                if original_channels == 1 && data.len() >= 16 {
                     // For mono files, make sure fmt chunk shows 1 channel
                     // Channel count is at offset 2 in fmt chunk
                     data[2] = 1;
@@ -364,35 +497,24 @@ impl WavCodec {
                 }
 
                 // Write the fmt chunk with potentially updated data
-                output.write_all(&id)?;
-                output.write_u32::<LittleEndian>(size)?;
-                output.write_all(&data)?;
-
-                if size % 2 == 1 {
-                    output.write_all(&[0])?; // padding
-                }
+                write_chunk(&mut output, &id, &data)?;
                 continue;
             }
 
             // Skip known metadata chunks since we'll replace them
-            if matches!(
-                id_str.as_str(),
-                "bext" | "iXML" | "SMED" | "SMRD" | "SMPL" | "id3 " | "ID3 " | "APIC"
-            ) {
-                if size % 2 == 1 {
-                    cursor.seek(SeekFrom::Current(1))?;
-                }
+            if WAV_METADATA_CHUNK_IDS.contains(&&id) || is_adtl(chunk) {
+                continue;
+            }
+            // LIST chunks are told apart by their list type
+            if replaced
+                .iter()
+                .any(|new| new.0 == id && (&id != b"LIST" || new.1.get(0..4) == data.get(0..4)))
+            {
                 continue;
             }
 
             // Write other chunks directly to output
-            output.write_all(&id)?;
-            output.write_u32::<LittleEndian>(size)?;
-            output.write_all(&data)?;
-
-            if size % 2 == 1 {
-                output.write_all(&[0])?;
-            }
+            write_chunk(&mut output, &id, data)?;
         }
 
         // If the fmt chunk wasn't found in the input file (unlikely), don't proceed
@@ -401,30 +523,15 @@ impl WavCodec {
         }
 
         // Organize metadata chunks by type
+        let has_picture = chunks
+            .iter()
+            .any(|chunk| matches!(chunk, MetadataChunk::Picture { .. }));
         for chunk in chunks {
             match chunk {
-                MetadataChunk::Bext(data) => {
-                    // Update channel count in Broadcast WAV extension if necessary
-                    let mut bext_data = data.clone();
-                    if original_channels == 1 && bext_data.len() >= 356 {
-                        // Update channel count in BEXT chunk (at offset 354-355)
-                        bext_data[354] = 1;
-                        bext_data[355] = 0; // Little-endian representation of 1
-                    }
-                    bext_chunks.push(MetadataChunk::Bext(bext_data));
-                }
-                MetadataChunk::IXml(xml) => {
-                    // Check for any channel references in iXML that need updating
-                    let mut updated_xml = xml.clone();
-                    if original_channels == 1 {
-                        // Replace any references to "2 channels" or similar with "1 channel"
-                        // This is a simplistic approach and might need refinement
-                        updated_xml = updated_xml.replace("CHANNELS=2", "CHANNELS=1");
-                        updated_xml = updated_xml.replace("channels=2", "channels=1");
-                        updated_xml = updated_xml.replace("NumChannels=2", "NumChannels=1");
-                    }
-                    ixml_chunks.push(MetadataChunk::IXml(updated_xml));
-                }
+                // bext has no channel count, offset 354 is part of the UMID
+                MetadataChunk::Bext(data) => bext_chunks.push(MetadataChunk::Bext(data.clone())),
+                // Track lists are updated with the audio, see `Metadata::remove_tracks`
+                MetadataChunk::IXml(xml) => ixml_chunks.push(MetadataChunk::IXml(xml.clone())),
                 MetadataChunk::Picture {
                     mime_type,
                     description,
@@ -446,10 +553,17 @@ impl WavCodec {
                 MetadataChunk::Soundminer(data) => {
                     other_chunks.push(MetadataChunk::Soundminer(data.clone()))
                 }
+                MetadataChunk::Markers(_) => other_chunks.push(chunk.clone()),
+                // The raw APIC data is only written when it wasn't recognized as a picture
+                MetadataChunk::Unknown { id, .. } if id == "APIC" && has_picture => {}
+                // These chunks describe the audio in the file being written to
+                MetadataChunk::Unknown { id, .. } if id == "fmt " || id == "data" => {}
                 MetadataChunk::Unknown { id, data } => other_chunks.push(MetadataChunk::Unknown {
                     id: id.clone(),
                     data: data.clone(),
                 }),
+                // AIFF only
+                MetadataChunk::Comments(_) | MetadataChunk::Application { .. } => {}
             }
         }
 
@@ -501,6 +615,13 @@ impl WavCodec {
         for chunk in &other_chunks {
             match chunk {
                 MetadataChunk::Soundminer(data) => write_chunk(&mut output, b"SMED", data)?,
+                MetadataChunk::Markers(markers) => {
+                    let (cue, adtl) = markers.to_wav();
+                    write_chunk(&mut output, b"cue ", &cue)?;
+                    if let Some(adtl) = adtl {
+                        write_chunk(&mut output, b"LIST", &adtl)?;
+                    }
+                }
                 MetadataChunk::Unknown { id, data } => {
                     write_chunk(&mut output, id.as_bytes(), data)?;
                 }
@@ -518,6 +639,23 @@ impl WavCodec {
     }
 }
 
+/// Writes `data` next to the file and renames it over the original, so a crash or full disk
+/// never leaves a half written recording behind.
+fn replace_file(file_path: &str, data: &[u8]) -> R<()> {
+    let tmp = format!("{}.smdbc.tmp", file_path);
+    let result = fs::write(&tmp, data)
+        .and_then(|_| fs::set_permissions(&tmp, fs::metadata(file_path)?.permissions()))
+        .and_then(|_| fs::rename(&tmp, file_path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(result?)
+}
+
+fn is_adtl((id, data): &Chunk) -> bool {
+    id == b"LIST" && data.starts_with(b"adtl")
+}
+
 fn write_chunk<W: Write>(writer: &mut W, id: &[u8], data: &[u8]) -> R<()> {
     writer.write_all(id)?;
     writer.write_u32::<LittleEndian>(data.len() as u32)?;
@@ -528,6 +666,152 @@ fn write_chunk<W: Write>(writer: &mut W, id: &[u8], data: &[u8]) -> R<()> {
     Ok(())
 }
 
+impl AifCodec {
+    pub fn extract_metadata_from_file(&self, file_path: &str) -> R<Metadata> {
+        let file = std::fs::File::open(file_path)?;
+        let mapped_file = unsafe { MmapOptions::new().map(&file)? };
+        Ok(Metadata::Aiff(self.extract_metadata_chunks(&mapped_file)?))
+    }
+
+    pub fn embed_metadata_to_file(&self, file_path: &str, metadata: &Metadata) -> R<()> {
+        let chunks = match metadata {
+            Metadata::Wav(chunks) | Metadata::Aiff(chunks) => chunks,
+            _ => return Err(anyhow!("Unsupported metadata format")),
+        };
+
+        let file = std::fs::File::open(file_path)?;
+        let mapped_file = unsafe { MmapOptions::new().map(&file)? };
+        let new_data = self.embed_metadata_chunks(&mapped_file, chunks)?;
+        drop(mapped_file);
+
+        replace_file(file_path, &new_data)
+    }
+
+    fn extract_metadata_chunks(&self, input: &[u8]) -> R<Vec<MetadataChunk>> {
+        let (container, walked) = walk_chunks(input)?;
+        if container != Container::Aiff {
+            return Err(anyhow!("Not an AIFF file"));
+        }
+
+        let mut chunks = Vec::new();
+        for (id, data) in walked {
+            if AIFF_AUDIO_CHUNK_IDS.contains(&&id) {
+                continue;
+            }
+            let parsed = match &id {
+                b"MARK" => Markers::parse_aiff(data).ok().map(MetadataChunk::Markers),
+                b"COMT" => Comment::parse_aiff(data).ok().map(MetadataChunk::Comments),
+                b"APPL" if data.len() >= 4 => Some(MetadataChunk::Application {
+                    signature: String::from_utf8_lossy(&data[0..4]).to_string(),
+                    data: data[4..].to_vec(),
+                }),
+                _ => None,
+            };
+            if let Some(chunk) = parsed {
+                chunks.push(chunk);
+                continue;
+            }
+            let text = std::str::from_utf8(data).ok();
+            let chunk = match (&id, text) {
+                // Text that isn't UTF-8 (older files use Mac Roman) is kept as raw bytes
+                (id, Some(text)) if AIFF_TEXT_CHUNK_IDS.contains(&id) => MetadataChunk::TextTag {
+                    key: String::from_utf8_lossy(id).to_string(),
+                    value: text.trim_end_matches('\0').to_string(),
+                },
+                (b"ID3 " | b"id3 ", _) => MetadataChunk::ID3(data.to_vec()),
+                (b"bext", _) => MetadataChunk::Bext(data.to_vec()),
+                (b"iXML", _) => MetadataChunk::IXml(String::from_utf8_lossy(data).to_string()),
+                (b"SMED", _) => MetadataChunk::Soundminer(data.to_vec()),
+                // INST and the like, or chunks that don't parse, are copied as they are
+                _ => MetadataChunk::Unknown {
+                    id: String::from_utf8_lossy(&id).to_string(),
+                    data: data.to_vec(),
+                },
+            };
+            chunks.push(chunk);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Keeps the audio chunks of `input`, and any other chunk that `chunks` has no replacement
+    /// for, then appends `chunks`.
+    fn embed_metadata_chunks(&self, input: &[u8], chunks: &[MetadataChunk]) -> R<Vec<u8>> {
+        let (container, walked) = walk_chunks(input)?;
+        if container != Container::Aiff {
+            return Err(anyhow!("Not an AIFF file"));
+        }
+
+        let new_chunks: Vec<([u8; 4], Cow<[u8]>)> = chunks
+            .iter()
+            .filter_map(|chunk| {
+                let id: &[u8] = match chunk {
+                    MetadataChunk::TextTag { key, .. } => key.as_bytes(),
+                    MetadataChunk::ID3(_) => b"ID3 ",
+                    MetadataChunk::Bext(_) => b"bext",
+                    MetadataChunk::IXml(_) => b"iXML",
+                    MetadataChunk::Soundminer(_) => b"SMED",
+                    MetadataChunk::Markers(_) => b"MARK",
+                    MetadataChunk::Comments(_) => b"COMT",
+                    MetadataChunk::Application { .. } => b"APPL",
+                    MetadataChunk::Unknown { id, .. } => id.as_bytes(),
+                    // No AIFF home for these
+                    MetadataChunk::APE(_) | MetadataChunk::Picture { .. } => return None,
+                };
+                let id: [u8; 4] = id.try_into().ok()?;
+                if AIFF_AUDIO_CHUNK_IDS.contains(&&id) {
+                    return None;
+                }
+                // Text tags from WAV iXML only fit AIFF's own text chunks
+                if matches!(chunk, MetadataChunk::TextTag { .. })
+                    && !AIFF_TEXT_CHUNK_IDS.contains(&&id)
+                {
+                    return None;
+                }
+                let data = match chunk {
+                    MetadataChunk::Markers(markers) => Cow::Owned(markers.to_aiff()),
+                    MetadataChunk::Comments(comments) => Cow::Owned(Comment::to_aiff(comments)),
+                    MetadataChunk::Application { signature, data } => {
+                        let mut appl = signature.as_bytes().to_vec();
+                        appl.resize(4, b' ');
+                        appl.extend(data);
+                        Cow::Owned(appl)
+                    }
+                    _ => Cow::Borrowed(chunk.data()),
+                };
+                Some((id, data))
+            })
+            .collect();
+
+        let mut output = Cursor::new(Vec::with_capacity(input.len()));
+        output.write_all(&input[0..12])?;
+        for (id, data) in walked {
+            if AIFF_AUDIO_CHUNK_IDS.contains(&&id) || !new_chunks.iter().any(|(new, _)| *new == id)
+            {
+                write_aiff_chunk(&mut output, &id, data)?;
+            }
+        }
+        for (id, data) in &new_chunks {
+            write_aiff_chunk(&mut output, id, data)?;
+        }
+
+        let mut result = output.into_inner();
+        let form_size = result.len() as u32 - 8;
+        (&mut result[4..8]).write_u32::<BigEndian>(form_size)?;
+        Ok(result)
+    }
+}
+
+fn write_aiff_chunk<W: Write>(writer: &mut W, id: &[u8], data: &[u8]) -> R<()> {
+    writer.write_all(id)?;
+    writer.write_u32::<BigEndian>(data.len() as u32)?;
+    writer.write_all(data)?;
+    if data.len() % 2 == 1 {
+        writer.write_all(&[0])?; // padding
+    }
+    Ok(())
+}
+
 impl FlacCodec {
     pub fn extract_metadata_from_file(&self, file_path: &str) -> R<Metadata> {
         let tag = Tag::read_from_path(file_path)?;
@@ -557,3 +841,254 @@ impl FlacCodec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WAVE".to_vec();
+        let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 1, 0, 2, 0, 16, 0];
+        write_chunk(&mut body, b"fmt ", &fmt).unwrap();
+        write_chunk(&mut body, b"data", &[1, 2, 3, 4]).unwrap();
+        for (id, data) in chunks {
+            write_chunk(&mut body, *id, data).unwrap();
+        }
+        [
+            b"RIFF".as_slice(),
+            &(body.len() as u32).to_le_bytes(),
+            &body,
+        ]
+        .concat()
+    }
+
+    fn aiff(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"AIFF".to_vec();
+        let mut comm = vec![0, 1, 0, 0, 0, 2, 0, 16];
+        comm.extend([0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]); // 48 kHz as an 80 bit float
+        write_aiff_chunk(&mut body, b"COMM", &comm).unwrap();
+        write_aiff_chunk(&mut body, b"SSND", &[0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4]).unwrap();
+        for (id, data) in chunks {
+            write_aiff_chunk(&mut body, *id, data).unwrap();
+        }
+        [
+            b"FORM".as_slice(),
+            &(body.len() as u32).to_be_bytes(),
+            &body,
+        ]
+        .concat()
+    }
+
+    fn chunks_of(path: &Path) -> Vec<([u8; 4], Vec<u8>)> {
+        let file = fs::read(path).unwrap();
+        walk_chunks(&file)
+            .unwrap()
+            .1
+            .into_iter()
+            .map(|(id, data)| (id, data.to_vec()))
+            .collect()
+    }
+
+    fn markers() -> Markers {
+        Markers {
+            markers: vec![
+                Marker {
+                    id: 1,
+                    position: 100,
+                    name: "Start".to_string(),
+                },
+                Marker {
+                    id: 2,
+                    position: 2000,
+                    name: "Impact".to_string(),
+                },
+            ],
+            adtl: Vec::new(),
+        }
+    }
+
+    fn comments() -> Vec<Comment> {
+        vec![Comment {
+            timestamp: 3_000_000_000,
+            marker: 2,
+            text: "Door slam".to_string(),
+        }]
+    }
+
+    #[test]
+    fn aiff_metadata_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let appl = b"pdosSoundminer data".as_slice();
+        let original = [
+            (b"NAME", b"Door".as_slice()),
+            (b"ANNO", b"Heavy wooden door"),
+            (b"COMT", &Comment::to_aiff(&comments())),
+            (b"MARK", &markers().to_aiff()),
+            (b"APPL", appl),
+            (
+                b"INST",
+                &[
+                    60, 0, 0, 127, 0, 127, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                ],
+            ),
+        ];
+        let source = dir.path().join("source.aif");
+        fs::write(&source, aiff(&original)).unwrap();
+
+        let metadata = Metadata::from_file(&source).unwrap();
+        let Metadata::Aiff(chunks) = &metadata else {
+            panic!("not an AIFF file");
+        };
+        assert!(
+            chunks
+                .iter()
+                .any(|c| matches!(c, MetadataChunk::Markers(m) if *m == markers()))
+        );
+        assert!(
+            chunks
+                .iter()
+                .any(|c| matches!(c, MetadataChunk::Comments(c) if *c == comments()))
+        );
+        assert!(chunks.iter().any(|c| matches!(
+            c,
+            MetadataChunk::Application { signature, data }
+                if signature == "pdos" && data == b"Soundminer data"
+        )));
+
+        // Written into another AIFF file every chunk comes out as it went in, once
+        let target = dir.path().join("target.aif");
+        fs::write(&target, aiff(&[(b"NAME", b"Old name")])).unwrap();
+        metadata.set_metadata(target.to_str().unwrap()).unwrap();
+        let written = chunks_of(&target);
+        assert_eq!(written.len(), 2 + original.len());
+        for (id, data) in original {
+            assert!(written.contains(&(*id, data.to_vec())), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn markers_convert_between_wav_and_aiff() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cue, adtl) = markers().to_wav();
+        let source = dir.path().join("source.wav");
+        fs::write(
+            &source,
+            wav(&[
+                (b"cue ", &cue),
+                (b"LIST", &adtl.unwrap()),
+                (b"LIST", b"INFOINAM\x05\0\0\0Door\0\0"),
+            ]),
+        )
+        .unwrap();
+
+        // WAV only chunks stay behind, the markers carry over with their names
+        let aif = dir.path().join("converted.aif");
+        fs::write(&aif, aiff(&[])).unwrap();
+        Metadata::from_file(&source)
+            .unwrap()
+            .set_metadata(aif.to_str().unwrap())
+            .unwrap();
+        let written = chunks_of(&aif);
+        let ids: Vec<[u8; 4]> = written.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [*b"COMM", *b"SSND", *b"MARK"]);
+        assert_eq!(Markers::parse_aiff(&written[2].1).unwrap(), markers());
+
+        // And back again, leaving AIFF comments behind
+        fs::write(
+            &aif,
+            aiff(&[
+                (b"MARK", &markers().to_aiff()),
+                (b"COMT", &Comment::to_aiff(&comments())),
+            ]),
+        )
+        .unwrap();
+        let target = dir.path().join("converted.wav");
+        fs::write(&target, wav(&[])).unwrap();
+        Metadata::from_file(&aif)
+            .unwrap()
+            .set_metadata(target.to_str().unwrap())
+            .unwrap();
+        let written = chunks_of(&target);
+        let ids: Vec<[u8; 4]> = written.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [*b"fmt ", *b"data", *b"cue ", *b"LIST"]);
+        let parsed = Markers::parse_wav(&written[2].1, Some(&written[3].1)).unwrap();
+        assert_eq!(parsed, markers());
+    }
+
+    #[test]
+    fn embedding_twice_leaves_the_file_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cue, adtl) = markers().to_wav();
+        let path = dir.path().join("door.wav");
+        fs::write(
+            &path,
+            wav(&[
+                (b"bext", &[0; 602]),
+                (b"cue ", &cue),
+                (b"LIST", &adtl.unwrap()),
+                (b"LIST", b"INFOINAM\x05\0\0\0Door\0\0"),
+                (b"PEAK", &[1, 0, 0, 0, 0, 0, 0, 0]),
+            ]),
+        )
+        .unwrap();
+
+        let metadata = Metadata::from_file(&path).unwrap();
+        metadata.set_metadata(path.to_str().unwrap()).unwrap();
+        let once = fs::read(&path).unwrap();
+        assert_eq!(chunks_of(&path).len(), 7);
+        metadata.set_metadata(path.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), once);
+    }
+
+    #[test]
+    fn walks_padded_and_truncated_chunks() {
+        let mut file = wav(&[(b"odd ", &[7, 7, 7])]);
+        // Cut the last chunk short, as an interrupted recording would be
+        file.extend(b"tail");
+        file.extend(100u32.to_le_bytes());
+        file.extend([1, 2]);
+        let (container, chunks) = walk_chunks(&file).unwrap();
+        assert_eq!(container, Container::Wav);
+        assert_eq!(
+            chunks,
+            [
+                (*b"fmt ", &file[20..36]),
+                (*b"data", [1u8, 2, 3, 4].as_slice()),
+                (*b"odd ", [7u8, 7, 7].as_slice()),
+                (*b"tail", [1u8, 2].as_slice()),
+            ]
+        );
+
+        let mut aifc = aiff(&[]);
+        aifc[8..12].copy_from_slice(b"AIFC");
+        assert_eq!(walk_chunks(&aifc).unwrap().0, Container::Aiff);
+        assert!(walk_chunks(b"OggS\0\0\0\0\0\0\0\0").is_err());
+        assert!(walk_chunks(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn checksums_ignore_metadata_and_chunk_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("plain.wav");
+        let tagged = dir.path().join("tagged.wav");
+        let other = dir.path().join("other.wav");
+        fs::write(&plain, wav(&[])).unwrap();
+        // Metadata in front of the audio, in a file that has been tagged since
+        let mut file = wav(&[]);
+        let audio = file.split_off(12);
+        write_chunk(&mut file, b"bext", &[0; 602]).unwrap();
+        write_chunk(&mut file, b"iXML", b"<BWFXML/>").unwrap();
+        file.extend(audio);
+        let size = file.len() as u32 - 8;
+        file[4..8].copy_from_slice(&size.to_le_bytes());
+        fs::write(&tagged, file).unwrap();
+        let mut file = wav(&[]);
+        let last = file.len() - 1;
+        file[last] = 5;
+        fs::write(&other, file).unwrap();
+
+        let checksum = |path: &Path| audio_checksum(path).unwrap();
+        assert_eq!(checksum(&plain), checksum(&tagged));
+        assert_ne!(checksum(&plain), checksum(&other));
+    }
+}
@@ -1,31 +1,43 @@
+pub mod bext;
+pub mod channels;
+pub mod checksum;
 pub mod chromaprint;
 pub mod decode;
 pub mod encode;
+pub mod fingerprint_index;
+pub mod ixml;
+pub mod landmarks;
+pub mod markers;
 pub mod metadata;
 // pub mod ffmpeg;
 pub mod playback;
 // pub mod shazam_fingerprint_processing;
-// pub mod landmarks;
 // pub mod claude;
 // pub mod encode;
 // pub mod decode_old;
 // pub mod fft;
-// pub mod shazam_style;
 // pub mod shazam_search_claude;
 // pub mod symphonia_play;
 
+use crate::prelude::*;
+
+pub use bext::*;
+pub use channels::*;
+pub use checksum::*;
 pub use chromaprint::*;
 pub use decode::*;
 pub use encode::*;
+pub use fingerprint_index::*;
+pub use ixml::*;
+pub use landmarks::*;
+pub use markers::*;
 // pub use ffmpeg::*;
 pub use metadata::*;
 pub use playback::*;
 // pub use shazam_fingerprint_processing::*;
-// pub use landmarks::*;
 // pub use claude::*;
 // pub use encode::*;
 // pub use fft::*;
-// pub use shazam_style::*;
 // pub use shazam_search_claude::*;
 // pub use symphonia_play::*;
 
@@ -41,17 +53,50 @@ impl AudioBuffer {
         if self.data.is_empty() || self.channels < 2 {
             return Ok(());
         }
+        let extra: Vec<u16> = (1..self.channels).collect();
+        self.strip_channels(&extra)
+    }
 
-        let first_channel = std::mem::take(&mut self.data[0]);
-        self.data.clear();
-        self.data.push(first_channel);
+    /// Removes the channels in `drop`, numbered from 0. At least one channel has to remain.
+    pub fn strip_channels(&mut self, drop: &[u16]) -> R<()> {
+        if let Some(&channel) = drop.iter().find(|&&c| c as usize >= self.data.len()) {
+            return Err(anyhow!(
+                "Channel {} out of range for {} channels",
+                channel + 1,
+                self.data.len()
+            ));
+        }
+        let data = std::mem::take(&mut self.data);
+        self.data = data
+            .into_iter()
+            .enumerate()
+            .filter(|(c, _)| !drop.contains(&(*c as u16)))
+            .map(|(_, channel)| channel)
+            .collect();
+        if self.data.is_empty() {
+            return Err(anyhow!("Cannot remove every channel"));
+        }
 
-        self.channels = 1;
+        self.channels = self.data.len() as u16;
 
         Ok(())
     }
 
+    pub fn frames(&self) -> usize {
+        self.data.first().map_or(0, Vec::len)
+    }
+
     pub fn export(&self, output_file: &str) -> R<()> {
+        self.export_with_metadata(output_file, None)
+    }
+
+    /// Like `export`, but embeds `metadata` in the new file and checks that it decodes to this
+    /// buffer's channels and length before it replaces `output_file`.
+    pub fn export_with_metadata(
+        &self,
+        output_file: &str,
+        metadata: Option<&metadata::Metadata>,
+    ) -> R<()> {
         // Create a Path object for the output file
         let output_path = Path::new(output_file);
 
@@ -126,7 +171,13 @@ impl AudioBuffer {
         match get_encoder(output_file) {
             Ok(codec) => {
                 codec.encode_file(self, temp_path)?;
-                // codec.embed_file_metadata_chunks(temp_path, &self.metadata)?;
+                let finished = metadata
+                    .map_or(Ok(()), |metadata| metadata.set_metadata(temp_path))
+                    .and_then(|_| self.verify(&temp_file));
+                if let Err(e) = finished {
+                    let _ = std::fs::remove_file(&temp_file);
+                    return Err(e);
+                }
             }
             Err(error) => return Err(error),
         }
@@ -167,4 +218,21 @@ impl AudioBuffer {
             }
         }
     }
+
+    fn verify(&self, path: &Path) -> R<()> {
+        let mut frames = 0;
+        let (_, channels) = for_each_decoded_packet(path, |samples, channels| {
+            frames += samples.len() / channels.max(1);
+        })?;
+        if channels != self.channels || frames != self.frames() {
+            return Err(anyhow!(
+                "Written file has {} channels and {} frames, expected {} and {}",
+                channels,
+                frames,
+                self.channels,
+                self.frames()
+            ));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,365 @@
+// Headless front end for the search pipeline. Opens a Soundminer database by path,
+// runs the same steps as the app's `search` command and writes the results as JSON.
+
+use smdbc_lib::{
+    CompareKey, Database, Enabled, LogEmitter, NoopEmitter, Preferences, WaveformMatchType,
+    search_database,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const USAGE: &str = "\
+Usage: smdbc <DATABASE.sqlite> [options]
+
+Runs the SMDB Companion search pipeline without the app window and writes the
+records marked for removal as JSON.
+
+With --query, searches the database's stored fingerprints for sounds like FILE
+instead and writes the ranked matches.
+
+Settings:
+  --enabled <JSON|@FILE>    Enabled algorithms as JSON (inline or @path)
+  --prefs <JSON|@FILE>      Preferences as JSON (inline or @path)
+  Flags below are applied on top of any JSON settings.
+
+Algorithms:
+  --basic / --no-basic      Duplicate search on the match criteria (on by default)
+  --filename                Similar filename matching
+  --audiosuite              Audiosuite tag matching
+  --filetags                Mark filenames containing an autoselect tag
+  --invalid-path            Mark records whose file is missing
+  --duration <SECONDS>      Mark records shorter than SECONDS
+  --dual-mono               Analyze multichannel files for identical, inverted,
+                            near-identical and silent channels
+  --channel-tolerance <DB>  dBFS below which channels count as silent or alike
+                            (default: -60)
+  --waveform <TYPE>         Audio content comparison: exact, similar, subset,
+                            checksum or landmark
+  --compare <DATABASE>      Mark records that also exist in DATABASE; repeat
+                            to compare against several databases
+  --compare-key <KEY>       What --compare matches on: filename, root,
+                            filename-duration, checksum or fingerprint
+  --query <FILE>            Rank stored fingerprints by similarity to FILE
+
+Preferences:
+  --match <COL,COL,..>      Match criteria columns (default: Filename,Channels,Duration)
+  --tags <TAG,TAG,..>       Audiosuite tags
+  --autoselects <TAG,..>    Filename tags for --filetags
+  --ignore-filetype         Ignore file extensions when matching filenames
+  --threshold <PERCENT>     Similarity threshold for relative and subset matching
+                            (default: 80)
+  --fast-similarity         Index fingerprints for similar matching; much faster
+                            on large databases but may miss some matches
+  --batch-size <N>          Records processed per batch (default: 1000)
+  --no-store                Don't store fingerprints in the database
+  --no-fetch                Don't reuse fingerprints stored in the database
+
+Output:
+  -o, --output <FILE>       Write results to FILE instead of stdout
+  --all                     Include records that are kept, not just marked ones
+  -v, --verbose             Print detailed progress to stderr
+  -q, --quiet               Don't print progress
+  -h, --help                Show this help
+";
+
+const FLAGS_WITH_VALUE: [&str; 10] = [
+    "--channel-tolerance",
+    "--duration",
+    "--compare",
+    "--compare-key",
+    "--waveform",
+    "--match",
+    "--tags",
+    "--autoselects",
+    "--threshold",
+    "--batch-size",
+];
+
+struct Args {
+    database: PathBuf,
+    query: Option<PathBuf>,
+    enabled: Enabled,
+    pref: Preferences,
+    output: Option<PathBuf>,
+    all: bool,
+    verbose: bool,
+    quiet: bool,
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("smdbc: {}", e);
+            eprintln!("Try 'smdbc --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = tauri::async_runtime::block_on(run(args)) {
+        eprintln!("smdbc: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> anyhow::Result<()> {
+    let path = args
+        .database
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid database path"))?;
+    let db = Database::new(path, false).await;
+    if args.enabled.dbcompare {
+        for compare in args.enabled.compare_databases() {
+            if !PathBuf::from(&*compare).exists() {
+                anyhow::bail!("Compare database not found: {}", compare);
+            }
+        }
+    }
+
+    if let Some(query) = &args.query {
+        let matches = if args.quiet {
+            db.find_similar_sounds(query, &args.pref, &NoopEmitter)
+                .await
+        } else {
+            db.find_similar_sounds(query, &args.pref, &LogEmitter::new(args.verbose))
+                .await
+        }
+        .map_err(|e| anyhow::anyhow!(e))?;
+        eprintln!("{} matching sounds found", matches.len());
+        return write_json(&matches, args.output.as_deref());
+    }
+
+    let db = if args.quiet {
+        search_database(&NoopEmitter, db, args.enabled, args.pref).await
+    } else {
+        search_database(&LogEmitter::new(args.verbose), db, args.enabled, args.pref).await
+    }
+    .map_err(|e| anyhow::anyhow!(e))?;
+    for summary in db.compare_summary() {
+        eprintln!(
+            "{}: {} matching records, {} records in database",
+            summary.database, summary.matched, summary.records
+        );
+    }
+    let results = db.records_2_frontend().await;
+
+    let total = results.len();
+    let results: Vec<_> = results
+        .into_iter()
+        .filter(|record| args.all || record.is_marked())
+        .collect();
+    eprintln!("{} of {} records marked", results.len(), total);
+    write_json(&results, args.output.as_deref())
+}
+
+fn write_json(value: &impl serde::Serialize, output: Option<&Path>) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    match output {
+        Some(output) => {
+            std::fs::write(output, json)?;
+            eprintln!("Results written to {}", output.display());
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(json.as_bytes())?;
+            stdout.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_args(argv: Vec<String>) -> anyhow::Result<Args> {
+    let mut database = None;
+    let mut enabled_json = None;
+    let mut pref_json = None;
+    let mut output = None;
+    let mut query = None;
+    let mut all = false;
+    let mut verbose = false;
+    let mut quiet = false;
+    let mut flags: Vec<(String, Option<String>)> = Vec::new();
+
+    let mut argv = argv.into_iter();
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--enabled" => enabled_json = Some(next_value(&mut argv, &arg)?),
+            "--prefs" => pref_json = Some(next_value(&mut argv, &arg)?),
+            "-o" | "--output" => output = Some(PathBuf::from(next_value(&mut argv, &arg)?)),
+            "--query" => query = Some(PathBuf::from(next_value(&mut argv, &arg)?)),
+            "--all" => all = true,
+            "-v" | "--verbose" => verbose = true,
+            "-q" | "--quiet" => quiet = true,
+            flag if flag.starts_with('-') => {
+                let value = if FLAGS_WITH_VALUE.contains(&flag) {
+                    Some(next_value(&mut argv, &arg)?)
+                } else {
+                    None
+                };
+                flags.push((arg, value));
+            }
+            _ => {
+                if database.is_some() {
+                    anyhow::bail!("Unexpected argument: {}", arg);
+                }
+                database = Some(PathBuf::from(arg));
+            }
+        }
+    }
+
+    let database = database.ok_or_else(|| anyhow::anyhow!("No database given"))?;
+    if !database.exists() {
+        anyhow::bail!("Database not found: {}", database.display());
+    }
+
+    let mut enabled = match enabled_json {
+        Some(json) => serde_json::from_str(&read_json_arg(&json)?)?,
+        None => Enabled {
+            basic: true,
+            ..Default::default()
+        },
+    };
+    let mut pref = match pref_json {
+        Some(json) => serde_json::from_str(&read_json_arg(&json)?)?,
+        None => default_preferences(),
+    };
+
+    for (flag, value) in flags {
+        apply_flag(&mut enabled, &mut pref, &flag, value.as_deref())?;
+    }
+
+    // JSON settings may leave these unset, and both would break the search
+    if pref.batch_size == 0 {
+        pref.batch_size = 1000;
+    }
+    if pref.match_criteria.is_empty() {
+        pref.match_criteria = default_preferences().match_criteria;
+    }
+
+    if let Some(query) = &query
+        && !query.exists()
+    {
+        anyhow::bail!("Query file not found: {}", query.display());
+    }
+
+    Ok(Args {
+        database,
+        query,
+        enabled,
+        pref,
+        output,
+        all,
+        verbose,
+        quiet,
+    })
+}
+
+fn apply_flag(
+    enabled: &mut Enabled,
+    pref: &mut Preferences,
+    flag: &str,
+    value: Option<&str>,
+) -> anyhow::Result<()> {
+    let value = value.unwrap_or_default();
+    match flag {
+        "--basic" => enabled.basic = true,
+        "--no-basic" => enabled.basic = false,
+        "--filename" => enabled.filename = true,
+        "--audiosuite" => enabled.audiosuite = true,
+        "--filetags" => enabled.filetags = true,
+        "--invalid-path" => enabled.invalidpath = true,
+        "--dual-mono" => enabled.dual_mono = true,
+        "--duration" => {
+            enabled.duration = true;
+            enabled.min_dur = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid duration: {}", value))?;
+        }
+        "--compare" => {
+            enabled.dbcompare = true;
+            enabled.compare_dbs.push(Arc::from(value));
+        }
+        "--compare-key" => {
+            enabled.compare_key = match value.to_lowercase().as_str() {
+                "filename" => CompareKey::Filename,
+                "root" => CompareKey::Root,
+                "filename-duration" => CompareKey::FilenameDuration,
+                "checksum" => CompareKey::Checksum,
+                "fingerprint" => CompareKey::Fingerprint,
+                _ => anyhow::bail!("Unknown compare key: {}", value),
+            };
+        }
+        "--waveform" => {
+            enabled.waveform = true;
+            pref.waveform_search_type = match value.to_lowercase().as_str() {
+                "exact" => WaveformMatchType::Exact,
+                "similar" => WaveformMatchType::Similar,
+                "subset" => WaveformMatchType::Subset,
+                "checksum" => WaveformMatchType::Checksum,
+                "landmark" => WaveformMatchType::Landmark,
+                _ => anyhow::bail!("Unknown waveform match type: {}", value),
+            };
+        }
+        "--match" => pref.match_criteria = split_list(value),
+        "--tags" => pref.tags = split_list(value),
+        "--autoselects" => pref.autoselects = split_list(value),
+        "--ignore-filetype" => pref.ignore_filetype = true,
+        "--threshold" => {
+            pref.similarity_threshold = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid threshold: {}", value))?;
+        }
+        "--fast-similarity" => pref.fast_similarity = true,
+        "--channel-tolerance" => {
+            pref.channel_tolerance_db = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid channel tolerance: {}", value))?;
+        }
+        "--batch-size" => {
+            pref.batch_size = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid batch size: {}", value))?;
+        }
+        "--no-store" => pref.store_waveforms = false,
+        "--no-fetch" => pref.fetch_waveforms = false,
+        _ => anyhow::bail!("Unknown option: {}", flag),
+    }
+    Ok(())
+}
+
+fn next_value(argv: &mut impl Iterator<Item = String>, flag: &str) -> anyhow::Result<String> {
+    argv.next()
+        .ok_or_else(|| anyhow::anyhow!("{} requires a value", flag))
+}
+
+fn read_json_arg(arg: &str) -> anyhow::Result<String> {
+    match arg.strip_prefix('@') {
+        Some(path) => Ok(std::fs::read_to_string(path)?),
+        None => Ok(arg.to_string()),
+    }
+}
+
+fn split_list(value: &str) -> Vec<Arc<str>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(Arc::from)
+        .collect()
+}
+
+// Mirrors the app's default preferences in src/stores/preferences.ts
+fn default_preferences() -> Preferences {
+    Preferences {
+        match_criteria: split_list("Filename,Channels,Duration"),
+        similarity_threshold: 80.0,
+        store_waveforms: true,
+        fetch_waveforms: true,
+        batch_size: 1000,
+        ..Default::default()
+    }
+}
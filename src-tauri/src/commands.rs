@@ -90,7 +90,7 @@ pub async fn search(
     };
 
     let handle = tokio::spawn(async move {
-        let result = run_search(app, db, enabled, pref).await;
+        let result = search_database(&app, db, enabled, pref).await;
         let _ = tx.send(result);
     });
 
@@ -115,9 +115,15 @@ pub async fn search(
         // Wait for search to complete
         result = rx => {
             match result {
-                Ok(result) => {
-                   result
+                Ok(Ok(db)) => {
+                    // Keep the results so overrides and exports act on what the user sees
+                    let results = db.records_2_frontend().await;
+                    let mut state = state.lock().await;
+                    state.db.records = db.records;
+                    state.db.compare_summary = db.compare_summary;
+                    Ok(results)
                 }
+                Ok(Err(e)) => Err(e),
                 Err(_) => {
                     Err("Fingerprinting task aborted or failed".to_string())
                 }
@@ -126,16 +132,26 @@ pub async fn search(
     }
 }
 
-async fn run_search(
-    app: AppHandle,
-    mut db: Database,
+/// Runs the full search pipeline (fetch, compare, dupe, dual mono, waveform) against `db`.
+/// Shared by the `search` command and the headless `smdbc` binary.
+pub async fn run_search<E: StatusEmitter>(
+    app: &E,
+    db: Database,
     enabled: Enabled,
     pref: Preferences,
 ) -> Result<Vec<FileRecordFrontend>, String> {
-    println!("Starting Search");
+    let db = search_database(app, db, enabled, pref).await?;
+    Ok(db.records_2_frontend().await)
+}
 
-    let _ = db.add_column("_fingerprint").await;
-    let _ = db.add_column("_DualMono").await;
+/// Same as `run_search` but hands back the searched database instead of frontend records.
+pub async fn search_database<E: StatusEmitter>(
+    app: &E,
+    mut db: Database,
+    enabled: Enabled,
+    pref: Preferences,
+) -> Result<Database, String> {
+    println!("Starting Search");
 
     let mut counter = 0;
     let mut total = 1;
@@ -153,21 +169,35 @@ async fn run_search(
     app.substatus("starting", 0, "Gathering records from database...");
     counter += 1;
 
-    let _ = db.fetch_all_filerecords(&enabled, &pref, &app).await;
+    let _ = db.fetch_all_filerecords(&enabled, &pref, app).await;
     if db.abort.load(Ordering::SeqCst) {
         println!("Aborting fingerprint scan - early exit");
         return Err("Aborted".to_string());
     }
 
+    let rescan = enabled.waveform
+        || enabled.dual_mono
+        || (enabled.dbcompare
+            && matches!(
+                enabled.compare_key,
+                CompareKey::Checksum | CompareKey::Fingerprint
+            ));
+    if rescan {
+        let _ = db.refresh_scan_state(&pref, app).await;
+    }
+
     if enabled.dbcompare {
         counter += 1;
         app.status(
             "compare",
             counter * 100 / total,
-            &format!("Comparing records against {}", enabled.compare_db),
+            &format!(
+                "Comparing records against {}",
+                enabled.compare_databases().join(", ")
+            ),
         );
 
-        db.compare_search(&enabled, &pref, &app).await;
+        db.compare_search(&enabled, &pref, app).await;
     }
     if db.abort.load(Ordering::SeqCst) {
         println!("Aborting fingerprint scan - early exit");
@@ -182,7 +212,7 @@ async fn run_search(
             "Performing Duplicate Search",
         );
 
-        db.dupe_search(&pref, &enabled, &app);
+        db.dupe_search(&pref, &enabled, app);
 
         app.substatus("starting", 10, "Sorting Records");
 
@@ -196,7 +226,7 @@ async fn run_search(
             "Performing Dual Mono Search",
         );
 
-        db.dual_mono_search(&pref, &app).await;
+        db.dual_mono_search(&pref, app).await;
     }
     if db.abort.load(Ordering::SeqCst) {
         println!("Aborting fingerprint scan - early exit");
@@ -210,25 +240,78 @@ async fn run_search(
             "Analyzing audio content for waveform analysis",
         );
 
-        let _ = db.wave_search_chromaprint(&pref, &app).await;
+        let _ = db.wave_search_chromaprint(&pref, app).await;
     }
-    {}
+    if rescan && pref.store_waveforms {
+        let _ = db.save_scan_state(app).await;
+    }
+    db.merge_groups(&pref);
+    let _ = db.apply_manual_keepers().await;
     app.status("complete", 100, "Search completed! Gathering Results");
 
     println!("Search Ended");
-    Ok(db.records_2_frontend().await)
+    Ok(db)
 }
 
 #[tauri::command]
 pub async fn clear_fingerprints(state: State<'_, Mutex<AppState>>) -> Result<Arc<str>, String> {
     println!("Clearing Fingerprints");
     let state = state.lock().await;
-    let _ = state.db.remove_column("_fingerprint").await;
+    let _ = state.db.clear_fingerprints().await;
     println!("Fingerprints Cleared");
     Ok(state.db.get_name().unwrap_or(Arc::from("Select Database")))
 }
 
 #[tauri::command]
+pub async fn get_compare_summary(
+    state: State<'_, Mutex<AppState>>,
+) -> Result<Vec<CompareSummary>, String> {
+    let state = state.lock().await;
+    Ok(state.db.compare_summary().to_vec())
+}
+
+#[tauri::command]
+pub async fn find_similar_sounds(
+    app: AppHandle,
+    state: State<'_, Mutex<AppState>>,
+    path: String,
+    pref: Preferences,
+) -> Result<Vec<SoundMatch>, String> {
+    let db = {
+        let state = state.lock().await;
+        state.db.abort.store(false, Ordering::SeqCst);
+        state.db.clone()
+    };
+    db.find_similar_sounds(Path::new(&path), &pref, &app).await
+}
+
+#[tauri::command]
+pub async fn get_bext(path: String) -> Result<Option<Bext>, String> {
+    read_bext(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_bext(
+    state: State<'_, Mutex<AppState>>,
+    path: String,
+    bext: Bext,
+) -> Result<(), String> {
+    write_bext(Path::new(&path), &bext).map_err(|e| e.to_string())?;
+    let db = state.lock().await.db.clone();
+    db.mark_dirty(&path).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn diff_group_metadata(
+    state: State<'_, Mutex<AppState>>,
+    ids: Vec<usize>,
+) -> Result<MetadataDiff, String> {
+    let db = state.lock().await.db.clone();
+    db.diff_metadata(&ids).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn remove_records(
     state: State<'_, Mutex<AppState>>,
     app: AppHandle,
@@ -239,16 +322,18 @@ pub async fn remove_records(
     files: Vec<&str>,
     dual_mono: Vec<DualMono>,
     strip_dual_mono: bool,
+    strip_quiet_channels: bool,
+    merge: Option<MergeOptions>,
 ) -> Result<Arc<str>, String> {
     println!("Removing Records");
     println!("Dual Mono: {:?}", dual_mono);
     let mut state = state.lock().await;
 
-    if strip_dual_mono {
-        app.rstatus("starting", 0, "Stripping Dual Mono Records...");
-
-        let _ = state.db.clean_multi_mono(&app, &dual_mono).await;
-    }
+    let strips = if strip_dual_mono {
+        state.db.channel_strips(&dual_mono, strip_quiet_channels)
+    } else {
+        Vec::new()
+    };
 
     if clone {
         app.rstatus(
@@ -259,6 +344,70 @@ pub async fn remove_records(
 
         state.db = state.db.create_clone(&clone_tag).await;
     }
+
+    // Duplicates have to be read before their rows are gone
+    let merges = match merge.as_ref() {
+        Some(merge) => state
+            .db
+            .plan_merge(&records, merge)
+            .await
+            .map_err(|e| format!("Failed to merge metadata into keepers: {}", e))?,
+        None => Vec::new(),
+    };
+    app.rstatus("starting", 25, "Writing Undo Journal...");
+
+    // Nothing is removed or merged unless it can be undone
+    let Some(path) = state.db.path.clone() else {
+        return Err("No database is open".to_string());
+    };
+    let mut journal = RemovalJournal::new(&path);
+    // Keepers get merged metadata, stripped files a new channel count
+    let updated: Vec<usize> = merges
+        .iter()
+        .map(|merge| merge.id)
+        .chain(strips.iter().map(|strip| strip.id))
+        .collect();
+    state
+        .db
+        .journal_rows(&records, &mut journal)
+        .await
+        .map_err(|e| format!("Failed to write undo journal: {}", e))?;
+    state
+        .db
+        .journal_updated_rows(&updated, &mut journal)
+        .await
+        .map_err(|e| format!("Failed to write undo journal: {}", e))?;
+    // The safety copy shares its files with the original database, which must not change
+    let embed = !clone && merge.as_ref().is_some_and(|merge| merge.embed);
+    let embedded = merges
+        .iter()
+        .filter(|_| embed)
+        .map(|merge| Path::new(&*merge.path));
+    // Missing files are skipped by the strip and reported by the embed
+    for path in strips
+        .iter()
+        .map(|strip| Path::new(&strip.path))
+        .chain(embedded)
+        .filter(|path| path.exists())
+    {
+        journal
+            .back_up(path)
+            .map_err(|e| format!("Failed to write undo journal: {}", e))?;
+    }
+    journal
+        .save()
+        .map_err(|e| format!("Failed to write undo journal: {}", e))?;
+
+    if !strips.is_empty() {
+        app.rstatus("starting", 27, "Stripping Redundant Channels...");
+        let _ = state.db.clean_multi_mono(&app, &strips).await;
+    }
+
+    state
+        .db
+        .merge_into_keepers(&app, &merges, embed)
+        .await
+        .map_err(|e| format!("Failed to merge metadata into keepers: {}", e))?;
     app.rstatus("starting", 30, "Removing Records from Database...");
 
     let _ = state.db.remove(&records, &app).await;
@@ -268,11 +417,18 @@ pub async fn remove_records(
         match delete {
             Delete::Trash => "Moving files to Trash",
             Delete::Delete => "Deleting Files",
+            Delete::Quarantine { .. } => "Moving files to Quarantine",
             Delete::Keep => "Cleaning up....",
         },
     );
 
-    let _ = delete.delete_files(files, &app);
+    let removed = delete.delete_files(files, &app);
+    if let Ok(removed) = removed {
+        journal.files = removed;
+        if let Err(e) = journal.save() {
+            println!("Failed to record removed files in undo journal: {}", e);
+        }
+    }
 
     println!("Remove Ended");
     app.rstatus("complete", 100, "Success! Removal is complete");
@@ -280,6 +436,116 @@ pub async fn remove_records(
     Ok(state.db.get_name().unwrap_or(Arc::from("Select Database")))
 }
 
+#[tauri::command]
+pub async fn undo_last_removal(
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<UndoSummary, String> {
+    println!("Undoing Last Removal");
+    let state = state.lock().await;
+    let Some(path) = state.db.path.as_deref() else {
+        return Err("No database is open".to_string());
+    };
+    let Some(journal) = RemovalJournal::latest(path).map_err(|e| e.to_string())? else {
+        return Err("Nothing to undo for this database".to_string());
+    };
+
+    let summary = state
+        .db
+        .undo_removal(&journal, &app)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!("Undo Ended");
+    app.rstatus("complete", 100, "Undo is complete");
+
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn restore_quarantine(
+    app: AppHandle,
+    root: String,
+    files: Vec<String>,
+) -> Result<RestoreSummary, String> {
+    println!("Restoring Quarantine: {}", root);
+    app.rstatus("starting", 0, "Restoring Quarantined Files...");
+    let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
+    let summary = restore_quarantined(Path::new(&root), &files, &app).map_err(|e| e.to_string())?;
+    app.rstatus("complete", 100, "Restore is complete");
+    Ok(summary)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn plan_removal(
+    state: State<'_, Mutex<AppState>>,
+    clone: bool,
+    clone_tag: String,
+    records: Vec<usize>,
+    delete: Delete,
+    files: Vec<&str>,
+    dual_mono: Vec<DualMono>,
+    strip_dual_mono: bool,
+    strip_quiet_channels: bool,
+) -> Result<RemovalPlan, String> {
+    println!("Planning Removal");
+    let state = state.lock().await;
+    state
+        .db
+        .plan_removal(
+            clone,
+            &clone_tag,
+            &records,
+            &delete,
+            &files,
+            &dual_mono,
+            strip_dual_mono,
+            strip_quiet_channels,
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn export_removal_plan(plan: RemovalPlan, path: String) -> Result<(), String> {
+    plan.export(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn export_results(
+    state: State<'_, Mutex<AppState>>,
+    path: String,
+    format: ExportFormat,
+    columns: Vec<Arc<str>>,
+    marked_only: bool,
+) -> Result<usize, String> {
+    println!("Exporting Results to {}", path);
+    let state = state.lock().await;
+    state
+        .db
+        .export_results(Path::new(&path), format, &columns, marked_only)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_groups(state: State<'_, Mutex<AppState>>) -> Result<Vec<DuplicateGroup>, String> {
+    let state = state.lock().await;
+    Ok(state.db.groups())
+}
+
+#[tauri::command]
+pub async fn set_keeper(
+    state: State<'_, Mutex<AppState>>,
+    id: usize,
+) -> Result<Vec<FileRecordFrontend>, String> {
+    println!("Setting Keeper: {}", id);
+    let mut state = state.lock().await;
+    state.db.set_keeper(id).await.map_err(|e| e.to_string())?;
+    Ok(state.db.records_2_frontend().await)
+}
+
 #[tauri::command]
 pub async fn find(
     state: State<'_, Mutex<AppState>>,
@@ -297,7 +563,7 @@ pub async fn find(
         let case = if case_sensitive { "GLOB" } else { "LIKE" };
         let query =
             // format!("SELECT rowid, filepath, duration FROM {TABLE} WHERE {column} {case} ?");
-            format!("SELECT rowid, filepath, duration, _fingerprint, description, channels, bitdepth, samplerate, _DualMono  FROM {TABLE} WHERE {column} {case} ?");
+            format!("SELECT rowid, filepath, duration, description, channels, bitdepth, samplerate FROM {TABLE} WHERE {column} {case} ?");
 
         // Get pool with error handling
         let pool = state.db.get_pool().await.unwrap();
@@ -466,6 +732,11 @@ pub async fn get_results(
                 bitdepth: record.bitdepth,
                 samplerate: record.samplerate,
                 channels: record.channels,
+                group: record.group,
+                role: record.role,
+                subset: record.subset,
+                compare_match: record.compare_match.clone(),
+                channel_analysis: record.channel_analysis.clone(),
             }
         })
         .collect(); // Parallel collect
@@ -0,0 +1,204 @@
+use crate::prelude::*;
+
+/// Where a compared field lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum DiffSource {
+    Database,
+    Bext,
+    IXml,
+    Vorbis,
+    /// AIFF text chunks such as NAME and ANNO
+    Chunk,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDiff {
+    pub source: DiffSource,
+    pub field: Arc<str>,
+    /// One value per record, in the order of `MetadataDiff::ids`
+    pub values: Vec<Option<Arc<str>>>,
+    /// The filled in values don't all agree
+    pub differs: bool,
+    /// Empty in the keeper but filled in at least one duplicate
+    pub keeper_missing: bool,
+}
+
+/// Field by field comparison of the records of a duplicate group, keeper first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetadataDiff {
+    pub keeper: Option<usize>,
+    pub ids: Vec<usize>,
+    pub fields: Vec<FieldDiff>,
+}
+
+type Fields = Vec<(DiffSource, Arc<str>, Arc<str>)>;
+
+impl Database {
+    /// Compares the database rows and embedded file metadata of `ids`. The keeper of their
+    /// group in the current results goes first, otherwise the first id is taken as keeper.
+    pub async fn diff_metadata(&self, ids: &[usize]) -> Result<MetadataDiff, sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Err(sqlx::Error::Configuration(
+                "No database connection available".into(),
+            ));
+        };
+
+        let keeper = self
+            .records
+            .iter()
+            .find(|r| ids.contains(&r.id) && r.role == Some(GroupRole::Keeper))
+            .map(|r| r.id)
+            .or_else(|| ids.first().copied());
+        let mut ids = ids.to_vec();
+        if let Some(keeper) = keeper
+            && let Some(position) = ids.iter().position(|&id| id == keeper)
+        {
+            ids.remove(position);
+            ids.insert(0, keeper);
+        }
+
+        let columns = self.fetch_columns().await?;
+        let select = columns
+            .iter()
+            .map(|c| format!("\"{}\"", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders = std::iter::repeat_n("?", ids.len())
+            .collect::<Vec<_>>()
+            .join(",");
+        let query = format!(
+            "SELECT rowid, filepath, {} FROM {} WHERE rowid IN ({})",
+            select, TABLE, placeholders
+        );
+        let mut query_builder = sqlx::query(&query);
+        for &id in &ids {
+            query_builder = query_builder.bind(id as i64);
+        }
+        let rows = query_builder.fetch_all(&pool).await?;
+
+        let per_record: Vec<Fields> = ids
+            .par_iter()
+            .map(|&id| {
+                let Some(row) = rows
+                    .iter()
+                    .find(|row| row.try_get::<i64, _>(0).ok() == Some(id as i64))
+                else {
+                    return Vec::new();
+                };
+                let mut fields: Fields = columns
+                    .iter()
+                    .filter_map(|column| {
+                        let value = get_column_as_string(row, column)?;
+                        Some((DiffSource::Database, column.clone(), value))
+                    })
+                    .collect();
+                if let Some(path) = get_column_as_string(row, "filepath") {
+                    fields.extend(file_fields(Path::new(&*path)));
+                }
+                fields
+            })
+            .collect();
+
+        // Fields in the order they are first seen, keeper first
+        let mut order: Vec<(DiffSource, Arc<str>)> = Vec::new();
+        let mut index: HashMap<(DiffSource, Arc<str>), usize> = HashMap::new();
+        let mut values: Vec<Vec<Option<Arc<str>>>> = Vec::new();
+        for (record, fields) in per_record.into_iter().enumerate() {
+            for (source, field, value) in fields {
+                let i = *index.entry((source, field.clone())).or_insert_with(|| {
+                    order.push((source, field));
+                    values.push(vec![None; ids.len()]);
+                    values.len() - 1
+                });
+                values[i][record] = Some(value);
+            }
+        }
+
+        let fields = order
+            .into_iter()
+            .zip(values)
+            .map(|((source, field), values)| {
+                let filled: Vec<&str> = values
+                    .iter()
+                    .flatten()
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+                let keeper_empty = values
+                    .first()
+                    .and_then(|v| v.as_deref())
+                    .is_none_or(|v| v.trim().is_empty());
+                FieldDiff {
+                    source,
+                    field,
+                    differs: filled.windows(2).any(|pair| pair[0] != pair[1]),
+                    keeper_missing: keeper_empty && !filled.is_empty(),
+                    values,
+                }
+            })
+            .collect();
+
+        Ok(MetadataDiff {
+            keeper,
+            ids,
+            fields,
+        })
+    }
+}
+
+fn file_fields(path: &Path) -> Fields {
+    let metadata = metadata::Metadata::get_metadata(path);
+    let mut fields: Fields = Vec::new();
+
+    if let Some(bext) = metadata.bext() {
+        for (field, value) in bext.fields() {
+            fields.push((DiffSource::Bext, Arc::from(field), Arc::from(value)));
+        }
+    }
+    if let Some(ixml) = metadata.ixml() {
+        push_numbered(&mut fields, DiffSource::IXml, ixml.text_fields());
+    }
+    match &metadata {
+        metadata::Metadata::Flac(tag) => {
+            if let Some(comments) = tag.vorbis_comments() {
+                let mut keys: Vec<&String> = comments.comments.keys().collect();
+                keys.sort();
+                for key in keys {
+                    let value = comments.comments[key].join("; ");
+                    fields.push((
+                        DiffSource::Vorbis,
+                        Arc::from(key.as_str()),
+                        Arc::from(value),
+                    ));
+                }
+            }
+        }
+        metadata::Metadata::Aiff(chunks) => {
+            let text = chunks
+                .iter()
+                .filter_map(|chunk| match chunk {
+                    MetadataChunk::TextTag { key, value } => Some((key.clone(), value.clone())),
+                    _ => None,
+                })
+                .collect();
+            push_numbered(&mut fields, DiffSource::Chunk, text);
+        }
+        metadata::Metadata::Wav(_) => {}
+    }
+    fields
+}
+
+// Repeated keys, like the NAME of every iXML track, get numbered so they line up by position
+fn push_numbered(fields: &mut Fields, source: DiffSource, values: Vec<(String, String)>) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (key, value) in values {
+        let count = seen.entry(key.clone()).or_default();
+        *count += 1;
+        let key = if *count > 1 {
+            format!("{} {}", key, count)
+        } else {
+            key
+        };
+        fields.push((source, Arc::from(key), Arc::from(value)));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::prelude::*;
+
+/// Quotes a field only when it needs it, doubling any embedded quotes.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|f| csv_field(f.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    PathList,
+}
+
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    id: usize,
+    filepath: &'a str,
+    filename: &'a str,
+    status: &'static str,
+    algorithm: Vec<&'a Algorithm>,
+    group: Option<usize>,
+    role: Option<GroupRole>,
+    keeper: Option<&'a str>,
+    subset: Option<SubsetOf>,
+    compare_match: Option<&'a CompareMatch>,
+    channel_analysis: Option<&'a ChannelAnalysis>,
+    columns: HashMap<&'a str, Option<Arc<str>>>,
+}
+
+impl Database {
+    /// Writes the current search results to `path`. Returns how many records were written.
+    pub async fn export_results(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+        columns: &[Arc<str>],
+        marked_only: bool,
+    ) -> R<usize> {
+        let records: Vec<&FileRecord> = self
+            .records
+            .iter()
+            .filter(|r| !marked_only || r.is_marked())
+            .collect();
+
+        let mut contents = String::new();
+        match format {
+            ExportFormat::PathList => {
+                for record in &records {
+                    contents.push_str(record.get_filepath());
+                    contents.push('\n');
+                }
+            }
+            ExportFormat::Csv | ExportFormat::JsonLines => {
+                let ids: Vec<usize> = records.iter().map(|r| r.id).collect();
+                let values = self.fetch_column_values(&ids, columns).await?;
+                let keepers: HashMap<usize, &str> = self
+                    .records
+                    .iter()
+                    .filter(|r| r.get_role() == Some(GroupRole::Keeper))
+                    .filter_map(|r| Some((r.get_group()?, r.get_filepath())))
+                    .collect();
+
+                if format == ExportFormat::Csv {
+                    let mut header = vec![
+                        "id",
+                        "filepath",
+                        "filename",
+                        "status",
+                        "algorithm",
+                        "group",
+                        "role",
+                        "keeper",
+                        "subset_of",
+                        "subset_start",
+                        "subset_end",
+                        "subset_score",
+                        "compare_match",
+                        "compare_score",
+                        "channels",
+                    ];
+                    header.extend(columns.iter().map(|c| &**c));
+                    contents.push_str(&csv_row(&header));
+                }
+
+                for record in records.iter() {
+                    let keeper = record.get_group().and_then(|g| keepers.get(&g).copied());
+                    let row = export_record(record, columns, values.get(&record.id), keeper);
+                    if format == ExportFormat::JsonLines {
+                        contents.push_str(&serde_json::to_string(&row)?);
+                        contents.push('\n');
+                        continue;
+                    }
+                    let mut fields = vec![
+                        row.id.to_string(),
+                        row.filepath.to_string(),
+                        row.filename.to_string(),
+                        row.status.to_string(),
+                        row.algorithm
+                            .iter()
+                            .map(|a| format!("{:?}", a))
+                            .collect::<Vec<_>>()
+                            .join(";"),
+                        row.group.map(|g| g.to_string()).unwrap_or_default(),
+                        row.role.map(|r| format!("{:?}", r)).unwrap_or_default(),
+                        row.keeper.unwrap_or_default().to_string(),
+                    ];
+                    fields.extend(match row.subset {
+                        Some(subset) => [
+                            subset.parent.to_string(),
+                            format!("{:.3}", subset.start),
+                            format!("{:.3}", subset.end),
+                            format!("{:.3}", subset.score),
+                        ],
+                        None => Default::default(),
+                    });
+                    fields.extend(match row.compare_match {
+                        Some(compare) => {
+                            [compare.path.to_string(), format!("{:.3}", compare.score)]
+                        }
+                        None => Default::default(),
+                    });
+                    fields.push(
+                        row.channel_analysis
+                            .map(|analysis| analysis.summary())
+                            .unwrap_or_default(),
+                    );
+                    fields.extend(columns.iter().map(|c| {
+                        row.columns
+                            .get(&**c)
+                            .cloned()
+                            .flatten()
+                            .map(|v| v.to_string())
+                            .unwrap_or_default()
+                    }));
+                    contents.push_str(&csv_row(&fields));
+                }
+            }
+        }
+
+        fs::write(path, contents)?;
+        Ok(records.len())
+    }
+
+    /// Reads `columns` for each id straight from the database, in column order.
+    async fn fetch_column_values(
+        &self,
+        ids: &[usize],
+        columns: &[Arc<str>],
+    ) -> R<HashMap<usize, Vec<Option<Arc<str>>>>> {
+        const BATCH_SIZE: usize = 12321;
+        let mut values = HashMap::new();
+        if columns.is_empty() {
+            return Ok(values);
+        }
+        let pool = self
+            .get_pool()
+            .await
+            .ok_or_else(|| anyhow!("No database connection available"))?;
+        let select = columns
+            .iter()
+            .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let placeholders = std::iter::repeat_n("?", chunk.len())
+                .collect::<Vec<_>>()
+                .join(",");
+            let query = format!(
+                "SELECT rowid, {} FROM {} WHERE rowid IN ({})",
+                select, TABLE, placeholders
+            );
+            let mut query_builder = sqlx::query(&query);
+            for &id in chunk {
+                query_builder = query_builder.bind(id as i64);
+            }
+            for row in query_builder.fetch_all(&pool).await? {
+                let id = row.get::<i64, _>(0) as usize;
+                let row_values = columns
+                    .iter()
+                    .map(|c| get_column_as_string(&row, c))
+                    .collect();
+                values.insert(id, row_values);
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn export_record<'a>(
+    record: &'a FileRecord,
+    columns: &'a [Arc<str>],
+    values: Option<&Vec<Option<Arc<str>>>>,
+    keeper: Option<&'a str>,
+) -> ExportRecord<'a> {
+    let mut algorithm: Vec<&Algorithm> = record.algorithm.iter().collect();
+    algorithm.sort();
+    ExportRecord {
+        id: record.id,
+        filepath: record.get_filepath(),
+        filename: record.get_filename(),
+        status: if record.is_marked() { "Remove" } else { "Keep" },
+        algorithm,
+        group: record.get_group(),
+        role: record.get_role(),
+        keeper,
+        subset: record.get_subset(),
+        compare_match: record.get_compare_match(),
+        channel_analysis: record.get_channel_analysis(),
+        columns: columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (&**c, values.and_then(|v| v[i].clone())))
+            .collect(),
+    }
+}
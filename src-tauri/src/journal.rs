@@ -0,0 +1,522 @@
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedFile {
+    pub original: PathBuf,
+    pub action: Delete,
+    pub location: Option<PathBuf>, // where the file ended up, if we know
+}
+
+/// A file changed in place, with the copy of it taken beforehand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewrittenFile {
+    pub original: PathBuf,
+    pub backup: PathBuf,
+}
+
+/// Everything needed to reverse one `remove_records` session.
+/// Rows are stored as SQLite `quote()` literals so every value type round-trips exactly.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RemovalJournal {
+    pub id: String,
+    pub database: PathBuf,
+    pub created: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>, // quote(rowid) followed by one literal per column
+    /// Rows of the keepers metadata was merged into, as they were before, laid out like `rows`
+    #[serde(default)]
+    pub updated: Vec<Vec<String>>,
+    pub files: Vec<RemovedFile>,
+    /// Files whose channels were stripped or metadata rewritten
+    #[serde(default)]
+    pub rewritten: Vec<RewrittenFile>,
+    #[serde(skip)]
+    dir: PathBuf, // folder the journal is saved in
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UndoSummary {
+    pub records: usize,
+    pub files: usize,
+    pub failed: Vec<String>,
+    /// Files in the macOS Trash, which only the Finder's Put Back can return
+    pub put_back: Vec<String>,
+}
+
+impl RemovalJournal {
+    pub fn new(database: &Path) -> Self {
+        Self::new_in(&Self::dir(database), database)
+    }
+
+    /// A journal kept in `dir` instead of the app's data folder.
+    pub fn new_in(dir: &Path, database: &Path) -> Self {
+        let now = chrono::Local::now();
+        Self {
+            id: now.format("%Y%m%d-%H%M%S%.3f").to_string(),
+            database: database.to_path_buf(),
+            created: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            dir: dir.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    fn dir(database: &Path) -> PathBuf {
+        let base = dirs::data_dir().unwrap_or_else(env::temp_dir);
+        let stem = database
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("database");
+        let hash = hex::encode(Sha256::digest(database.to_string_lossy().as_bytes()));
+        base.join("SMDB Companion")
+            .join("journal")
+            .join(format!("{}-{}", stem, &hash[..8]))
+    }
+
+    fn file_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.json", self.id))
+    }
+
+    pub fn save(&self) -> R<PathBuf> {
+        let path = self.file_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_vec(self)?)?;
+        println!("Removal journal written to {}", path.display());
+        Ok(path)
+    }
+
+    /// Most recent session for `database` that hasn't been undone yet.
+    pub fn latest(database: &Path) -> R<Option<Self>> {
+        Self::latest_in(&Self::dir(database))
+    }
+
+    pub fn latest_in(dir: &Path) -> R<Option<Self>> {
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let newest = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "json")
+                    && !path.to_string_lossy().ends_with(".undone.json")
+            })
+            .max();
+
+        let Some(path) = newest else {
+            return Ok(None);
+        };
+        let mut journal: Self = serde_json::from_slice(&fs::read(&path)?)?;
+        journal.dir = dir.to_path_buf();
+        Ok(Some(journal))
+    }
+
+    /// Copies `path` next to the journal before it is changed in place.
+    pub fn back_up(&mut self, path: &Path) -> R<()> {
+        let folder = self.dir.join(&self.id);
+        fs::create_dir_all(&folder)?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Not a file: {}", path.display()))?;
+        // Numbered, since files in different folders can share a name
+        let backup = folder.join(format!(
+            "{}-{}",
+            self.rewritten.len(),
+            name.to_string_lossy()
+        ));
+        fs::copy(path, &backup).with_context(|| format!("Failed to back up {}", path.display()))?;
+        self.rewritten.push(RewrittenFile {
+            original: path.to_path_buf(),
+            backup,
+        });
+        Ok(())
+    }
+
+    pub fn mark_undone(&self) -> R<()> {
+        let path = self.file_path();
+        fs::rename(&path, path.with_extension("undone.json"))?;
+        Ok(())
+    }
+
+    /// Puts the session's files back, counting them in `summary`.
+    pub fn restore_files<E: StatusEmitter>(&self, app: &E, summary: &mut UndoSummary) {
+        let total = self.files.len().max(1);
+        #[cfg(not(target_os = "macos"))]
+        let mut trashed = trash_index(&self.files);
+
+        for (i, file) in self.files.iter().enumerate() {
+            app.rsubstatus(
+                "undo",
+                i * 100 / total,
+                &format!("Restoring files: {}/{}", i + 1, self.files.len()),
+            );
+            let result = match &file.action {
+                Delete::Keep => continue,
+                Delete::Delete => Err(anyhow!("File was permanently deleted")),
+                #[cfg(target_os = "macos")]
+                Delete::Trash => {
+                    summary.put_back.push(file.original.display().to_string());
+                    continue;
+                }
+                #[cfg(not(target_os = "macos"))]
+                Delete::Trash => restore_from_trash(file, &mut trashed),
+                Delete::Quarantine { root } => restore_from_quarantine(file, root),
+            };
+            match result {
+                Ok(_) => summary.files += 1,
+                Err(e) => {
+                    println!("Failed to restore {}: {}", file.original.display(), e);
+                    summary
+                        .failed
+                        .push(format!("{}: {}", file.original.display(), e));
+                }
+            }
+        }
+
+        for file in &self.rewritten {
+            match fs::copy(&file.backup, &file.original) {
+                Ok(_) => {
+                    summary.files += 1;
+                    let _ = fs::remove_file(&file.backup);
+                }
+                Err(e) => summary
+                    .failed
+                    .push(format!("{}: {}", file.original.display(), e)),
+            }
+        }
+        app.rsubstatus("undo", 100, "File restore complete");
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+async fn table_columns(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query(&format!("PRAGMA table_info({});", TABLE))
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.try_get::<String, _>("name").ok())
+        .collect())
+}
+
+impl Database {
+    /// Reverses the session in `journal`: its rows go back first, then whatever files can be
+    /// restored, and the session is marked undone so the one before it is next.
+    pub async fn undo_removal<E: StatusEmitter>(
+        &self,
+        journal: &RemovalJournal,
+        app: &E,
+    ) -> R<UndoSummary> {
+        app.rstatus("starting", 0, "Restoring Records to Database...");
+        let mut summary = UndoSummary {
+            records: self.restore_rows(journal, app).await?,
+            ..Default::default()
+        };
+
+        app.rstatus("starting", 50, "Restoring Files...");
+        journal.restore_files(app, &mut summary);
+
+        journal.mark_undone()?;
+        Ok(summary)
+    }
+
+    /// Copies the full rows about to be removed into `journal`.
+    pub async fn journal_rows(
+        &self,
+        ids: &[usize],
+        journal: &mut RemovalJournal,
+    ) -> Result<(), sqlx::Error> {
+        let rows = self.quoted_rows(ids, journal).await?;
+        journal.rows.extend(rows);
+        Ok(())
+    }
+
+    /// Copies the rows about to be changed into `journal`, so undoing puts their old values back.
+    pub async fn journal_updated_rows(
+        &self,
+        ids: &[usize],
+        journal: &mut RemovalJournal,
+    ) -> Result<(), sqlx::Error> {
+        let rows = self.quoted_rows(ids, journal).await?;
+        journal.updated.extend(rows);
+        Ok(())
+    }
+
+    async fn quoted_rows(
+        &self,
+        ids: &[usize],
+        journal: &mut RemovalJournal,
+    ) -> Result<Vec<Vec<String>>, sqlx::Error> {
+        const BATCH_SIZE: usize = 12321;
+        let Some(pool) = self.get_pool().await else {
+            return Err(sqlx::Error::Configuration(
+                "No database connection available".into(),
+            ));
+        };
+
+        let columns = table_columns(&pool).await?;
+        let select = columns
+            .iter()
+            .map(|c| format!("quote({})", quote_ident(c)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut rows = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let placeholders = std::iter::repeat_n("?", chunk.len())
+                .collect::<Vec<_>>()
+                .join(",");
+            let query = format!(
+                "SELECT quote(rowid), {} FROM {} WHERE rowid IN ({})",
+                select, TABLE, placeholders
+            );
+            let mut query_builder = sqlx::query(&query);
+            for &id in chunk {
+                query_builder = query_builder.bind(id as i64);
+            }
+            for row in query_builder.fetch_all(&pool).await? {
+                let values = (0..=columns.len())
+                    .map(|i| row.try_get::<String, _>(i))
+                    .collect::<Result<_, _>>()?;
+                rows.push(values);
+            }
+        }
+        journal.columns = columns;
+        Ok(rows)
+    }
+
+    /// Reinserts journaled rows under their original rowids, and puts back the old values of
+    /// updated ones. Columns that no longer exist in the table are skipped.
+    pub async fn restore_rows<E: StatusEmitter>(
+        &self,
+        journal: &RemovalJournal,
+        app: &E,
+    ) -> Result<usize, sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Err(sqlx::Error::Configuration(
+                "No database connection available".into(),
+            ));
+        };
+
+        let existing: HashSet<String> = table_columns(&pool).await?.into_iter().collect();
+        let keep: Vec<usize> = journal
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| existing.contains(*c))
+            .map(|(i, _)| i)
+            .collect();
+        let columns = keep
+            .iter()
+            .map(|&i| quote_ident(&journal.columns[i]))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let total = journal.rows.len() + journal.updated.len();
+        let mut tx = pool.begin().await?;
+        for (count, row) in journal.rows.iter().chain(&journal.updated).enumerate() {
+            if count % RECORD_DIVISOR == 0 {
+                app.rsubstatus(
+                    "undo",
+                    count * 100 / total,
+                    &format!("Restoring records: {}/{}", count, total),
+                );
+            }
+            let mut values = vec![row[0].as_str()];
+            values.extend(keep.iter().map(|&i| row[i + 1].as_str()));
+            let query = format!(
+                "INSERT OR REPLACE INTO {} (rowid, {}) VALUES ({})",
+                TABLE,
+                columns,
+                values.join(", ")
+            );
+            sqlx::query(&query).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        app.rsubstatus("undo", 100, "Records restored");
+
+        Ok(journal.rows.len())
+    }
+}
+
+fn restore_from_quarantine(file: &RemovedFile, root: &Path) -> R<()> {
+    if file.original.exists() {
+        return Err(anyhow!("A file already exists at the original location"));
+    }
+    let location = file
+        .location
+        .as_ref()
+        .ok_or_else(|| anyhow!("Quarantine location unknown"))?;
+    move_file(location, &file.original)?;
+    QuarantineManifest::forget(root, &file.original)
+}
+
+/// Where each trashed file ended up. The macOS Trash can't be listed, Finder renames files
+/// whose names collide and other volumes have their own Trash, so nothing is recorded there.
+#[cfg(target_os = "macos")]
+pub fn trashed_locations(_files: &[&str]) -> HashMap<PathBuf, PathBuf> {
+    HashMap::new()
+}
+
+/// Where each trashed file ended up, looked up right after `trash::delete`.
+#[cfg(not(target_os = "macos"))]
+pub fn trashed_locations(files: &[&str]) -> HashMap<PathBuf, PathBuf> {
+    let items = trash::os_limited::list().unwrap_or_default();
+    files
+        .iter()
+        .filter_map(|file| {
+            let original = PathBuf::from(file);
+            let item = items
+                .iter()
+                .filter(|item| item.original_path() == original)
+                .max_by_key(|item| item.time_deleted)?;
+            Some((original, PathBuf::from(&item.id)))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+type TrashIndex = HashMap<PathBuf, trash::TrashItem>;
+
+#[cfg(not(target_os = "macos"))]
+fn trash_index(files: &[RemovedFile]) -> TrashIndex {
+    if !files.iter().any(|f| f.action == Delete::Trash) {
+        return HashMap::new();
+    }
+    let mut index: TrashIndex = HashMap::new();
+    for item in trash::os_limited::list().unwrap_or_default() {
+        let path = item.original_path();
+        let newer = index
+            .get(&path)
+            .is_none_or(|existing| existing.time_deleted < item.time_deleted);
+        if newer {
+            index.insert(path, item);
+        }
+    }
+    index
+}
+
+#[cfg(not(target_os = "macos"))]
+fn restore_from_trash(file: &RemovedFile, trashed: &mut TrashIndex) -> R<()> {
+    if file.original.exists() {
+        return Err(anyhow!("A file already exists at the original location"));
+    }
+    let item = trashed
+        .remove(&file.original)
+        .ok_or_else(|| anyhow!("File is no longer in the Trash"))?;
+    trash::os_limited::restore_all([item])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    async fn database(path: &Path) -> Database {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        for query in [
+            "CREATE TABLE justinmetadata (filepath TEXT, Description TEXT, Duration REAL, _Dirty INTEGER)",
+            "INSERT INTO justinmetadata (rowid, filepath, Description, Duration, _Dirty) VALUES
+                (1, '/sounds/door.wav', 'Door ''slam''', 1.5, 0),
+                (2, '/sounds/gone.wav', NULL, 0.25, 1),
+                (3, '/sounds/gate.wav', 'Gate', 3.0, 0)",
+        ] {
+            sqlx::query(query).execute(&pool).await.unwrap();
+        }
+        pool.close().await;
+        Database::new(path.to_str().unwrap(), false).await
+    }
+
+    async fn rows(db: &Database) -> Vec<(i64, String, Option<String>, f64, i64)> {
+        sqlx::query_as(
+            "SELECT rowid, filepath, Description, Duration, _Dirty FROM justinmetadata ORDER BY rowid",
+        )
+        .fetch_all(&db.get_pool().await.unwrap())
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn undoes_the_latest_removal_then_the_one_before() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sqlite");
+        let journals = dir.path().join("journal");
+        let db = database(&path).await;
+        let before = rows(&db).await;
+
+        let mut first = RemovalJournal::new_in(&journals, &path);
+        first.id = "1".to_string();
+        db.journal_rows(&[3], &mut first).await.unwrap();
+        first.save().unwrap();
+        db.remove(&[3], &NoopEmitter).await.unwrap();
+
+        // The second session quarantines one file and deletes the other
+        let sound = dir.path().join("sounds").join("door.wav");
+        fs::create_dir_all(sound.parent().unwrap()).unwrap();
+        fs::write(&sound, b"RIFF door").unwrap();
+        let root = dir.path().join("quarantine");
+        let mut second = RemovalJournal::new_in(&journals, &path);
+        second.id = "2".to_string();
+        db.journal_rows(&[1, 2], &mut second).await.unwrap();
+        second.save().unwrap();
+        db.remove(&[1, 2], &NoopEmitter).await.unwrap();
+        let moved = quarantine_files(&root, &[sound.to_str().unwrap()], &NoopEmitter).unwrap();
+        second.files = vec![
+            RemovedFile {
+                original: sound.clone(),
+                action: Delete::Quarantine { root: root.clone() },
+                location: Some(moved[0].1.clone()),
+            },
+            RemovedFile {
+                original: dir.path().join("sounds").join("gone.wav"),
+                action: Delete::Delete,
+                location: None,
+            },
+        ];
+        second.save().unwrap();
+        assert!(rows(&db).await.is_empty());
+
+        let latest = RemovalJournal::latest_in(&journals).unwrap().unwrap();
+        assert_eq!(latest.id, "2");
+        let summary = db.undo_removal(&latest, &NoopEmitter).await.unwrap();
+        assert_eq!(summary.records, 2);
+        assert_eq!(summary.files, 1);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(summary.failed[0].contains("gone.wav"));
+        assert_eq!(fs::read(&sound).unwrap(), b"RIFF door");
+        assert!(QuarantineManifest::load(&root).unwrap().entries.is_empty());
+        assert_eq!(rows(&db).await, before[..2]);
+
+        let latest = RemovalJournal::latest_in(&journals).unwrap().unwrap();
+        assert_eq!(latest.id, "1");
+        db.undo_removal(&latest, &NoopEmitter).await.unwrap();
+        assert_eq!(rows(&db).await, before);
+        assert!(RemovalJournal::latest_in(&journals).unwrap().is_none());
+    }
+
+    #[test]
+    fn puts_rewritten_files_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let sound = dir.path().join("door.wav");
+        fs::write(&sound, b"stereo door").unwrap();
+
+        let mut journal = RemovalJournal::new_in(&dir.path().join("journal"), &sound);
+        journal.back_up(&sound).unwrap();
+        fs::write(&sound, b"mono door").unwrap();
+
+        let mut summary = UndoSummary::default();
+        journal.restore_files(&NoopEmitter, &mut summary);
+        assert_eq!(summary.files, 1);
+        assert!(summary.failed.is_empty());
+        assert_eq!(fs::read(&sound).unwrap(), b"stereo door");
+        assert!(!journal.rewritten[0].backup.exists());
+    }
+}
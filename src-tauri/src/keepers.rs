@@ -0,0 +1,202 @@
+use crate::prelude::*;
+
+/// Records the user promoted to keeper, kept next to `justinmetadata` so later searches honour them.
+/// Only created once a keeper is chosen, so searching never writes to the database.
+pub const KEEPER_TABLE: &str = "smdbc_manual_keepers";
+
+// Tags saying which search matched a record to its group. A keeper the user picked is kept
+// because they said so, not because of them.
+const MATCH_TAGS: [Algorithm; 5] = [
+    A::Basic,
+    A::SimilarFilename,
+    A::SimilarAudio,
+    A::Waveforms,
+    A::Tags,
+];
+
+impl Database {
+    async fn ensure_keeper_table(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, chosen TEXT)",
+            KEEPER_TABLE
+        );
+        sqlx::query(&query).execute(pool).await?;
+        Ok(())
+    }
+
+    /// Makes `id` the keeper of its group and demotes the rest of the group.
+    pub async fn set_keeper(&mut self, id: usize) -> R<()> {
+        let group = self
+            .records
+            .iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| anyhow!("Record {} is not in the current results", id))?
+            .get_group()
+            .ok_or_else(|| anyhow!("Record {} is not part of a duplicate group", id))?;
+
+        let members: Vec<usize> = self
+            .records
+            .iter()
+            .filter(|r| r.get_group() == Some(group))
+            .map(|r| r.id)
+            .collect();
+
+        let pool = self
+            .get_pool()
+            .await
+            .ok_or_else(|| anyhow!("No database connection available"))?;
+        self.ensure_keeper_table(&pool).await?;
+
+        let mut tx = pool.begin().await?;
+        // Only one manual keeper per group, so forget earlier picks among these members
+        let placeholders = std::iter::repeat_n("?", members.len())
+            .collect::<Vec<_>>()
+            .join(",");
+        let query = format!(
+            "DELETE FROM {} WHERE id IN ({})",
+            KEEPER_TABLE, placeholders
+        );
+        let mut query_builder = sqlx::query(&query);
+        for &member in &members {
+            query_builder = query_builder.bind(member as i64);
+        }
+        query_builder.execute(&mut *tx).await?;
+
+        let query = format!("INSERT INTO {} (id, chosen) VALUES (?, ?)", KEEPER_TABLE);
+        sqlx::query(&query)
+            .bind(id as i64)
+            .bind(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.promote(group, id);
+        Ok(())
+    }
+
+    /// Re-applies keepers chosen in earlier sessions to freshly searched groups.
+    pub async fn apply_manual_keepers(&mut self) -> Result<(), sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(());
+        };
+
+        let query = format!("SELECT id FROM {}", KEEPER_TABLE);
+        let chosen: HashSet<usize> =
+            match sqlx::query_scalar::<_, i64>(&query).fetch_all(&pool).await {
+                Ok(ids) => ids.into_iter().map(|id| id as usize).collect(),
+                // No keeper was ever chosen in this database
+                Err(sqlx::Error::Database(e)) if e.message().contains("no such table") => {
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+        if chosen.is_empty() {
+            return Ok(());
+        }
+
+        let overrides: Vec<(usize, usize)> = self
+            .records
+            .iter()
+            .filter(|r| chosen.contains(&r.id) && r.get_role() == Some(GroupRole::Duplicate))
+            .filter_map(|r| Some((r.get_group()?, r.id)))
+            .collect();
+        for (group, id) in overrides {
+            self.promote(group, id);
+        }
+        Ok(())
+    }
+
+    fn promote(&mut self, group: usize, id: usize) {
+        for record in self
+            .records
+            .iter_mut()
+            .filter(|r| r.get_group() == Some(group))
+        {
+            if record.id == id {
+                record.role = Some(GroupRole::Keeper);
+                record.algorithm.retain(|a| !MATCH_TAGS.contains(a));
+                record.algorithm.insert(A::Keep);
+                record.algorithm.insert(A::Manual);
+            } else if record.algorithm.remove(&A::Keep) {
+                record.role = Some(GroupRole::Duplicate);
+                record.algorithm.insert(A::Manual);
+            } else {
+                record.role = Some(GroupRole::Duplicate);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    async fn keeper_table_exists(path: &Path) -> bool {
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path))
+            .await
+            .unwrap();
+        let found: Option<String> =
+            sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?")
+                .bind(KEEPER_TABLE)
+                .fetch_optional(&pool)
+                .await
+                .unwrap();
+        pool.close().await;
+        found.is_some()
+    }
+
+    #[tokio::test]
+    async fn chosen_keeper_survives_the_next_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sqlite");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE justinmetadata (filepath TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let mut db = Database::new(path.to_str().unwrap(), false).await;
+        let search = |db: &mut Database| {
+            db.records.clear();
+            for id in 1..=3 {
+                let mut record = FileRecord {
+                    id,
+                    ..Default::default()
+                };
+                record.algorithm.insert(A::Basic);
+                if id == 1 {
+                    record.algorithm.insert(A::Keep);
+                    record.join_group(1, GroupRole::Keeper);
+                } else {
+                    record.join_group(1, GroupRole::Duplicate);
+                }
+                db.records.push(record);
+            }
+        };
+
+        // Searching a database nobody picked a keeper in leaves it untouched
+        search(&mut db);
+        db.apply_manual_keepers().await.unwrap();
+        assert!(!keeper_table_exists(&path).await);
+        assert_eq!(db.records[0].get_role(), Some(GroupRole::Keeper));
+
+        db.set_keeper(2).await.unwrap();
+        assert!(keeper_table_exists(&path).await);
+        let chosen = &db.records[1];
+        assert_eq!(chosen.get_role(), Some(GroupRole::Keeper));
+        assert!(chosen.algorithm.contains(&A::Keep));
+        assert!(!chosen.algorithm.contains(&A::Basic));
+        assert_eq!(db.records[0].get_role(), Some(GroupRole::Duplicate));
+        assert!(!db.records[0].algorithm.contains(&A::Keep));
+
+        search(&mut db);
+        db.apply_manual_keepers().await.unwrap();
+        assert_eq!(db.records[1].get_role(), Some(GroupRole::Keeper));
+        assert_eq!(db.records[0].get_role(), Some(GroupRole::Duplicate));
+    }
+}
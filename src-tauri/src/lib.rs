@@ -1,16 +1,34 @@
 pub mod commands;
+pub mod diff;
+pub mod export;
+pub mod journal;
+pub mod keepers;
+pub mod merge;
+pub mod plan;
 pub mod preferences;
+pub mod quarantine;
+pub mod scan;
 pub mod search;
+pub mod status;
 pub use crate::audio::*;
 pub mod audio;
 pub use dirs::home_dir;
 pub use preferences::*;
 pub mod prelude;
+pub use quarantine::*;
+pub use scan::*;
 // pub use FFcodex::*;
 pub use commands::*;
+pub use diff::*;
+pub use export::*;
+pub use journal::*;
+pub use keepers::*;
+pub use merge::*;
+pub use plan::*;
 pub use regex::Regex;
 pub use sqlx::Row;
 pub use sqlx::sqlite::{SqlitePool, SqliteRow};
+pub use status::*;
 use std::hash::Hash;
 // use tauri::App;
 // use tauri::menu::{Menu, MenuBuilder, MenuItem, Submenu};
@@ -65,6 +83,18 @@ pub fn run() {
             pause_audio,
             resume_audio,
             clear_fingerprints,
+            get_compare_summary,
+            find_similar_sounds,
+            get_bext,
+            set_bext,
+            diff_group_metadata,
+            undo_last_removal,
+            restore_quarantine,
+            plan_removal,
+            export_removal_plan,
+            export_results,
+            get_groups,
+            set_keeper,
             refresh_all_windows
         ])
         .run(tauri::generate_context!())
@@ -102,32 +132,32 @@ fn set_library_path() {
     use std::env;
     use std::path::Path;
 
-    if let Ok(exe_path) = env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            #[cfg(debug_assertions)]
-            let resources_path = Path::new(&exe_dir).join("../../../resources");
-
-            #[cfg(not(debug_assertions))]
-            let resources_path = if exe_dir.to_string_lossy().contains("MacOS") {
-                // We're inside a macOS bundle
-                Path::new(&exe_dir)
-                    .join("../Resources/resources")
-                    .to_path_buf()
-            } else {
-                Path::new(&exe_dir).join("resources").to_path_buf()
-            };
+    if let Ok(exe_path) = env::current_exe()
+        && let Some(exe_dir) = exe_path.parent()
+    {
+        #[cfg(debug_assertions)]
+        let resources_path = Path::new(&exe_dir).join("../../../resources");
+
+        #[cfg(not(debug_assertions))]
+        let resources_path = if exe_dir.to_string_lossy().contains("MacOS") {
+            // We're inside a macOS bundle
+            Path::new(&exe_dir)
+                .join("../Resources/resources")
+                .to_path_buf()
+        } else {
+            Path::new(&exe_dir).join("resources").to_path_buf()
+        };
 
-            if resources_path.exists() {
-                println!("Found resources at: {}", resources_path.display());
-                let path_string = resources_path.to_string_lossy().to_string();
-                // env::set_var is unsafe because it modifies process-wide state
-                unsafe {
-                    env::set_var("DYLD_LIBRARY_PATH", &path_string);
-                }
-                println!("Set DYLD_LIBRARY_PATH to {}", path_string);
-            } else {
-                println!("Resources path not found at: {}", resources_path.display());
+        if resources_path.exists() {
+            println!("Found resources at: {}", resources_path.display());
+            let path_string = resources_path.to_string_lossy().to_string();
+            // env::set_var is unsafe because it modifies process-wide state
+            unsafe {
+                env::set_var("DYLD_LIBRARY_PATH", &path_string);
             }
+            println!("Set DYLD_LIBRARY_PATH to {}", path_string);
+        } else {
+            println!("Resources path not found at: {}", resources_path.display());
         }
     }
 }
@@ -143,67 +173,6 @@ pub struct AppState {
     // reg: Registration,
 }
 
-#[derive(Clone, serde::Serialize)]
-pub struct StatusUpdate {
-    stage: String,
-    progress: usize,
-    message: String,
-}
-
-pub trait StatusEmitter {
-    fn status(&self, stage: &str, progress: usize, message: &str);
-    fn substatus(&self, stage: &str, progress: usize, message: &str);
-    fn rstatus(&self, stage: &str, progress: usize, message: &str);
-    fn rsubstatus(&self, stage: &str, progress: usize, message: &str);
-}
-
-impl StatusEmitter for AppHandle {
-    fn status(&self, stage: &str, progress: usize, message: &str) {
-        self.emit(
-            "search-status",
-            StatusUpdate {
-                stage: stage.into(),
-                progress,
-                message: message.into(),
-            },
-        )
-        .ok();
-    }
-    fn substatus(&self, stage: &str, progress: usize, message: &str) {
-        self.emit(
-            "search-sub-status",
-            StatusUpdate {
-                stage: stage.into(),
-                progress,
-                message: message.into(),
-            },
-        )
-        .ok();
-    }
-    fn rstatus(&self, stage: &str, progress: usize, message: &str) {
-        self.emit(
-            "remove-status",
-            StatusUpdate {
-                stage: stage.into(),
-                progress,
-                message: message.into(),
-            },
-        )
-        .ok();
-    }
-    fn rsubstatus(&self, stage: &str, progress: usize, message: &str) {
-        self.emit(
-            "remove-sub-status",
-            StatusUpdate {
-                stage: stage.into(),
-                progress,
-                message: message.into(),
-            },
-        )
-        .ok();
-    }
-}
-
 #[derive(Serialize, Deserialize)] // Need Deserialize to receive it back
 pub struct FileRecordFrontend {
     id: usize,
@@ -215,13 +184,68 @@ pub struct FileRecordFrontend {
     samplerate: u32,
     duration: Arc<str>,
     description: Arc<str>,
+    group: Option<usize>,
+    role: Option<GroupRole>,
+    subset: Option<SubsetOf>,
+    compare_match: Option<CompareMatch>,
+    channel_analysis: Option<ChannelAnalysis>,
     // data: HashMap<Arc<str>, Arc<str>>,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+impl FileRecordFrontend {
+    pub fn is_marked(&self) -> bool {
+        !self.algorithm.contains(&A::Keep)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupRole {
+    Keeper,
+    Duplicate,
+}
+
+/// Where a subset clip was found inside its parent, times in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SubsetOf {
+    pub parent: usize,
+    pub start: f64,
+    pub end: f64,
+    pub score: f64,
+}
+
+/// The compare database record a record was flagged against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompareMatch {
+    pub database: Arc<str>, // name of the compare database
+    pub id: usize,
+    pub path: Arc<str>,
+    pub score: f64, // 1.0 unless matched by fingerprint
+}
+
+/// Overlap between the searched records and one compare database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareSummary {
+    pub database: Arc<str>,
+    pub path: Arc<str>,
+    pub records: usize, // records in the compare database
+    pub matched: usize, // searched records found in it
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub id: usize,
+    pub keeper: Option<usize>,
+    pub members: Vec<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct DualMono {
     pub id: usize,
     pub path: String,
+    /// Channels to remove, numbered from 0. Empty takes the redundant channels of the record's
+    /// channel analysis, see `Database::channel_strips`.
+    #[serde(default)]
+    pub drop: Vec<u16>,
 }
 
 // Change visibility of `FileRecord` fields to private where possible
@@ -237,8 +261,17 @@ pub struct FileRecord {
     description: Arc<str>,             // Made private
     data: HashMap<Arc<str>, Arc<str>>, // Made private
     fingerprint: Option<Arc<str>>,     // Made private
+    checksum: Option<Arc<str>>,        // SHA-256 of the audio payload, see `audio_checksum`
     dual_mono: Option<bool>,           // Made private
-    algorithm: HashSet<Algorithm>,     // Made private
+    channel_analysis: Option<ChannelAnalysis>,
+    algorithm: HashSet<Algorithm>, // Made private
+    group: Option<usize>,          // id of the keeper chosen by the search
+    role: Option<GroupRole>,
+    #[serde(skip)]
+    joined: Vec<usize>, // every group a search pass put this record in, see `merge_groups`
+    subset: Option<SubsetOf>,
+    compare_match: Option<CompareMatch>,
+    identity: Option<Arc<str>>, // FileIdentity hash, only read when rescanning
 }
 impl Hash for FileRecord {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -265,10 +298,10 @@ impl FileRecord {
         let path = PathBuf::from(path_str);
         // let path_exists = path.exists();
         let duration_str: &str = row.get(2);
-        let description: &str = row.get(4);
-        let channels = row.get(5);
-        let bitdepth = row.get(6);
-        let samplerate = row.get(7);
+        let description: &str = row.get(3);
+        let channels = row.get(4);
+        let bitdepth = row.get(5);
+        let samplerate = row.get(6);
 
         let mut algorithm = HashSet::new();
         let mut keep = true;
@@ -321,38 +354,28 @@ impl FileRecord {
             }
         }
 
-        let f = row.try_get::<&str, _>("_fingerprint").unwrap_or_default();
-
-        let fingerprint = if f.is_empty() || !pref.fetch_waveforms {
-            None
-        } else {
-            Some(Arc::from(f))
-        };
-
-        let mut dual_mono = None;
-
-        if pref.fetch_waveforms {
-            let dm = row.try_get::<&str, _>("_DualMono").ok();
-            dual_mono = match dm {
-                Some("1") => Some(true),
-                Some("0") => Some(false),
-                _ => None,
-            };
-        }
-
+        // Fingerprints, checksums and dual mono results live in SCAN_TABLE, see `refresh_scan_state`
         let mut record = Self {
             id,
             path,
             root: Arc::default(),
             duration: Arc::from(duration_str),
             data,
-            fingerprint,
+            fingerprint: None,
+            checksum: None,
             algorithm,
             channels,
             bitdepth,
             samplerate,
             description: Arc::from(description),
-            dual_mono,
+            dual_mono: None,
+            channel_analysis: None,
+            group: None,
+            role: None,
+            joined: Vec::new(),
+            subset: None,
+            compare_match: None,
+            identity: None,
         };
 
         record.set_root(enabled, pref);
@@ -420,6 +443,73 @@ impl FileRecord {
             .unwrap_or("Invalid Path")
     }
 
+    pub fn get_group(&self) -> Option<usize> {
+        self.group
+    }
+
+    pub fn get_role(&self) -> Option<GroupRole> {
+        self.role
+    }
+
+    pub fn join_group(&mut self, group: usize, role: GroupRole) {
+        self.group = Some(group);
+        self.role = Some(role);
+        if !self.joined.contains(&group) {
+            self.joined.push(group);
+        }
+    }
+
+    pub fn get_subset(&self) -> Option<SubsetOf> {
+        self.subset
+    }
+
+    /// Value compared against the compare database; None for records that can never match.
+    pub fn compare_key(&self, key: CompareKey) -> Option<Arc<str>> {
+        match key {
+            CompareKey::Filename => Some(Arc::from(self.get_filename())),
+            CompareKey::Root => Some(self.root.clone()),
+            CompareKey::FilenameDuration => Some(Arc::from(format!(
+                "{}\u{0}{}",
+                self.get_filename(),
+                self.duration
+            ))),
+            CompareKey::Checksum => self.checksum.clone(),
+            CompareKey::Fingerprint => None,
+        }
+    }
+
+    pub fn get_channel_analysis(&self) -> Option<&ChannelAnalysis> {
+        self.channel_analysis.as_ref()
+    }
+
+    pub fn get_compare_match(&self) -> Option<&CompareMatch> {
+        self.compare_match.as_ref()
+    }
+
+    pub fn leave_group(&mut self) {
+        self.group = None;
+        self.role = None;
+        self.joined.clear();
+    }
+
+    pub fn is_marked(&self) -> bool {
+        !self.algorithm.contains(&A::Keep)
+    }
+
+    /// Puts a sorted group under its first record, the keeper.
+    pub fn set_group(records: &mut [FileRecord]) {
+        if let Some(keeper) = records.first().map(|r| r.id) {
+            records.iter_mut().enumerate().for_each(|(i, r)| {
+                let role = if i == 0 {
+                    GroupRole::Keeper
+                } else {
+                    GroupRole::Duplicate
+                };
+                r.join_group(keeper, role);
+            });
+        }
+    }
+
     pub fn check_path(&self) -> bool {
         self.path.exists()
     }
@@ -472,10 +562,10 @@ impl FileRecord {
     }
 
     pub fn get_duration(&self) -> Result<f64, String> {
-        if let Some((minutes, rest)) = self.duration.split_once(':') {
-            if let (Ok(mins), Ok(secs)) = (minutes.parse::<f64>(), rest.parse::<f64>()) {
-                return Ok((mins * 60.0) + secs);
-            }
+        if let Some((minutes, rest)) = self.duration.split_once(':')
+            && let (Ok(mins), Ok(secs)) = (minutes.parse::<f64>(), rest.parse::<f64>())
+        {
+            return Ok((mins * 60.0) + secs);
         }
         Err("Unable to parse duration".to_string())
     }
@@ -492,6 +582,8 @@ pub struct Database {
     is_compare: bool,
     // abort: Arc<RwLock<bool>>,
     abort: Arc<AtomicBool>,
+    scan_state: HashMap<usize, ScanState>,
+    compare_summary: Vec<CompareSummary>,
 }
 
 // Change visibility of `Database` methods to private where possible
@@ -536,33 +628,40 @@ impl Database {
         }
     }
 
+    /// Where `create_clone` puts the safety copy for `tag`.
+    pub fn clone_path(&self, tag: &str) -> Option<PathBuf> {
+        let path = self.path.as_ref()?.to_string_lossy().to_string();
+        Some(PathBuf::from(
+            path.replace(".sqlite", &format!("_{}.sqlite", tag)),
+        ))
+    }
+
     async fn create_clone(&self, tag: &str) -> Database {
-        let mut path = self.path.as_ref().unwrap().to_string_lossy().to_string();
-        path = path.replace(".sqlite", &format!("_{}.sqlite", tag));
-        let path = PathBuf::from(path);
+        let path = self.clone_path(tag).unwrap();
         let _result = fs::copy(self.path.as_ref().unwrap(), &path);
 
         let mut db = Database::default();
         db.init(Some(path), false).await;
+        // The copy has the same rowids, so the current results still apply to it
+        db.records = self.records.clone();
         db
     }
 
     fn get_path(&self) -> Option<Arc<str>> {
-        if let Some(path) = &self.path {
-            if let Some(path) = path.to_str() {
-                return Some(Arc::from(path));
-            }
+        if let Some(path) = &self.path
+            && let Some(path) = path.to_str()
+        {
+            return Some(Arc::from(path));
         }
         None
     }
 
     fn get_name(&self) -> Option<Arc<str>> {
-        if let Some(path) = &self.path {
-            if let Some(name) = path.file_stem() {
-                if let Some(name_str) = name.to_str() {
-                    return Some(Arc::from(name_str));
-                }
-            }
+        if let Some(path) = &self.path
+            && let Some(name) = path.file_stem()
+            && let Some(name_str) = name.to_str()
+        {
+            return Some(Arc::from(name_str));
         }
         None
     }
@@ -570,6 +669,14 @@ impl Database {
         self.size
     }
 
+    pub fn compare_summary(&self) -> &[CompareSummary] {
+        &self.compare_summary
+    }
+
+    pub fn abort_handle(&self) -> Arc<AtomicBool> {
+        self.abort.clone()
+    }
+
     fn get_records_size(&self) -> usize {
         self.records
             .iter()
@@ -577,73 +684,13 @@ impl Database {
             .count()
     }
 
+    /// Compare databases belong to other libraries and are only ever read.
     pub async fn get_pool(&self) -> Option<SqlitePool> {
-        if let Some(path) = self.get_path() {
-            return SqlitePool::connect(&path).await.ok();
-        }
-        None
-    }
-
-    async fn remove_column(&self, remove: &str) -> Result<(), sqlx::Error> {
-        if let Some(pool) = self.get_pool().await {
-            // First check if the column already exists
-            let columns = sqlx::query(&format!("PRAGMA table_info({});", TABLE))
-                .fetch_all(&pool)
-                .await?;
-
-            // Check if our column exists
-            let column_exists = columns.iter().any(|row| {
-                let column_name: &str = row.try_get("name").unwrap_or_default();
-                column_name == remove
-            });
-
-            // Only remove the column if it exists
-            if column_exists {
-                // Remove the column
-                let query = format!("ALTER TABLE {} DROP COLUMN {};", TABLE, remove);
-                sqlx::query(&query).execute(&pool).await?;
-                println!("Removed column: {}", remove);
-            } else {
-                println!("Column '{}' does not exist", remove);
-            }
-
-            return Ok(());
-        }
-
-        Err(sqlx::Error::Configuration(
-            "No database connection available".into(),
-        ))
-    }
-
-    async fn add_column(&self, add: &str) -> Result<(), sqlx::Error> {
-        if let Some(pool) = self.get_pool().await {
-            // First check if the column already exists
-            let columns = sqlx::query(&format!("PRAGMA table_info({});", TABLE))
-                .fetch_all(&pool)
-                .await?;
-
-            // Check if our column exists
-            let column_exists = columns.iter().any(|row| {
-                let column_name: &str = row.try_get("name").unwrap_or_default();
-                column_name == add
-            });
-
-            // Only add the column if it doesn't exist
-            if !column_exists {
-                // Add the column with TEXT type (you can change this if needed)
-                let query = format!("ALTER TABLE {} ADD COLUMN {} TEXT;", TABLE, add);
-                sqlx::query(&query).execute(&pool).await?;
-                println!("Added new column: {}", add);
-            } else {
-                println!("Column '{}' already exists", add);
-            }
-
-            return Ok(());
-        }
-
-        Err(sqlx::Error::Configuration(
-            "No database connection available".into(),
-        ))
+        let path = self.get_path()?;
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&*path)
+            .read_only(self.is_compare);
+        SqlitePool::connect_with(options).await.ok()
     }
 
     async fn fetch_size(&self) -> Result<usize, sqlx::Error> {
@@ -658,7 +705,11 @@ impl Database {
         Ok(0)
     }
 
-    pub async fn remove(&self, ids: &[usize], app: &AppHandle) -> Result<(), sqlx::Error> {
+    pub async fn remove<E: StatusEmitter>(
+        &self,
+        ids: &[usize],
+        app: &E,
+    ) -> Result<(), sqlx::Error> {
         const BATCH_SIZE: usize = 12321; // Define the batch size
         let _ = app;
         let mut counter = 0;
@@ -673,8 +724,7 @@ impl Database {
 
                 counter += BATCH_SIZE;
                 // Create placeholders for each ID in the chunk
-                let placeholders = std::iter::repeat("?")
-                    .take(chunk.len())
+                let placeholders = std::iter::repeat_n("?", chunk.len())
                     .collect::<Vec<_>>()
                     .join(",");
                 let query = format!("DELETE FROM {} WHERE rowid IN ({})", TABLE, placeholders);
@@ -695,10 +745,11 @@ impl Database {
         Ok(())
     }
 
-    pub async fn clean_multi_mono(
+    /// Removes the listed channels of each file, as resolved by `channel_strips`.
+    pub async fn clean_multi_mono<E: StatusEmitter>(
         &self,
-        app: &AppHandle,
-        records: &Vec<DualMono>,
+        app: &E,
+        records: &[DualMono],
     ) -> Result<(), sqlx::Error> {
         use std::sync::Mutex;
 
@@ -707,7 +758,7 @@ impl Database {
         let completed = AtomicUsize::new(0);
         let failures = AtomicUsize::new(0);
 
-        // Create a synchronized collection for successful record IDs only
+        // Create a synchronized collection for successful record IDs and their new channel counts
         let successful_ids = Arc::new(Mutex::new(Vec::with_capacity(records.len())));
 
         records.par_iter().for_each(|record| {
@@ -740,12 +791,12 @@ impl Database {
             println!("  Format: {}", extension);
 
             // Process the file
-            match ffcodex_lib::clean_multi_mono(path.to_str().unwrap_or_default()) {
-                Ok(_) => {
+            match strip_channels_from_file(path, &record.drop) {
+                Ok(channels) => {
                     println!("  Strip multi-mono successful");
                     match successful_ids.lock() {
                         Ok(mut ids) => {
-                            ids.push(record.id);
+                            ids.push((record.id, channels));
                         }
                         Err(_) => {
                             println!("ERROR: Failed to acquire lock on successful_ids");
@@ -788,15 +839,26 @@ impl Database {
 
         // Only update database if we have SUCCESSFUL records to update
         if !successful_ids.is_empty() {
-            match self
-                .update_channel_count_to_mono(app, &successful_ids)
-                .await
-            {
+            let mut by_channels: HashMap<u16, Vec<usize>> = HashMap::new();
+            for (id, channels) in successful_ids {
+                by_channels.entry(channels).or_default().push(id);
+            }
+            let mut updated = Ok(());
+            for (channels, ids) in &by_channels {
+                updated = self.update_channel_count(app, ids, *channels).await;
+                if updated.is_err() {
+                    break;
+                }
+            }
+            match updated {
                 Ok(_) => {
                     app.rsubstatus(
                         "complete with results",
                         100,
-                        &format!("Updated {} files to mono, {} failures", success, failed),
+                        &format!(
+                            "Stripped channels from {} files, {} failures",
+                            success, failed
+                        ),
                     );
                     Ok(())
                 }
@@ -826,12 +888,12 @@ impl Database {
         }
     }
 
-    pub async fn fetch_filerecords(
+    pub async fn fetch_filerecords<E: StatusEmitter>(
         &mut self,
         query: &str,
         enabled: &Enabled,
         pref: &Preferences,
-        app: &AppHandle,
+        app: &E,
     ) -> Result<(), sqlx::Error> {
         // self.records.clear();
         let completed = AtomicUsize::new(0);
@@ -843,7 +905,7 @@ impl Database {
             .enumerate()
             .map(|(count, row)| {
                 let new_completed = completed.fetch_add(1, Ordering::SeqCst) + 1;
-                if new_completed % RECORD_DIVISOR == 0 {
+                if new_completed.is_multiple_of(RECORD_DIVISOR) {
                     app.substatus(
                         "gather",
                         new_completed * 100 / rows.len(),
@@ -861,16 +923,16 @@ impl Database {
         Ok(())
     }
 
-    pub async fn fetch_all_filerecords(
+    pub async fn fetch_all_filerecords<E: StatusEmitter>(
         &mut self,
         enabled: &Enabled,
         pref: &Preferences,
-        app: &AppHandle,
+        app: &E,
     ) -> Result<(), sqlx::Error> {
         println!("Gathering all records from database");
         self.fetch_filerecords(
             &format!(
-                "SELECT rowid, filepath, duration, _fingerprint, description, channels, bitdepth, samplerate, _DualMono, {} FROM {}",
+                "SELECT rowid, filepath, duration, description, channels, bitdepth, samplerate, {} FROM {}",
                 pref.get_data_requirements(),
                 TABLE
             ),
@@ -904,10 +966,22 @@ impl Database {
 
         Ok(columns)
     }
-    pub async fn update_channel_count_to_mono(
+    /// Flags the records of the file at `path` so Soundminer picks up its rewritten metadata.
+    pub async fn mark_dirty(&self, path: &str) -> R<()> {
+        if let Some(pool) = self.get_pool().await {
+            sqlx::query(&format!("UPDATE {TABLE} SET _Dirty = 1 WHERE FilePath = ?"))
+                .bind(path)
+                .execute(&pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn update_channel_count<E: StatusEmitter>(
         &self,
-        app: &AppHandle,
+        app: &E,
         record_ids: &[usize],
+        channels: u16,
     ) -> Result<(), sqlx::Error> {
         const BATCH_SIZE: usize = 1000; // Smaller batch size for updates
 
@@ -920,15 +994,14 @@ impl Database {
             // Process in batches
             for chunk in record_ids.chunks(BATCH_SIZE) {
                 // Create placeholders for SQL IN clause
-                let placeholders = std::iter::repeat("?")
-                    .take(chunk.len())
+                let placeholders = std::iter::repeat_n("?", chunk.len())
                     .collect::<Vec<_>>()
                     .join(",");
 
                 // Build update query
                 let query = format!(
-                    "UPDATE {} SET Channels = 1, _Dirty = 1 WHERE rowid IN ({})",
-                    TABLE, placeholders
+                    "UPDATE {} SET Channels = {}, _Dirty = 1 WHERE rowid IN ({})",
+                    TABLE, channels, placeholders
                 );
 
                 // Create query builder
@@ -963,7 +1036,12 @@ impl Database {
             app.rstatus(
                 "complete",
                 100,
-                format!("Updated {} records to mono", record_ids.len()).as_str(),
+                format!(
+                    "Updated {} records to {} channels",
+                    record_ids.len(),
+                    channels
+                )
+                .as_str(),
             );
         }
 
@@ -994,20 +1072,24 @@ fn checktags(name: &str, tags: &Vec<Arc<str>>) -> bool {
     false
 }
 
-#[derive(PartialEq, serde::Serialize, Deserialize, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, serde::Serialize, Deserialize, Clone, Default)]
 pub enum Delete {
     #[default]
     Keep,
     Trash,
     Delete,
+    Quarantine {
+        root: PathBuf,
+    },
 }
 
 impl Delete {
-    pub fn delete_files(
+    /// Returns the files that were actually removed so the removal can be journaled.
+    pub fn delete_files<E: StatusEmitter>(
         &self,
         files: Vec<&str>,
-        app: &AppHandle,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        app: &E,
+    ) -> Result<Vec<RemovedFile>, Box<dyn std::error::Error>> {
         println!("Removing Files");
         app.rsubstatus("remove", 0, "Preparing to remove files...");
 
@@ -1037,9 +1119,11 @@ impl Delete {
 
         if valid_files.is_empty() {
             app.rsubstatus("remove", 100, "No valid files to process");
-            return Ok(());
+            return Ok(Vec::new());
         }
 
+        let mut removed: Vec<&str> = Vec::new();
+        let mut quarantined: HashMap<PathBuf, PathBuf> = HashMap::new();
         match self {
             Delete::Trash => {
                 #[cfg(target_os = "windows")]
@@ -1054,7 +1138,7 @@ impl Delete {
                         );
 
                         match trash::delete(file) {
-                            Ok(_) => {}
+                            Ok(_) => removed.push(*file),
                             Err(e) => {
                                 // Log error but continue with other files
                                 println!("Failed to move to trash: {}: {}", file, e);
@@ -1071,15 +1155,13 @@ impl Delete {
                 #[cfg(not(target_os = "windows"))]
                 {
                     // macOS/Linux - use batch operation which is more efficient
-                    if !valid_files.is_empty() {
-                        app.rsubstatus("remove", 50, "Moving files to trash...");
-                        match trash::delete_all(&valid_files) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                app.rsubstatus("error", 100, &format!("Trash error: {}", e));
-                                eprintln!("Move to Trash Failed: {}", e);
-                                return Err(e.into());
-                            }
+                    app.rsubstatus("remove", 50, "Moving files to trash...");
+                    match trash::delete_all(&valid_files) {
+                        Ok(_) => removed.extend(&valid_files),
+                        Err(e) => {
+                            app.rsubstatus("error", 100, &format!("Trash error: {}", e));
+                            eprintln!("Move to Trash Failed: {}", e);
+                            return Err(e.into());
                         }
                     }
                 }
@@ -1093,21 +1175,51 @@ impl Delete {
                         &format!("Permanently deleting: {}/{}", i + 1, total),
                     );
 
-                    if let Err(e) = fs::remove_file(file) {
-                        eprintln!("Failed to remove file {}: {}", file, e);
-                        app.rsubstatus(
-                            "warning",
-                            10 + (i * 90 / total),
-                            &format!("Warning: Failed to delete: {}", file),
-                        );
+                    match fs::remove_file(file) {
+                        Ok(_) => removed.push(*file),
+                        Err(e) => {
+                            eprintln!("Failed to remove file {}: {}", file, e);
+                            app.rsubstatus(
+                                "warning",
+                                10 + (i * 90 / total),
+                                &format!("Warning: Failed to delete: {}", file),
+                            );
+                        }
                     }
                 }
             }
+            Delete::Quarantine { root } => {
+                for (original, location) in quarantine_files(root, &valid_files, app)? {
+                    quarantined.insert(original, location);
+                }
+                removed.extend(
+                    valid_files
+                        .iter()
+                        .filter(|file| quarantined.contains_key(Path::new(file))),
+                );
+            }
             _ => {}
         }
 
+        let mut locations = match self {
+            Delete::Trash => trashed_locations(&removed),
+            Delete::Quarantine { .. } => quarantined,
+            _ => HashMap::new(),
+        };
+        let removed = removed
+            .into_iter()
+            .map(|file| {
+                let original = PathBuf::from(file);
+                RemovedFile {
+                    location: locations.remove(&original),
+                    original,
+                    action: self.clone(),
+                }
+            })
+            .collect();
+
         app.rsubstatus("remove", 100, "File removal complete");
-        Ok(())
+        Ok(removed)
     }
 }
 
@@ -1130,124 +1242,6 @@ fn get_column_as_string(row: &SqliteRow, column: &str) -> Option<Arc<str>> {
     None
 }
 
-async fn batch_store_data_optimized(
-    pool: &SqlitePool,
-    data: &[(usize, &str)],
-    column: &str,
-    app: &AppHandle,
-) {
-    let name: &str = column.strip_prefix('_').unwrap_or(column);
-
-    if data.is_empty() {
-        println!("No {} to store", name);
-        return;
-    }
-
-    println!("Storing {} {} in database", data.len(), name);
-
-    app.substatus(
-        "db-storage",
-        0,
-        format!("Storing {} {} in database...", name, data.len()).as_str(),
-    );
-
-    match pool.begin().await {
-        Ok(mut tx) => {
-            let _ = sqlx::query("PRAGMA journal_mode = WAL")
-                .execute(&mut *tx)
-                .await;
-            let _ = sqlx::query("PRAGMA synchronous = NORMAL")
-                .execute(&mut *tx)
-                .await;
-
-            let total = data.len();
-            let mut success_count = 0;
-            let mut error_count = 0;
-
-            for (i, (id, d)) in data.iter().enumerate() {
-                if i % 25 == 0 || i == total - 1 {
-                    app.substatus(
-                        "db-storage",
-                        (i + 1) * 100 / total,
-                        format!("Storing {}: {}/{}", name, i + 1, total).as_str(),
-                    );
-                }
-
-                let result = sqlx::query(&format!(
-                    "UPDATE {} SET {} = ? WHERE rowid = ?",
-                    TABLE, column
-                ))
-                .bind(d)
-                .bind(*id as i64)
-                .execute(&mut *tx)
-                .await;
-
-                match result {
-                    Ok(result) => {
-                        if result.rows_affected() == 0 {
-                            println!(
-                                "WARNING: No rows affected when updating {} for ID {}",
-                                name, id
-                            );
-                        } else {
-                            success_count += 1;
-                        }
-                    }
-                    Err(e) => {
-                        println!("ERROR updating {} for ID {}: {}", name, id, e);
-                        error_count += 1;
-                    }
-                }
-            }
-            app.substatus(
-                "db-storage",
-                99,
-                &format!(
-                    "Committing all changes to database: {} {}s updated, {} errors",
-                    success_count, name, error_count
-                ),
-            );
-
-            match tx.commit().await {
-                Ok(_) => {
-                    println!(
-                        "Transaction committed successfully: {} {}s updated, {} errors",
-                        success_count, name, error_count
-                    );
-
-                    let checkpoint_result = sqlx::query("PRAGMA wal_checkpoint(FULL)")
-                        .execute(pool)
-                        .await;
-
-                    if let Err(e) = checkpoint_result {
-                        println!("WARNING: Checkpoint failed: {}", e);
-                    } else {
-                        println!("Database checkpoint successful");
-                    }
-                }
-                Err(e) => println!("ERROR: Transaction failed to commit: {}", e),
-            }
-            app.substatus(
-                "db-storage",
-                100,
-                format!(
-                    "Database update complete: {} {} stored",
-                    success_count, name
-                )
-                .as_str(),
-            );
-        }
-        Err(e) => {
-            println!("ERROR: Failed to start transaction: {}", e);
-            app.substatus(
-                "db-storage",
-                100,
-                &format!("ERROR: Databaes update failed: {}", e),
-            );
-        }
-    }
-}
-
 // async fn update_column(
 //     pool: &SqlitePool,
 //     row: usize,
@@ -0,0 +1,528 @@
+use crate::prelude::*;
+
+// Columns describing the sound rather than the file, the only ones a duplicate can hand down.
+// Anything else, like paths, audio properties, dates or Soundminer's own bookkeeping, stays.
+const DESCRIPTIVE_COLUMNS: [&str; 30] = [
+    "Description",
+    "Description_en",
+    "Keywords",
+    "Notes",
+    "Designer",
+    "DesignerInitials",
+    "FXName",
+    "TrackTitle",
+    "Category",
+    "Category_en",
+    "SubCategory",
+    "SubCategory_en",
+    "CatID",
+    "CategoryFull",
+    "UserCategory",
+    "VendorCategory",
+    "Library",
+    "Manufacturer",
+    "Show",
+    "Scene",
+    "Take",
+    "Tape",
+    "Location",
+    "Microphone",
+    "MicPerspective",
+    "RecType",
+    "RecMedium",
+    "Source",
+    "URL",
+    "UserComments",
+];
+
+const EMPTY_IXML: &str =
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><BWFXML><IXML_VERSION>2.10</IXML_VERSION></BWFXML>";
+
+/// How a column of the keeper takes in the values of the duplicates removed from its group
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// Leave the keeper's value alone
+    #[default]
+    Keep,
+    /// Take the first filled in duplicate value when the keeper's is empty
+    FillEmpty,
+    /// Add every comma or semicolon separated entry the keeper doesn't have yet
+    Keywords,
+    /// Take the longest value, the keeper's on a tie
+    Longest,
+}
+
+impl MergePolicy {
+    /// The keeper's new value, or `None` when it stays as it is.
+    pub fn merge(self, keeper: &str, duplicates: &[&str]) -> Option<String> {
+        let merged = match self {
+            MergePolicy::Keep => return None,
+            MergePolicy::FillEmpty => {
+                if !keeper.trim().is_empty() {
+                    return None;
+                }
+                duplicates
+                    .iter()
+                    .find(|v| !v.trim().is_empty())?
+                    .to_string()
+            }
+            MergePolicy::Keywords => {
+                let mut keywords: Vec<&str> = Vec::new();
+                let mut keeper_count = None;
+                for value in std::iter::once(&keeper).chain(duplicates) {
+                    for keyword in value.split([',', ';']).map(str::trim) {
+                        if !keyword.is_empty()
+                            && !keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword))
+                        {
+                            keywords.push(keyword);
+                        }
+                    }
+                    keeper_count.get_or_insert(keywords.len());
+                }
+                // Only rewrite the keeper's list when there is something to add
+                if Some(keywords.len()) == keeper_count {
+                    return None;
+                }
+                keywords.join(", ")
+            }
+            MergePolicy::Longest => duplicates
+                .iter()
+                .fold(keeper, |longest, value| {
+                    if value.trim().chars().count() > longest.trim().chars().count() {
+                        value
+                    } else {
+                        longest
+                    }
+                })
+                .to_string(),
+        };
+        (merged != keeper).then_some(merged)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeOptions {
+    /// Policies by column name, matched ignoring case. Only descriptive columns are merged.
+    #[serde(default)]
+    pub policies: HashMap<Arc<str>, MergePolicy>,
+    /// Policy of the columns not listed in `policies`
+    #[serde(default)]
+    pub default: MergePolicy,
+    /// Also write the merged values into the keeper's file. Never done when removing into a
+    /// safety copy, whose files are the original database's too.
+    #[serde(default)]
+    pub embed: bool,
+}
+
+impl MergeOptions {
+    pub fn policy(&self, column: &str) -> MergePolicy {
+        if !DESCRIPTIVE_COLUMNS
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(column))
+        {
+            return MergePolicy::Keep;
+        }
+        self.policies
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(column))
+            .map(|(_, policy)| *policy)
+            .unwrap_or(self.default)
+    }
+}
+
+/// Merged values of one keeper
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeeperMerge {
+    pub id: usize,
+    pub path: Arc<str>,
+    pub values: Vec<(Arc<str>, String)>,
+}
+
+impl Database {
+    /// Works out what merging the metadata of the records about to be removed into the keepers
+    /// of their groups in the current results would change, without changing anything. Groups
+    /// whose keeper is removed too are left alone.
+    pub async fn plan_merge(
+        &self,
+        records: &[usize],
+        options: &MergeOptions,
+    ) -> Result<Vec<KeeperMerge>, sqlx::Error> {
+        const BATCH_SIZE: usize = 12321;
+        let Some(pool) = self.get_pool().await else {
+            return Err(sqlx::Error::Configuration(
+                "No database connection available".into(),
+            ));
+        };
+
+        let removed: HashSet<usize> = records.iter().copied().collect();
+        let groups: Vec<(usize, Vec<usize>)> = self
+            .groups()
+            .into_iter()
+            .filter_map(|group| {
+                let keeper = group.keeper.filter(|k| !removed.contains(k))?;
+                let duplicates: Vec<usize> = group
+                    .members
+                    .into_iter()
+                    .filter(|id| *id != keeper && removed.contains(id))
+                    .collect();
+                (!duplicates.is_empty()).then_some((keeper, duplicates))
+            })
+            .collect();
+
+        let columns: Vec<(Arc<str>, MergePolicy)> = self
+            .fetch_columns()
+            .await?
+            .into_iter()
+            .map(|c| {
+                let policy = options.policy(&c);
+                (c, policy)
+            })
+            .filter(|(_, policy)| *policy != MergePolicy::Keep)
+            .collect();
+        if groups.is_empty() || columns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: Vec<usize> = groups
+            .iter()
+            .flat_map(|(keeper, duplicates)| std::iter::once(keeper).chain(duplicates))
+            .copied()
+            .collect();
+        let select = columns
+            .iter()
+            .map(|(c, _)| format!("\"{}\"", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut rows = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let placeholders = std::iter::repeat_n("?", chunk.len())
+                .collect::<Vec<_>>()
+                .join(",");
+            let query = format!(
+                "SELECT rowid, filepath, {} FROM {} WHERE rowid IN ({})",
+                select, TABLE, placeholders
+            );
+            let mut query_builder = sqlx::query(&query);
+            for &id in chunk {
+                query_builder = query_builder.bind(id as i64);
+            }
+            rows.extend(query_builder.fetch_all(&pool).await?);
+        }
+        let rows: HashMap<usize, &SqliteRow> = rows
+            .iter()
+            .map(|row| (row.try_get::<i64, _>(0).unwrap_or_default() as usize, row))
+            .collect();
+
+        Ok(groups
+            .iter()
+            .filter_map(|(keeper, duplicates)| {
+                let keeper_row = rows.get(keeper)?;
+                let duplicate_rows: Vec<&SqliteRow> = duplicates
+                    .iter()
+                    .filter_map(|id| rows.get(id).copied())
+                    .collect();
+                let values: Vec<(Arc<str>, String)> = columns
+                    .iter()
+                    .filter_map(|(column, policy)| {
+                        let keeper_value = get_column_as_string(keeper_row, column);
+                        let duplicate_values: Vec<Arc<str>> = duplicate_rows
+                            .iter()
+                            .filter_map(|row| get_column_as_string(row, column))
+                            .collect();
+                        let duplicate_values: Vec<&str> =
+                            duplicate_values.iter().map(|v| &**v).collect();
+                        let merged = policy.merge(
+                            keeper_value.as_deref().unwrap_or_default(),
+                            &duplicate_values,
+                        )?;
+                        Some((column.clone(), merged))
+                    })
+                    .collect();
+                (!values.is_empty()).then(|| KeeperMerge {
+                    id: *keeper,
+                    path: get_column_as_string(keeper_row, "filepath").unwrap_or_default(),
+                    values,
+                })
+            })
+            .collect())
+    }
+
+    /// Writes `merges` from `plan_merge` into the keepers' rows and marks them dirty. With
+    /// `embed` set the values also go into the keepers' files; files that can't be written are
+    /// reported as an error once the rest are done. Returns the number of keepers updated.
+    pub async fn merge_into_keepers<E: StatusEmitter>(
+        &self,
+        app: &E,
+        merges: &[KeeperMerge],
+        embed: bool,
+    ) -> R<usize> {
+        if merges.is_empty() {
+            return Ok(0);
+        }
+        let pool = self
+            .get_pool()
+            .await
+            .ok_or_else(|| anyhow!("No database connection available"))?;
+
+        app.rstatus("starting", 0, "Merging Metadata into Keepers...");
+        let mut tx = pool.begin().await?;
+        for (counter, merge) in merges.iter().enumerate() {
+            let set = merge
+                .values
+                .iter()
+                .map(|(c, _)| format!("\"{}\" = ?", c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!("UPDATE {} SET {}, _Dirty = 1 WHERE rowid = ?", TABLE, set);
+            let mut query_builder = sqlx::query(&query);
+            for (_, value) in &merge.values {
+                query_builder = query_builder.bind(value);
+            }
+            query_builder
+                .bind(merge.id as i64)
+                .execute(&mut *tx)
+                .await?;
+
+            app.rstatus(
+                "updating",
+                (counter + 1) * 100 / merges.len(),
+                format!("Merging metadata: {}/{}", counter + 1, merges.len()).as_str(),
+            );
+        }
+        tx.commit().await?;
+
+        if embed {
+            let failed: Vec<String> = merges
+                .par_iter()
+                .filter_map(|merge| {
+                    embed_merged(Path::new(&*merge.path), &merge.values)
+                        .err()
+                        .map(|e| format!("{}: {}", merge.path, e))
+                })
+                .collect();
+            if !failed.is_empty() {
+                return Err(anyhow!(
+                    "Failed to embed merged metadata in {} of {} files:\n{}",
+                    failed.len(),
+                    merges.len(),
+                    failed.join("\n")
+                ));
+            }
+        }
+
+        Ok(merges.len())
+    }
+}
+
+/// Writes merged column values into a file: Description goes to the bext chunk and every value
+/// to the iXML USER block of WAV and AIFF files, FLAC files get a Vorbis comment per column.
+fn embed_merged(path: &Path, values: &[(Arc<str>, String)]) -> R<()> {
+    let mut metadata = metadata::Metadata::from_file(path)?;
+    if let metadata::Metadata::Flac(tag) = &mut metadata {
+        for (column, value) in values {
+            tag.set_vorbis(column.to_uppercase(), vec![value.clone()]);
+        }
+    } else {
+        if let Some((_, description)) = values
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case("description"))
+        {
+            let mut bext = metadata.bext().unwrap_or_default();
+            bext.description = description.clone();
+            metadata.set_bext(&bext)?;
+        }
+
+        let mut ixml = match metadata.ixml() {
+            Some(ixml) => ixml,
+            None => IXml::parse(EMPTY_IXML)?,
+        };
+        let root = ixml
+            .root_mut()
+            .ok_or_else(|| anyhow!("iXML has no root element"))?;
+        let user = root.child_or_insert("USER");
+        for (column, value) in values {
+            user.child_or_insert(&column.to_uppercase()).set_text(value);
+        }
+        metadata.set_ixml(&ixml)?;
+    }
+    metadata.set_metadata(path.to_str().ok_or_else(|| anyhow!("Invalid path"))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    #[test]
+    fn policies_merge_the_way_they_say() {
+        let merge = |policy: MergePolicy, keeper: &str, duplicates: &[&str]| {
+            policy.merge(keeper, duplicates)
+        };
+        assert_eq!(merge(MergePolicy::Keep, "", &["Door"]), None);
+
+        assert_eq!(
+            merge(MergePolicy::FillEmpty, " ", &["", "Door", "Gate"]),
+            Some("Door".to_string())
+        );
+        assert_eq!(merge(MergePolicy::FillEmpty, "Door", &["Gate"]), None);
+        assert_eq!(merge(MergePolicy::FillEmpty, "", &["", " "]), None);
+
+        assert_eq!(
+            merge(
+                MergePolicy::Keywords,
+                "door, wood",
+                &["Wood; slam", "creak"]
+            ),
+            Some("door, wood, slam, creak".to_string())
+        );
+        assert_eq!(merge(MergePolicy::Keywords, "door, wood", &["DOOR"]), None);
+
+        assert_eq!(
+            merge(MergePolicy::Longest, "Door", &["Door slam", "Door"]),
+            Some("Door slam".to_string())
+        );
+        assert_eq!(merge(MergePolicy::Longest, "Door", &["Gate"]), None);
+    }
+
+    #[test]
+    fn only_descriptive_columns_are_merged() {
+        let options = MergeOptions {
+            policies: HashMap::from([
+                (Arc::from("keywords"), MergePolicy::Keywords),
+                (Arc::from("Channels"), MergePolicy::Longest),
+            ]),
+            default: MergePolicy::FillEmpty,
+            embed: false,
+        };
+        assert_eq!(options.policy("Keywords"), MergePolicy::Keywords);
+        assert_eq!(options.policy("Description"), MergePolicy::FillEmpty);
+        assert_eq!(options.policy("channels"), MergePolicy::Keep);
+        assert_eq!(options.policy("FilePath"), MergePolicy::Keep);
+        assert_eq!(options.policy("ScannedDate"), MergePolicy::Keep);
+        assert_eq!(options.policy("_Dirty"), MergePolicy::Keep);
+    }
+
+    async fn database(dir: &Path) -> Database {
+        let path = dir.join("test.sqlite");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        for query in [
+            "CREATE TABLE justinmetadata (filepath TEXT, Description TEXT, Keywords TEXT, _Dirty INTEGER)",
+            "INSERT INTO justinmetadata (rowid, filepath, Description, Keywords, _Dirty) VALUES
+                (1, '/missing/keeper.wav', '', 'door', 0),
+                (2, '/missing/copy.wav', 'Door slam', 'wood, door', 0),
+                (3, '/missing/other.wav', 'Gate', '', 0)",
+        ] {
+            sqlx::query(query).execute(&pool).await.unwrap();
+        }
+        pool.close().await;
+
+        let mut db = Database::new(path.to_str().unwrap(), false).await;
+        for (id, role) in [
+            (1, GroupRole::Keeper),
+            (2, GroupRole::Duplicate),
+            (3, GroupRole::Duplicate),
+        ] {
+            let mut record = FileRecord {
+                id,
+                ..Default::default()
+            };
+            record.join_group(1, role);
+            db.records.push(record);
+        }
+        db
+    }
+
+    async fn row(db: &Database, id: i64) -> Option<(String, String, i64)> {
+        sqlx::query_as("SELECT Description, Keywords, _Dirty FROM justinmetadata WHERE rowid = ?")
+            .bind(id)
+            .fetch_optional(&db.get_pool().await.unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merges_into_keepers_and_undoes_the_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = database(dir.path()).await;
+        let options = MergeOptions {
+            policies: HashMap::from([(Arc::from("Keywords"), MergePolicy::Keywords)]),
+            default: MergePolicy::FillEmpty,
+            embed: false,
+        };
+
+        // Only record 2 goes, so record 3 has nothing to give
+        let merges = db.plan_merge(&[2], &options).await.unwrap();
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].id, 1);
+
+        let mut journal = RemovalJournal::new(&dir.path().join("test.sqlite"));
+        db.journal_rows(&[2], &mut journal).await.unwrap();
+        db.journal_updated_rows(&[1], &mut journal).await.unwrap();
+        assert_eq!(
+            db.merge_into_keepers(&NoopEmitter, &merges, false)
+                .await
+                .unwrap(),
+            1
+        );
+        db.remove(&[2], &NoopEmitter).await.unwrap();
+        assert_eq!(
+            row(&db, 1).await,
+            Some(("Door slam".into(), "door, wood".into(), 1))
+        );
+        assert_eq!(row(&db, 2).await, None);
+
+        db.restore_rows(&journal, &NoopEmitter).await.unwrap();
+        assert_eq!(row(&db, 1).await, Some(("".into(), "door".into(), 0)));
+        assert_eq!(
+            row(&db, 2).await,
+            Some(("Door slam".into(), "wood, door".into(), 0))
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_files_it_could_not_embed_into() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = database(dir.path()).await;
+        let options = MergeOptions {
+            default: MergePolicy::FillEmpty,
+            ..Default::default()
+        };
+
+        let merges = db.plan_merge(&[2, 3], &options).await.unwrap();
+        let error = db
+            .merge_into_keepers(&NoopEmitter, &merges, true)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("/missing/keeper.wav"));
+        // The database still has the merged values, marked for Soundminer to embed
+        assert_eq!(
+            row(&db, 1).await,
+            Some(("Door slam".into(), "door".into(), 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn safety_copies_keep_the_results_to_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = database(dir.path()).await;
+        let clone = db.create_clone("thinned").await;
+        let options = MergeOptions {
+            default: MergePolicy::FillEmpty,
+            ..Default::default()
+        };
+
+        let merges = clone.plan_merge(&[2], &options).await.unwrap();
+        clone
+            .merge_into_keepers(&NoopEmitter, &merges, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            row(&clone, 1).await,
+            Some(("Door slam".into(), "door".into(), 1))
+        );
+        assert_eq!(row(&db, 1).await, Some(("".into(), "door".into(), 0)));
+    }
+}
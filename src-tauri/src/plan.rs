@@ -0,0 +1,282 @@
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlannedAction {
+    Keep,
+    Trash,
+    Delete,
+    Quarantine,
+    StripDualMono,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlannedStatus {
+    Ok,
+    Missing,
+    PermissionDenied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub action: PlannedAction,
+    pub destination: Option<PathBuf>,
+    pub size: u64,
+    pub status: PlannedStatus,
+    pub reason: Option<String>,
+}
+
+/// What `remove_records` would do with the same arguments. Building one touches nothing.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RemovalPlan {
+    pub database: Option<PathBuf>,
+    pub clone: bool,
+    pub rows_requested: usize,
+    pub rows_affected: usize,
+    pub files: Vec<PlannedFile>,
+    pub bytes_freed: u64,
+    pub missing: usize,
+    pub permission_denied: usize,
+}
+
+impl RemovalPlan {
+    /// One row per file, then the totals after a blank line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_row(&["path", "action", "destination", "size", "status", "reason"]);
+        for file in &self.files {
+            csv.push_str(&csv_row(&[
+                file.path.to_string_lossy().to_string(),
+                format!("{:?}", file.action),
+                file.destination
+                    .as_ref()
+                    .map(|d| d.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                file.size.to_string(),
+                format!("{:?}", file.status),
+                file.reason.clone().unwrap_or_default(),
+            ]));
+        }
+
+        csv.push('\n');
+        csv.push_str(&csv_row(&["summary", "value"]));
+        let database = self
+            .database
+            .as_ref()
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+        for (name, value) in [
+            ("database", database),
+            ("safety copy", self.clone.to_string()),
+            ("rows requested", self.rows_requested.to_string()),
+            ("rows affected", self.rows_affected.to_string()),
+            ("files", self.files.len().to_string()),
+            ("bytes freed", self.bytes_freed.to_string()),
+            ("missing", self.missing.to_string()),
+            ("permission denied", self.permission_denied.to_string()),
+        ] {
+            csv.push_str(&csv_row(&[name.to_string(), value]));
+        }
+        csv
+    }
+
+    pub fn export(&self, path: &Path) -> R<()> {
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => self.to_csv(),
+            _ => serde_json::to_string_pretty(self)?,
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn push(&mut self, file: PlannedFile) {
+        match file.status {
+            PlannedStatus::Ok => {
+                if matches!(
+                    file.action,
+                    PlannedAction::Trash | PlannedAction::Delete | PlannedAction::Quarantine
+                ) {
+                    self.bytes_freed += file.size;
+                }
+            }
+            PlannedStatus::Missing => self.missing += 1,
+            PlannedStatus::PermissionDenied => self.permission_denied += 1,
+        }
+        self.files.push(file);
+    }
+}
+
+impl Database {
+    pub async fn count_rows(&self, ids: &[usize]) -> Result<usize, sqlx::Error> {
+        const BATCH_SIZE: usize = 12321;
+        let Some(pool) = self.get_pool().await else {
+            return Ok(0);
+        };
+        let mut count = 0;
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let placeholders = std::iter::repeat_n("?", chunk.len())
+                .collect::<Vec<_>>()
+                .join(",");
+            let query = format!(
+                "SELECT COUNT(*) FROM {} WHERE rowid IN ({})",
+                TABLE, placeholders
+            );
+            let mut query_builder = sqlx::query_scalar::<_, i64>(&query);
+            for &id in chunk {
+                query_builder = query_builder.bind(id as i64);
+            }
+            count += query_builder.fetch_one(&pool).await? as usize;
+        }
+        Ok(count)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn plan_removal(
+        &self,
+        clone: bool,
+        clone_tag: &str,
+        records: &[usize],
+        delete: &Delete,
+        files: &[&str],
+        dual_mono: &[DualMono],
+        strip_dual_mono: bool,
+        strip_quiet_channels: bool,
+    ) -> Result<RemovalPlan, sqlx::Error> {
+        // Removal runs on the safety copy, a byte for byte copy of this database, so its rows
+        // are counted here
+        let mut plan = RemovalPlan {
+            database: if clone {
+                self.clone_path(clone_tag)
+            } else {
+                self.path.clone()
+            },
+            clone,
+            rows_requested: records.len(),
+            rows_affected: self.count_rows(records).await?,
+            ..Default::default()
+        };
+
+        if strip_dual_mono {
+            for record in self.channel_strips(dual_mono, strip_quiet_channels) {
+                plan.push(plan_file(
+                    Path::new(&record.path),
+                    PlannedAction::StripDualMono,
+                    None,
+                ));
+            }
+        }
+
+        let (action, root) = match delete {
+            Delete::Keep => (PlannedAction::Keep, None),
+            Delete::Trash => (PlannedAction::Trash, None),
+            Delete::Delete => (PlannedAction::Delete, None),
+            Delete::Quarantine { root } => (PlannedAction::Quarantine, Some(root)),
+        };
+        for file in files {
+            let path = Path::new(file);
+            let destination = root.map(|root| unique_path(mirrored_path(root, path)));
+            plan.push(plan_file(path, action, destination));
+        }
+
+        Ok(plan)
+    }
+}
+
+fn plan_file(path: &Path, action: PlannedAction, destination: Option<PathBuf>) -> PlannedFile {
+    let mut file = PlannedFile {
+        path: path.to_path_buf(),
+        action,
+        destination,
+        size: 0,
+        status: PlannedStatus::Ok,
+        reason: None,
+    };
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            file.status = PlannedStatus::Missing;
+            file.reason = Some("File does not exist".into());
+            return file;
+        }
+        Err(e) => {
+            file.status = PlannedStatus::PermissionDenied;
+            file.reason = Some(e.to_string());
+            return file;
+        }
+    };
+    file.size = metadata.len();
+
+    if let Some(reason) = permission_problem(path, &metadata, action) {
+        file.status = PlannedStatus::PermissionDenied;
+        file.reason = Some(reason.into());
+    }
+    file
+}
+
+// Best effort: only looks at permission flags so nothing on disk is touched
+fn permission_problem(
+    path: &Path,
+    metadata: &fs::Metadata,
+    action: PlannedAction,
+) -> Option<&'static str> {
+    match action {
+        PlannedAction::Keep => None,
+        PlannedAction::StripDualMono => metadata
+            .permissions()
+            .readonly()
+            .then_some("File is read-only"),
+        _ => {
+            let parent = path.parent()?;
+            if fs::metadata(parent).is_ok_and(|m| m.permissions().readonly()) {
+                return Some("Folder is read-only");
+            }
+            // Windows refuses to delete read-only files, unix only cares about the folder
+            if cfg!(target_os = "windows") && metadata.permissions().readonly() {
+                return Some("File is read-only");
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_ends_with_the_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        let door = dir.path().join("Door.wav");
+        fs::write(&door, [0u8; 100]).unwrap();
+        let mut plan = RemovalPlan {
+            database: Some(PathBuf::from("/dbs/Library.sqlite")),
+            rows_requested: 3,
+            rows_affected: 2,
+            ..Default::default()
+        };
+        plan.push(plan_file(&door, PlannedAction::Trash, None));
+        plan.push(plan_file(
+            &dir.path().join("Gone.wav"),
+            PlannedAction::Trash,
+            None,
+        ));
+
+        let csv = plan.to_csv();
+        let (files, summary) = csv.split_once("\n\n").unwrap();
+        assert_eq!(files.lines().count(), 3);
+        assert_eq!(
+            summary.lines().collect::<Vec<_>>(),
+            [
+                "summary,value",
+                "database,/dbs/Library.sqlite",
+                "safety copy,false",
+                "rows requested,3",
+                "rows affected,2",
+                "files,2",
+                "bytes freed,100",
+                "missing,1",
+                "permission denied,0",
+            ]
+        );
+    }
+}
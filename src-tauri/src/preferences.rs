@@ -6,7 +6,8 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{cmp::Ordering, collections::HashSet, sync::Arc};
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Preferences {
     pub columns: Vec<Arc<str>>,
     pub match_criteria: Vec<Arc<str>>,
@@ -19,7 +20,30 @@ pub struct Preferences {
     pub store_waveforms: bool,
     pub fetch_waveforms: bool,
     pub similarity_threshold: f64,
+    pub fast_similarity: bool, // index fingerprints for Similar matching, faster but may miss pairs
     pub batch_size: usize,
+    pub channel_tolerance_db: f64, // dBFS below which channels count as silent or alike
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            match_criteria: Vec::new(),
+            ignore_filetype: false,
+            tags: Vec::new(),
+            autoselects: Vec::new(),
+            preservation_order: Vec::new(),
+            display_all_records: false,
+            waveform_search_type: WaveformMatchType::default(),
+            store_waveforms: false,
+            fetch_waveforms: false,
+            similarity_threshold: 0.0,
+            fast_similarity: false,
+            batch_size: 0,
+            channel_tolerance_db: crate::DEFAULT_CHANNEL_TOLERANCE_DB,
+        }
+    }
 }
 
 impl Preferences {
@@ -369,11 +393,16 @@ pub enum Algorithm {
     Replace,
     Manual,
     DualMono,
+    SilentChannel,
+    DuplicateChannels, // some channels are identical, but not all of them
+    InvertedChannels,
+    NearIdenticalChannels,
     #[default]
     Keep,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Enabled {
     pub basic: bool,
     pub invalidpath: bool,
@@ -385,13 +414,60 @@ pub struct Enabled {
     pub min_dur: f64,
     pub dbcompare: bool,
     pub compare_db: Arc<str>,
+    pub compare_dbs: Vec<Arc<str>>, // searched alongside `compare_db`
+    pub compare_key: CompareKey,
     pub dual_mono: bool,
 }
 
+impl Enabled {
+    /// Every compare database path, in order and without repeats.
+    pub fn compare_databases(&self) -> Vec<Arc<str>> {
+        let mut paths: Vec<Arc<str>> = Vec::new();
+        for path in std::iter::once(&self.compare_db).chain(&self.compare_dbs) {
+            if !path.is_empty() && !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+        paths
+    }
+}
+
+/// What a record has to share with a compare database record to be flagged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CompareKey {
+    #[default]
+    Filename,
+    Root, // filename after `set_root` normalization
+    FilenameDuration,
+    Checksum,
+    Fingerprint, // Chromaprint similarity at `similarity_threshold`
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum WaveformMatchType {
     #[default]
     Exact,
     Similar,
     Subset,
+    Checksum,
+    Landmark,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_tolerance_defaults_to_the_shared_constant() {
+        assert_eq!(
+            Preferences::default().channel_tolerance_db,
+            crate::DEFAULT_CHANNEL_TOLERANCE_DB
+        );
+        let saved: Preferences = serde_json::from_str(r#"{"similarity_threshold": 90}"#).unwrap();
+        assert_eq!(
+            saved.channel_tolerance_db,
+            crate::DEFAULT_CHANNEL_TOLERANCE_DB
+        );
+        assert_eq!(saved.similarity_threshold, 90.0);
+    }
 }
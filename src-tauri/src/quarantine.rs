@@ -0,0 +1,335 @@
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Component;
+
+pub const MANIFEST_NAME: &str = "smdbc_quarantine.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    pub original: PathBuf,
+    pub quarantined: PathBuf,
+    pub size: u64,
+    pub moved: String,
+}
+
+/// Lists every file currently held under a quarantine root. Lives at `<root>/smdbc_quarantine.jsonl`,
+/// one entry per line, so each moved file can be recorded as soon as it is moved.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuarantineManifest {
+    pub entries: Vec<QuarantineEntry>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub failed: Vec<String>,
+}
+
+impl QuarantineManifest {
+    fn path(root: &Path) -> PathBuf {
+        root.join(MANIFEST_NAME)
+    }
+
+    pub fn load(root: &Path) -> R<Self> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let entries = fs::read_to_string(&path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                // Only a crash while appending leaves a line cut off
+                Err(e) => {
+                    println!("Skipping damaged quarantine manifest line: {}", e);
+                    None
+                }
+            })
+            .collect();
+        Ok(Self { entries })
+    }
+
+    pub fn save(&self, root: &Path) -> R<()> {
+        fs::create_dir_all(root)?;
+        // Write then rename so a crash never leaves a half written manifest
+        let path = Self::path(root);
+        let tmp = path.with_extension("jsonl.tmp");
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&serde_json::to_string(entry)?);
+            text.push('\n');
+        }
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Records one more quarantined file, synced to disk before returning.
+    pub fn append(root: &Path, entry: &QuarantineEntry) -> R<()> {
+        fs::create_dir_all(root)?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(Self::path(root))?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // Start a new line after one cut off by a crash
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    pub fn forget(root: &Path, original: &Path) -> R<()> {
+        let mut manifest = Self::load(root)?;
+        manifest.entries.retain(|e| e.original != original);
+        manifest.save(root)
+    }
+}
+
+/// Where `original` goes under `root`, keeping its full directory structure.
+/// `/Volumes/SFX/Door.wav` becomes `<root>/Volumes/SFX/Door.wav` and `D:\SFX\Door.wav` becomes `<root>/D/SFX/Door.wav`.
+pub fn mirrored_path(root: &Path, original: &Path) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for component in original.components() {
+        match component {
+            Component::Prefix(prefix) => {
+                let drive: String = prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .collect();
+                path.push(drive);
+            }
+            Component::Normal(part) => path.push(part),
+            _ => {}
+        }
+    }
+    path
+}
+
+pub(crate) fn unique_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// Moves each file into the mirrored tree under `root`, recording it in the manifest right after
+/// it moves. Stops at the first file that moved but couldn't be recorded, which is still returned.
+pub fn quarantine_files<E: StatusEmitter>(
+    root: &Path,
+    files: &[&str],
+    app: &E,
+) -> R<Vec<(PathBuf, PathBuf)>> {
+    let mut moved = Vec::new();
+    let total = files.len().max(1);
+
+    for (i, file) in files.iter().enumerate() {
+        app.rsubstatus(
+            "remove",
+            10 + (i * 90 / total),
+            &format!("Moving to quarantine: {}/{}", i + 1, files.len()),
+        );
+        let original = PathBuf::from(file);
+        let destination = unique_path(mirrored_path(root, &original));
+        let size = fs::metadata(&original).map(|m| m.len()).unwrap_or(0);
+
+        match move_file(&original, &destination) {
+            Ok(_) => {
+                let entry = QuarantineEntry {
+                    original: original.clone(),
+                    quarantined: destination.clone(),
+                    size,
+                    moved: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                };
+                let recorded = QuarantineManifest::append(root, &entry);
+                moved.push((original, destination));
+                if let Err(e) = recorded {
+                    println!("Failed to record {} in quarantine manifest: {}", file, e);
+                    app.rsubstatus(
+                        "error",
+                        100,
+                        &format!("Error: Quarantine manifest could not be written: {}", e),
+                    );
+                    break;
+                }
+            }
+            Err(e) => {
+                println!("Failed to quarantine {}: {}", file, e);
+                app.rsubstatus(
+                    "warning",
+                    10 + (i * 90 / total),
+                    &format!("Warning: Failed to quarantine: {}", file),
+                );
+            }
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Puts quarantined files back where they came from.
+/// With `originals` empty everything in the manifest is restored.
+pub fn restore_quarantined<E: StatusEmitter>(
+    root: &Path,
+    originals: &[PathBuf],
+    app: &E,
+) -> R<RestoreSummary> {
+    let mut manifest = QuarantineManifest::load(root)?;
+    let mut summary = RestoreSummary::default();
+    let selected: HashSet<&PathBuf> = originals.iter().collect();
+    let total = manifest.entries.len().max(1);
+
+    let mut remaining = Vec::new();
+    for (i, entry) in manifest.entries.drain(..).enumerate() {
+        if !selected.is_empty() && !selected.contains(&entry.original) {
+            remaining.push(entry);
+            continue;
+        }
+        app.rsubstatus(
+            "restore",
+            i * 100 / total,
+            &format!("Restoring: {}", entry.original.display()),
+        );
+        let result = if entry.original.exists() {
+            Err(anyhow!("A file already exists at the original location"))
+        } else {
+            move_file(&entry.quarantined, &entry.original)
+        };
+        match result {
+            Ok(_) => summary.restored += 1,
+            Err(e) => {
+                println!("Failed to restore {}: {}", entry.original.display(), e);
+                summary
+                    .failed
+                    .push(format!("{}: {}", entry.original.display(), e));
+                remaining.push(entry);
+            }
+        }
+    }
+
+    manifest.entries = remaining;
+    manifest.save(root)?;
+    app.rsubstatus("restore", 100, "Restore complete");
+    Ok(summary)
+}
+
+/// Moves a file, falling back to copy + verify + remove when the destination is on another volume.
+pub fn move_file(from: &Path, to: &Path) -> R<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)?;
+    File::open(to)?.sync_all()?;
+    if fs::metadata(from)?.len() != fs::metadata(to)?.len() || hash_file(from)? != hash_file(to)? {
+        let _ = fs::remove_file(to);
+        return Err(anyhow!(
+            "Copy of {} did not verify, original left in place",
+            from.display()
+        ));
+    }
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> R<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantines_into_a_mirrored_tree_and_restores() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("quarantine");
+        let door = dir.path().join("SFX").join("Door.wav");
+        let gate = dir.path().join("SFX").join("Gate.wav");
+        let missing = dir.path().join("SFX").join("Missing.wav");
+        fs::create_dir_all(door.parent().unwrap()).unwrap();
+        fs::write(&door, b"door").unwrap();
+        fs::write(&gate, b"gate").unwrap();
+
+        let files = [
+            door.to_str().unwrap(),
+            missing.to_str().unwrap(),
+            gate.to_str().unwrap(),
+        ];
+        let moved = quarantine_files(&root, &files, &NoopEmitter).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert_eq!(moved[0].1, mirrored_path(&root, &door));
+        assert!(moved[0].1.starts_with(&root) && moved[0].1.ends_with("SFX/Door.wav"));
+        assert!(!door.exists() && !gate.exists());
+        assert_eq!(fs::read(&moved[1].1).unwrap(), b"gate");
+
+        let manifest = QuarantineManifest::load(&root).unwrap();
+        let originals: Vec<&PathBuf> = manifest.entries.iter().map(|e| &e.original).collect();
+        assert_eq!(originals, [&door, &gate]);
+
+        // Restoring only the door leaves the gate in quarantine
+        let summary =
+            restore_quarantined(&root, std::slice::from_ref(&door), &NoopEmitter).unwrap();
+        assert_eq!(summary.restored, 1);
+        assert_eq!(fs::read(&door).unwrap(), b"door");
+        assert_eq!(QuarantineManifest::load(&root).unwrap().entries.len(), 1);
+
+        let summary = restore_quarantined(&root, &[], &NoopEmitter).unwrap();
+        assert_eq!(summary.restored, 1);
+        assert!(summary.failed.is_empty());
+        assert_eq!(fs::read(&gate).unwrap(), b"gate");
+        assert!(QuarantineManifest::load(&root).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn keeps_recording_after_a_cut_off_manifest_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("quarantine");
+        let door = dir.path().join("Door.wav");
+        fs::write(&door, b"door").unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(MANIFEST_NAME), "{\"original\":\"/SFX/Ga").unwrap();
+
+        quarantine_files(&root, &[door.to_str().unwrap()], &NoopEmitter).unwrap();
+        let manifest = QuarantineManifest::load(&root).unwrap();
+        assert_eq!(manifest.entries.len(), 1);
+        assert_eq!(manifest.entries[0].original, door);
+    }
+}
@@ -0,0 +1,562 @@
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// App-owned analysis data (fingerprints, checksums, channel analysis) keyed by rowid plus a file identity
+/// hash. Soundminer's own `justinmetadata` table is never altered.
+pub const SCAN_TABLE: &str = "smdbc_analysis";
+/// Fingerprints and checksums of compare database files, kept in the primary database because
+/// compare databases are opened read-only. Keyed by file identity alone, which covers the path.
+const COMPARE_CACHE_TABLE: &str = "smdbc_compare_cache";
+
+/// Fingerprint and checksum of a compare database file
+pub type CachedAnalysis = (Option<Arc<str>>, Option<Arc<str>>);
+/// Cached analysis by file identity
+pub type CompareCache = HashMap<Arc<str>, CachedAnalysis>;
+const LEGACY_COLUMNS: [&str; 2] = ["_fingerprint", "_DualMono"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIdentity {
+    pub size: u64,
+    pub mtime: i64,
+}
+
+impl FileIdentity {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        Some(Self {
+            size: metadata.len(),
+            mtime,
+        })
+    }
+
+    /// Changes whenever the file is moved, resized or rewritten.
+    pub fn hash(&self, path: &Path) -> Arc<str> {
+        let mut hasher = Sha256::new();
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(self.size.to_le_bytes());
+        hasher.update(self.mtime.to_le_bytes());
+        Arc::from(&hex::encode(hasher.finalize())[..16])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanState {
+    pub identity: Option<Arc<str>>, // None for rows migrated from the old columns
+    pub fingerprint: Option<Arc<str>>,
+    pub checksum: Option<Arc<str>>,
+    pub dual_mono: Option<bool>,
+    pub channel_analysis: Option<ChannelAnalysis>,
+}
+
+impl FileRecord {
+    pub fn scan_state(&self) -> Option<ScanState> {
+        let identity = self
+            .identity
+            .clone()
+            .or_else(|| Some(FileIdentity::of(&self.path)?.hash(&self.path)))?;
+        Some(ScanState {
+            identity: Some(identity),
+            fingerprint: self.fingerprint.clone(),
+            checksum: self.checksum.clone(),
+            dual_mono: self.dual_mono,
+            channel_analysis: self.channel_analysis.clone(),
+        })
+    }
+}
+
+async fn table_names(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'")
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn ensure_scan_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    if !table_names(pool).await?.iter().any(|t| t == SCAN_TABLE) {
+        create_scan_table(pool).await?;
+    }
+    Ok(())
+}
+
+async fn ensure_compare_cache(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (identity TEXT PRIMARY KEY, fingerprint TEXT, checksum TEXT)",
+        COMPARE_CACHE_TABLE
+    ))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Creating the table is what marks the migration as done, so both happen in one transaction
+async fn create_scan_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let rows = legacy_column_rows(pool).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!(
+        "CREATE TABLE {} (id INTEGER PRIMARY KEY, identity TEXT, fingerprint TEXT, dual_mono INTEGER, checksum TEXT, channel_analysis TEXT)",
+        SCAN_TABLE
+    ))
+    .execute(&mut *tx)
+    .await?;
+    write_scan_state(&mut tx, &rows, &NoopEmitter).await?;
+    tx.commit().await?;
+    if !rows.is_empty() {
+        println!("Migrated analysis for {} records", rows.len());
+    }
+    Ok(())
+}
+
+// The `_fingerprint`/`_DualMono` columns older versions added to `justinmetadata`. They are
+// only read; the table stays as Soundminer expects it.
+async fn legacy_column_rows(pool: &SqlitePool) -> Result<Vec<(usize, ScanState)>, sqlx::Error> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({});", TABLE))
+        .fetch_all(pool)
+        .await?;
+    let present: Vec<&str> = LEGACY_COLUMNS
+        .into_iter()
+        .filter(|legacy| {
+            columns
+                .iter()
+                .any(|row| row.try_get::<&str, _>("name").unwrap_or_default() == *legacy)
+        })
+        .collect();
+    if present.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let select = LEGACY_COLUMNS
+        .iter()
+        .map(|c| {
+            if present.contains(c) {
+                c.to_string()
+            } else {
+                "NULL".to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let query = format!(
+        "SELECT rowid, {} FROM {} WHERE {}",
+        select,
+        TABLE,
+        present
+            .iter()
+            .map(|c| format!("{} IS NOT NULL", c))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    );
+    Ok(sqlx::query(&query)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let fingerprint = row
+                .try_get::<Option<&str>, _>(1)
+                .ok()
+                .flatten()
+                .filter(|fp| !fp.is_empty() && *fp != "FAILED")
+                .map(Arc::from);
+            let dual_mono = match row.try_get::<Option<&str>, _>(2).ok().flatten() {
+                Some("1") => Some(true),
+                Some("0") => Some(false),
+                _ => None,
+            };
+            let state = ScanState {
+                identity: None,
+                fingerprint,
+                checksum: None,
+                dual_mono,
+                channel_analysis: None,
+            };
+            (row.get::<i64, _>(0) as usize, state)
+        })
+        .collect())
+}
+
+/// Upserts analysis rows in one transaction.
+pub async fn store_scan_state<E: StatusEmitter>(
+    pool: &SqlitePool,
+    rows: &[(usize, ScanState)],
+    app: &E,
+) -> Result<(), sqlx::Error> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    ensure_scan_table(pool).await?;
+
+    let mut tx = pool.begin().await?;
+    write_scan_state(&mut tx, rows, app).await?;
+    tx.commit().await?;
+    app.substatus("db-storage", 100, "Analysis stored");
+    println!("Stored analysis for {} records", rows.len());
+    Ok(())
+}
+
+async fn write_scan_state<E: StatusEmitter>(
+    conn: &mut sqlx::SqliteConnection,
+    rows: &[(usize, ScanState)],
+    app: &E,
+) -> Result<(), sqlx::Error> {
+    let query = format!(
+        "INSERT OR REPLACE INTO {} (id, identity, fingerprint, dual_mono, checksum, channel_analysis) VALUES (?, ?, ?, ?, ?, ?)",
+        SCAN_TABLE
+    );
+    for (count, (id, state)) in rows.iter().enumerate() {
+        if count % RECORD_DIVISOR == 0 {
+            app.substatus(
+                "db-storage",
+                count * 100 / rows.len(),
+                &format!("Storing analysis: {}/{}", count, rows.len()),
+            );
+        }
+        sqlx::query(&query)
+            .bind(*id as i64)
+            .bind(state.identity.as_deref())
+            .bind(state.fingerprint.as_deref())
+            .bind(state.dual_mono.map(|b| b as i64))
+            .bind(state.checksum.as_deref())
+            .bind(
+                state
+                    .channel_analysis
+                    .as_ref()
+                    .and_then(|analysis| serde_json::to_string(analysis).ok()),
+            )
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+impl Database {
+    async fn load_scan_state(&self) -> Result<HashMap<usize, ScanState>, sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(HashMap::new());
+        };
+        if !self.is_compare {
+            ensure_scan_table(&pool).await?;
+        } else if !table_names(&pool).await?.iter().any(|t| t == SCAN_TABLE) {
+            return Ok(HashMap::new());
+        }
+
+        let query = format!(
+            "SELECT id, identity, fingerprint, dual_mono, checksum, channel_analysis FROM {}",
+            SCAN_TABLE
+        );
+        let rows = sqlx::query(&query).fetch_all(&pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let id = row.get::<i64, _>(0) as usize;
+                let state = ScanState {
+                    identity: row.get::<Option<&str>, _>(1).map(Arc::from),
+                    fingerprint: row.get::<Option<&str>, _>(2).map(Arc::from),
+                    dual_mono: row.get::<Option<i64>, _>(3).map(|v| v == 1),
+                    checksum: row.get::<Option<&str>, _>(4).map(Arc::from),
+                    channel_analysis: row
+                        .get::<Option<&str>, _>(5)
+                        .and_then(|json| serde_json::from_str(json).ok()),
+                };
+                (id, state)
+            })
+            .collect())
+    }
+
+    /// Checks every record against the analysis from earlier runs. Unchanged files get their
+    /// previous fingerprint, checksum and channel analysis back; new or modified files are left
+    /// empty so the search analyzes them again.
+    pub async fn refresh_scan_state<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        app: &E,
+    ) -> Result<(), sqlx::Error> {
+        app.substatus("scan", 0, "Checking for changed files...");
+        let previous = self.load_scan_state().await?;
+        let total = self.records.len().max(1);
+        let completed = AtomicUsize::new(0);
+        let changed = AtomicUsize::new(0);
+
+        self.records.par_iter_mut().for_each(|record| {
+            let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+            if done.is_multiple_of(RECORD_DIVISOR) {
+                app.substatus(
+                    "scan",
+                    done * 100 / total,
+                    &format!("Checking for changed files: {}/{}", done, total),
+                );
+            }
+            let Some(identity) = FileIdentity::of(&record.path).map(|i| i.hash(&record.path))
+            else {
+                return;
+            };
+            record.identity = Some(identity.clone());
+
+            let Some(state) = previous.get(&record.id) else {
+                return;
+            };
+            // Migrated rows have no identity yet, so they are trusted once
+            let unchanged = state.identity.as_ref().is_none_or(|id| *id == identity);
+            if !unchanged {
+                changed.fetch_add(1, Ordering::SeqCst);
+                return;
+            }
+            if pref.fetch_waveforms {
+                record.fingerprint = state.fingerprint.clone();
+                record.checksum = state.checksum.clone();
+                record.dual_mono = state.dual_mono;
+                record.channel_analysis = state.channel_analysis.clone();
+            }
+        });
+
+        println!(
+            "{} files changed since the last scan",
+            changed.load(Ordering::SeqCst)
+        );
+        self.scan_state = previous;
+        app.substatus("scan", 100, "Changed files checked");
+        Ok(())
+    }
+
+    /// Writes analysis for every record whose file or results changed during this run. Compare
+    /// databases are left alone, see `save_compare_cache`.
+    pub async fn save_scan_state<E: StatusEmitter>(&self, app: &E) -> Result<(), sqlx::Error> {
+        if self.is_compare {
+            return Ok(());
+        }
+        let Some(pool) = self.get_pool().await else {
+            return Ok(());
+        };
+        let updates: Vec<(usize, ScanState)> = self
+            .records
+            .iter()
+            .filter_map(|record| {
+                let state = record.scan_state()?;
+                (self.scan_state.get(&record.id) != Some(&state)).then_some((record.id, state))
+            })
+            .collect();
+        store_scan_state(&pool, &updates, app).await
+    }
+
+    /// Fills in compare records that have no fingerprint or checksum of their own from the cache
+    /// in this database. Returns what was cached, for `save_compare_cache`.
+    pub async fn load_compare_cache(
+        &self,
+        compare: &mut Database,
+    ) -> Result<CompareCache, sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(HashMap::new());
+        };
+        ensure_compare_cache(&pool).await?;
+        let rows = sqlx::query(&format!(
+            "SELECT identity, fingerprint, checksum FROM {}",
+            COMPARE_CACHE_TABLE
+        ))
+        .fetch_all(&pool)
+        .await?;
+        let cache: CompareCache = rows
+            .iter()
+            .map(|row| {
+                (
+                    Arc::from(row.get::<&str, _>(0)),
+                    (
+                        row.get::<Option<&str>, _>(1).map(Arc::from),
+                        row.get::<Option<&str>, _>(2).map(Arc::from),
+                    ),
+                )
+            })
+            .collect();
+
+        for record in compare.records.iter_mut() {
+            let Some((fingerprint, checksum)) =
+                record.identity.as_ref().and_then(|id| cache.get(id))
+            else {
+                continue;
+            };
+            if record.fingerprint.is_none() {
+                record.fingerprint = fingerprint.clone();
+            }
+            if record.checksum.is_none() {
+                record.checksum = checksum.clone();
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Caches the fingerprints and checksums computed for `compare` during this run.
+    pub async fn save_compare_cache<E: StatusEmitter>(
+        &self,
+        compare: &Database,
+        cache: &CompareCache,
+        app: &E,
+    ) -> Result<(), sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(());
+        };
+        let updates: Vec<(Arc<str>, CachedAnalysis)> = compare
+            .records
+            .iter()
+            .filter_map(|record| {
+                let identity = record.identity.clone()?;
+                if record.fingerprint.is_none() && record.checksum.is_none() {
+                    return None;
+                }
+                // Analysis stored in the compare database itself needs no copy
+                let own = compare
+                    .scan_state
+                    .get(&record.id)
+                    .filter(|state| state.identity.as_ref() == Some(&identity));
+                if own.is_some_and(|state| {
+                    state.fingerprint == record.fingerprint && state.checksum == record.checksum
+                }) {
+                    return None;
+                }
+                let entry = (record.fingerprint.clone(), record.checksum.clone());
+                (cache.get(&identity) != Some(&entry)).then_some((identity, entry))
+            })
+            .collect();
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        app.substatus("compare", 0, "Caching compare database analysis...");
+        ensure_compare_cache(&pool).await?;
+        let query = format!(
+            "INSERT OR REPLACE INTO {} (identity, fingerprint, checksum) VALUES (?, ?, ?)",
+            COMPARE_CACHE_TABLE
+        );
+        let mut tx = pool.begin().await?;
+        for (identity, (fingerprint, checksum)) in &updates {
+            sqlx::query(&query)
+                .bind(&**identity)
+                .bind(fingerprint.as_deref())
+                .bind(checksum.as_deref())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn clear_fingerprints(&self) -> Result<(), sqlx::Error> {
+        let Some(pool) = self.get_pool().await else {
+            return Ok(());
+        };
+        ensure_scan_table(&pool).await?;
+        sqlx::query(&format!(
+            "UPDATE {} SET fingerprint = NULL, checksum = NULL",
+            SCAN_TABLE
+        ))
+        .execute(&pool)
+        .await?;
+        ensure_compare_cache(&pool).await?;
+        sqlx::query(&format!("DELETE FROM {}", COMPARE_CACHE_TABLE))
+            .execute(&pool)
+            .await?;
+        self.clear_landmarks().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    type AnalysisRow = (i64, Option<String>, Option<String>, Option<i64>);
+
+    async fn pool(dir: &Path) -> SqlitePool {
+        let options = SqliteConnectOptions::new()
+            .filename(dir.join("test.sqlite"))
+            .create_if_missing(true);
+        SqlitePool::connect_with(options).await.unwrap()
+    }
+
+    async fn execute(pool: &SqlitePool, query: &str) {
+        sqlx::query(query).execute(pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrates_legacy_columns_once_and_leaves_soundminer_table_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = pool(dir.path()).await;
+        execute(
+            &pool,
+            "CREATE TABLE justinmetadata (filepath TEXT, _fingerprint TEXT, _DualMono TEXT)",
+        )
+        .await;
+        execute(
+            &pool,
+            "INSERT INTO justinmetadata (rowid, filepath, _fingerprint, _DualMono) VALUES (1, '/a.wav', 'AQAA', '1'), (2, '/b.wav', 'FAILED', '0')",
+        )
+        .await;
+
+        ensure_scan_table(&pool).await.unwrap();
+        let rows: Vec<AnalysisRow> = sqlx::query_as(
+            "SELECT id, identity, fingerprint, dual_mono FROM smdbc_analysis ORDER BY id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (1, None, Some("AQAA".into()), Some(1)),
+                (2, None, None, Some(0)),
+            ]
+        );
+
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('justinmetadata')")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(columns, ["filepath", "_fingerprint", "_DualMono"]);
+
+        // Later runs keep what was stored since instead of migrating again
+        execute(
+            &pool,
+            "UPDATE smdbc_analysis SET fingerprint = 'NEW' WHERE id = 1",
+        )
+        .await;
+        ensure_scan_table(&pool).await.unwrap();
+        let fingerprint: String =
+            sqlx::query_scalar("SELECT fingerprint FROM smdbc_analysis WHERE id = 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(fingerprint, "NEW");
+    }
+
+    #[tokio::test]
+    async fn compare_databases_are_not_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = pool(dir.path()).await;
+        execute(&pool, "CREATE TABLE justinmetadata (filepath TEXT)").await;
+        execute(
+            &pool,
+            "INSERT INTO justinmetadata (rowid, filepath) VALUES (1, '/a.wav')",
+        )
+        .await;
+        pool.close().await;
+
+        let path = dir.path().join("test.sqlite");
+        let mut compare = Database::new(path.to_str().unwrap(), true).await;
+        compare
+            .refresh_scan_state(&Preferences::default(), &NoopEmitter)
+            .await
+            .unwrap();
+        compare.save_scan_state(&NoopEmitter).await.unwrap();
+
+        let pool = compare.get_pool().await.unwrap();
+        assert_eq!(table_names(&pool).await.unwrap(), ["justinmetadata"]);
+        assert!(
+            sqlx::query("DELETE FROM justinmetadata")
+                .execute(&pool)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -3,24 +3,98 @@
 pub use crate::prelude::*;
 
 impl Database {
-    pub async fn compare_search(&mut self, enabled: &Enabled, pref: &Preferences, app: &AppHandle) {
+    /// Flags records found in any of the compare databases. A record matching several is tagged
+    /// with the first, but counts towards the overlap of each in `compare_summary`.
+    pub async fn compare_search<E: StatusEmitter>(
+        &mut self,
+        enabled: &Enabled,
+        pref: &Preferences,
+        app: &E,
+    ) {
+        self.compare_summary.clear();
+        for path in enabled.compare_databases() {
+            if self.abort.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Some(summary) = self.compare_against(&path, enabled, pref, app).await {
+                println!(
+                    "{} of {} records found in {}",
+                    summary.matched,
+                    self.records.len(),
+                    summary.database
+                );
+                self.compare_summary.push(summary);
+            }
+        }
+    }
+
+    async fn compare_against<E: StatusEmitter>(
+        &mut self,
+        path: &Arc<str>,
+        enabled: &Enabled,
+        pref: &Preferences,
+        app: &E,
+    ) -> Option<CompareSummary> {
         let mut cdb = Database::default();
-        cdb.init(Some(PathBuf::from(&*enabled.compare_db)), true)
-            .await;
-        app.substatus("compare", 0, "Loading Compare Database");
+        cdb.init(Some(PathBuf::from(&**path)), true).await;
+        // Cancelling the search also stops analysis of the compare database
+        cdb.abort = self.abort.clone();
+        let database = cdb.get_name().unwrap_or_else(|| path.clone());
+        app.substatus(
+            "compare",
+            0,
+            &format!("Loading Compare Database {}", database),
+        );
 
         let _ = cdb.fetch_all_filerecords(enabled, pref, app).await;
         let mut total = cdb.get_size();
         if total == 0 {
             total = 100;
         }
-        println!("{} Records Found in Compare Database", total);
-        // Use HashSet for O(1) lookup
-        let filenames_to_check: HashSet<_> = cdb
-            .records
-            .iter()
-            .enumerate()
-            .map(|(count, record)| {
+        println!("{} Records Found in {}", total, database);
+
+        let key = enabled.compare_key;
+        if matches!(key, CompareKey::Checksum | CompareKey::Fingerprint) {
+            // The compare database is only read: its own stored analysis is reused and anything
+            // missing is computed and cached in this database instead
+            let _ = cdb.refresh_scan_state(pref, app).await;
+            let cache = if pref.fetch_waveforms {
+                self.load_compare_cache(&mut cdb).await.unwrap_or_default()
+            } else {
+                CompareCache::new()
+            };
+            if key == CompareKey::Checksum {
+                let _ = cdb.gather_checksums(app).await;
+                let _ = self.gather_checksums(app).await;
+            } else {
+                let _ = cdb.gather_fingerprints(pref, app).await;
+                let _ = self.gather_fingerprints(pref, app).await;
+            }
+            if pref.store_waveforms
+                && let Err(e) = self.save_compare_cache(&cdb, &cache, app).await
+            {
+                println!("Failed to cache compare database analysis: {}", e);
+            }
+        }
+        if self.abort.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let found: Vec<Option<(usize, f64)>> = if key == CompareKey::Fingerprint {
+            app.substatus(
+                "compare",
+                0,
+                &format!("Comparing fingerprints against {}", database),
+            );
+            self.match_fingerprints_against(
+                &cdb,
+                pref.similarity_threshold / 100.0,
+                pref.fast_similarity,
+            )
+        } else {
+            // Use HashMap for O(1) lookup, the first compare record with a key wins
+            let mut keys_to_check: HashMap<Arc<str>, usize> = HashMap::new();
+            for (count, record) in cdb.records.iter().enumerate() {
                 if count % RECORD_DIVISOR == 0 {
                     app.substatus(
                         "compare",
@@ -28,24 +102,33 @@ impl Database {
                         &format!("Processing Records into Memory: {}/{}", count, total),
                     );
                 }
+                if let Some(k) = record.compare_key(key) {
+                    keys_to_check.entry(k).or_insert(count);
+                }
+            }
+            app.substatus(
+                "compare",
+                100,
+                &format!("Processing Records into Memory: {}/{}", total, total),
+            );
 
-                record.get_filename()
-            })
-            .collect();
-        app.substatus(
-            "compare",
-            100,
-            &format!("Processing Records into Memory: {}/{}", total, total),
-        );
-
-        println!("filenames to check: {:?}", filenames_to_check);
+            self.records
+                .iter()
+                .map(|record| {
+                    let j = *keys_to_check.get(&record.compare_key(key)?)?;
+                    Some((j, 1.0))
+                })
+                .collect()
+        };
 
         // Convert Arc to Vec, modify in parallel, and convert back
         total = self.records.len();
+        let matched = AtomicUsize::new(0);
         self.records
             .par_iter_mut()
+            .zip(found)
             .enumerate()
-            .for_each(|(count, record)| {
+            .for_each(|(count, (record, found))| {
                 if count % RECORD_DIVISOR == 0 {
                     app.substatus(
                         "compare",
@@ -54,19 +137,43 @@ impl Database {
                     );
                 }
 
-                if filenames_to_check.contains(record.get_filename()) {
-                    record.algorithm.insert(A::Compare);
-                    record.algorithm.remove(&A::Keep);
+                let Some((j, score)) = found else {
+                    return;
+                };
+                matched.fetch_add(1, Ordering::SeqCst);
+                if record.compare_match.is_some() {
+                    return;
                 }
+                let other = &cdb.records[j];
+                record.algorithm.insert(A::Compare);
+                record.algorithm.remove(&A::Keep);
+                record.compare_match = Some(CompareMatch {
+                    database: database.clone(),
+                    id: other.id,
+                    path: Arc::from(other.get_filepath()),
+                    score,
+                });
             });
         app.substatus(
             "compare",
             100,
             &format!("Comparing against Database: {}/{}", total, total),
         );
+
+        Some(CompareSummary {
+            database,
+            path: path.clone(),
+            records: cdb.records.len(),
+            matched: matched.into_inner(),
+        })
     }
 
-    pub fn dupe_search(&mut self, pref: &Preferences, enabled: &Enabled, app: &AppHandle) {
+    pub fn dupe_search<E: StatusEmitter>(
+        &mut self,
+        pref: &Preferences,
+        enabled: &Enabled,
+        app: &E,
+    ) {
         println!("Starting Duplicate Search");
 
         let mut file_groups: HashMap<Vec<Arc<str>>, Vec<FileRecord>> =
@@ -127,6 +234,7 @@ impl Database {
                     return records;
                 }
                 pref.sort_vec(&mut records);
+                FileRecord::set_group(&mut records);
 
                 records.iter_mut().enumerate().for_each(|(i, record)| {
                     if !(enabled.audiosuite || enabled.filename)
@@ -160,6 +268,7 @@ impl Database {
                     records.iter_mut().for_each(|record| {
                         record.algorithm.clear();
                         record.algorithm.insert(A::Keep);
+                        record.leave_group();
                     });
                 }
 
@@ -202,13 +311,99 @@ impl Database {
                     bitdepth: record.bitdepth,
                     samplerate: record.samplerate,
                     channels: record.channels,
+                    group: record.group,
+                    role: record.role,
+                    subset: record.subset,
+                    compare_match: record.compare_match.clone(),
+                    channel_analysis: record.channel_analysis.clone(),
                 }
             })
             .collect();
         results
     }
 
-    pub async fn dual_mono_search(&mut self, pref: &Preferences, app: &AppHandle) {
+    /// Each search pass groups records on its own, so a record can end up in a group of the
+    /// duplicate search and another of a waveform search. Groups sharing a record become one,
+    /// kept by whichever member comes first in preservation order.
+    pub fn merge_groups(&mut self, pref: &Preferences) {
+        fn find(parents: &mut HashMap<usize, usize>, group: usize) -> usize {
+            let parent = *parents.entry(group).or_insert(group);
+            if parent == group {
+                return group;
+            }
+            let root = find(parents, parent);
+            parents.insert(group, root);
+            root
+        }
+
+        let mut parents: HashMap<usize, usize> = HashMap::new();
+        for record in &self.records {
+            if let Some((first, rest)) = record.joined.split_first() {
+                let root = find(&mut parents, *first);
+                for group in rest {
+                    let other = find(&mut parents, *group);
+                    if other != root {
+                        parents.insert(other, root);
+                    }
+                }
+            }
+        }
+
+        let mut merged: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, record) in self.records.iter().enumerate() {
+            if let Some(group) = record.joined.first() {
+                merged
+                    .entry(find(&mut parents, *group))
+                    .or_default()
+                    .push(i);
+            }
+        }
+        for members in merged.into_values() {
+            let mut group: Vec<FileRecord> =
+                members.iter().map(|&i| self.records[i].clone()).collect();
+            pref.sort_vec(&mut group);
+            let keeper = group[0].id;
+            for &i in &members {
+                let record = &mut self.records[i];
+                let role = if record.id == keeper {
+                    GroupRole::Keeper
+                } else {
+                    GroupRole::Duplicate
+                };
+                record.leave_group();
+                record.join_group(keeper, role);
+            }
+        }
+    }
+
+    /// Duplicate groups in result order, with the current keeper of each.
+    pub fn groups(&self) -> Vec<DuplicateGroup> {
+        let mut order = Vec::new();
+        let mut groups: HashMap<usize, DuplicateGroup> = HashMap::new();
+        for record in &self.records {
+            let Some(id) = record.group else {
+                continue;
+            };
+            let group = groups.entry(id).or_insert_with(|| {
+                order.push(id);
+                DuplicateGroup {
+                    id,
+                    keeper: None,
+                    members: Vec::new(),
+                }
+            });
+            if record.role == Some(GroupRole::Keeper) {
+                group.keeper = Some(record.id);
+            }
+            group.members.push(record.id);
+        }
+        order
+            .into_iter()
+            .filter_map(|id| groups.remove(&id))
+            .collect()
+    }
+
+    pub async fn dual_mono_search<E: StatusEmitter>(&mut self, pref: &Preferences, app: &E) {
         let pool = self.get_pool().await.unwrap();
         println!("Starting Dual Mono Search");
         let total = self.records.len();
@@ -216,6 +411,7 @@ impl Database {
         // let batch_size = 2000;
         let mut chunks_completed = 0;
         let mut records_batch = Vec::with_capacity(pref.batch_size);
+        let mut stored = Vec::new();
 
         app.status("dual_mono", 0, "Starting Dual Mono Search");
         for chunk in self.records.chunks_mut(pref.batch_size) {
@@ -236,10 +432,23 @@ impl Database {
                         // if !record.check_path() {
                         //     return None;
                         // }
-                        if let Some(b) = record.dual_mono {
-                            if b {
-                                record.algorithm.insert(A::DualMono);
-                            }
+                        // Cached results only hold for the tolerance they were made with
+                        if record
+                            .channel_analysis
+                            .as_ref()
+                            .is_some_and(|a| a.tolerance_db == pref.channel_tolerance_db)
+                        {
+                            record.apply_channel_analysis();
+                            return None;
+                        }
+                        // Verdicts migrated from the old _DualMono column come without a channel
+                        // analysis. A dual mono file needs none of the other channel checks, but
+                        // the rest are analyzed for silent, inverted and near-identical channels.
+                        if record.channel_analysis.is_none() && record.dual_mono == Some(true) {
+                            record.algorithm.insert(A::DualMono);
+                            return None;
+                        }
+                        if record.channels == 1 {
                             return None;
                         }
 
@@ -248,29 +457,27 @@ impl Database {
                             new_completed % pref.batch_size * 100 / pref.batch_size,
                             &format!("Dual Mono Search: {}/{}", new_completed, total),
                         );
-                        let is_identical = audio::decode::are_channels_identical(&record.path);
-                        record.dual_mono = Some(is_identical);
-                        if is_identical {
-                            record.algorithm.insert(A::DualMono);
-                        } else {
-                            record.algorithm.remove(&A::DualMono);
-                        }
-                        Some((record.id, is_identical))
+                        record.channel_analysis =
+                            analyze_channels(&record.path, pref.channel_tolerance_db).ok();
+                        record.channel_analysis.as_ref()?;
+                        record.apply_channel_analysis();
+                        Some((record.id, record.scan_state()?))
                     })
-                    .collect::<Vec<(usize, bool)>>()
+                    .collect::<Vec<(usize, ScanState)>>()
             }; // Mutable borrow of self.records (through chunk) ends here
 
             records_batch.extend(records_to_update);
 
             if pref.store_waveforms && records_batch.len() >= pref.batch_size {
                 app.substatus("dual_mono", 0, "storing chunk to database");
-                let to_db: Vec<(usize, &str)> = records_batch
-                    .iter()
-                    .map(|(id, is_identical)| (*id, if *is_identical { "1" } else { "0" }))
-                    .collect();
-
-                crate::batch_store_data_optimized(&pool, &to_db, "_DualMono", app).await;
-                records_batch.clear();
+                match store_scan_state(&pool, &records_batch, app).await {
+                    Ok(_) => stored.append(&mut records_batch),
+                    // Left for `save_scan_state` to retry
+                    Err(e) => {
+                        println!("Failed to store analysis: {}", e);
+                        records_batch.clear();
+                    }
+                }
             }
             chunks_completed += pref.batch_size;
             app.status(
@@ -278,20 +485,17 @@ impl Database {
                 100 * chunks_completed / total,
                 &format!("Dual Mono Search: {}/{}", chunks_completed, total),
             );
-            // Then transform the results into the format needed for batch_store_data_optimized
         }
         if pref.store_waveforms && !records_batch.is_empty() {
             app.substatus("dual_mono", 0, "storing chunk to database");
-            let to_db: Vec<(usize, &str)> = records_batch
-                .iter()
-                .map(|(id, is_identical)| (*id, if *is_identical { "1" } else { "0" }))
-                .collect();
-
-            crate::batch_store_data_optimized(&pool, &to_db, "_DualMono", app).await;
-            records_batch.clear();
+            match store_scan_state(&pool, &records_batch, app).await {
+                Ok(_) => stored.append(&mut records_batch),
+                Err(e) => println!("Failed to store analysis: {}", e),
+            }
         }
+        self.scan_state.extend(stored);
     }
-    pub async fn dual_mono_search_seq(&mut self, app: &AppHandle) {
+    pub async fn dual_mono_search_seq<E: StatusEmitter>(&mut self, app: &E) {
         println!("Starting Dual Mono Search");
         let total = self.records.len();
         let mut completed = 0;
@@ -361,3 +565,42 @@ impl Database {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_sharing_a_record_get_one_keeper() {
+        let mut db = Database::default();
+        for id in 1..=5 {
+            let mut record = FileRecord {
+                id,
+                ..Default::default()
+            };
+            record.algorithm.insert(A::Keep);
+            db.records.push(record);
+        }
+        // The duplicate search pairs 1 and 2, a waveform search 3 and 2, and 4 and 5
+        for (keeper, duplicate) in [(0, 1), (2, 1), (3, 4)] {
+            let group = db.records[keeper].id;
+            db.records[keeper].join_group(group, GroupRole::Keeper);
+            db.records[duplicate].join_group(group, GroupRole::Duplicate);
+            db.records[duplicate].algorithm.remove(&A::Keep);
+        }
+
+        db.merge_groups(&Preferences::default());
+        let groups = db.groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].keeper, Some(1));
+        assert_eq!(groups[0].members, [1, 2, 3]);
+        assert_eq!(groups[1].keeper, Some(4));
+        assert_eq!(groups[1].members, [4, 5]);
+        let keepers = db
+            .records
+            .iter()
+            .filter(|r| r.get_role() == Some(GroupRole::Keeper))
+            .count();
+        assert_eq!(keepers, 2);
+    }
+}
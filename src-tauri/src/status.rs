@@ -0,0 +1,147 @@
+use crate::prelude::*;
+use std::sync::mpsc::{Receiver, Sender};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum StatusChannel {
+    Search,
+    SearchSub,
+    Remove,
+    RemoveSub,
+}
+
+impl StatusChannel {
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            StatusChannel::Search => "search-status",
+            StatusChannel::SearchSub => "search-sub-status",
+            StatusChannel::Remove => "remove-status",
+            StatusChannel::RemoveSub => "remove-sub-status",
+        }
+    }
+
+    pub fn is_sub(&self) -> bool {
+        matches!(self, StatusChannel::SearchSub | StatusChannel::RemoveSub)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatusUpdate {
+    pub stage: String,
+    pub progress: usize,
+    pub message: String,
+}
+
+/// Progress reporting for the search and removal routines.
+/// Sinks only need `emit_status`; the four helpers route to it.
+pub trait StatusEmitter: Send + Sync {
+    fn emit_status(&self, channel: StatusChannel, stage: &str, progress: usize, message: &str);
+
+    fn status(&self, stage: &str, progress: usize, message: &str) {
+        self.emit_status(StatusChannel::Search, stage, progress, message);
+    }
+    fn substatus(&self, stage: &str, progress: usize, message: &str) {
+        self.emit_status(StatusChannel::SearchSub, stage, progress, message);
+    }
+    fn rstatus(&self, stage: &str, progress: usize, message: &str) {
+        self.emit_status(StatusChannel::Remove, stage, progress, message);
+    }
+    fn rsubstatus(&self, stage: &str, progress: usize, message: &str) {
+        self.emit_status(StatusChannel::RemoveSub, stage, progress, message);
+    }
+}
+
+impl StatusEmitter for AppHandle {
+    fn emit_status(&self, channel: StatusChannel, stage: &str, progress: usize, message: &str) {
+        self.emit(
+            channel.event_name(),
+            StatusUpdate {
+                stage: stage.into(),
+                progress,
+                message: message.into(),
+            },
+        )
+        .ok();
+    }
+}
+
+/// Discards every update.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopEmitter;
+
+impl StatusEmitter for NoopEmitter {
+    fn emit_status(&self, _channel: StatusChannel, _stage: &str, _progress: usize, _message: &str) {
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatusEvent {
+    pub channel: StatusChannel,
+    pub update: StatusUpdate,
+}
+
+/// Forwards every update to a channel so the caller can consume them on its own thread.
+#[derive(Debug, Clone)]
+pub struct ChannelEmitter {
+    sender: Sender<StatusEvent>,
+}
+
+impl ChannelEmitter {
+    pub fn new() -> (Self, Receiver<StatusEvent>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        (Self { sender }, receiver)
+    }
+
+    pub fn from_sender(sender: Sender<StatusEvent>) -> Self {
+        Self { sender }
+    }
+}
+
+impl StatusEmitter for ChannelEmitter {
+    fn emit_status(&self, channel: StatusChannel, stage: &str, progress: usize, message: &str) {
+        // A dropped receiver just means nobody is listening anymore
+        let _ = self.sender.send(StatusEvent {
+            channel,
+            update: StatusUpdate {
+                stage: stage.into(),
+                progress,
+                message: message.into(),
+            },
+        });
+    }
+}
+
+/// Writes updates to stderr. Sub-status lines are only written when `verbose` is set,
+/// and repeated updates for the same stage and percentage are skipped.
+#[derive(Debug, Default)]
+pub struct LogEmitter {
+    verbose: bool,
+    last: std::sync::Mutex<Option<(StatusChannel, String, usize)>>,
+}
+
+impl LogEmitter {
+    pub fn new(verbose: bool) -> Self {
+        Self {
+            verbose,
+            last: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl StatusEmitter for LogEmitter {
+    fn emit_status(&self, channel: StatusChannel, stage: &str, progress: usize, message: &str) {
+        if channel.is_sub() && !self.verbose {
+            return;
+        }
+        if let Ok(mut last) = self.last.lock() {
+            if last
+                .as_ref()
+                .is_some_and(|(c, s, p)| *c == channel && s == stage && *p == progress)
+            {
+                return;
+            }
+            *last = Some((channel, stage.to_string(), progress));
+        }
+        let indent = if channel.is_sub() { "    " } else { "" };
+        eprintln!("[{:>3}%] {}{}", progress.min(100), indent, message);
+    }
+}
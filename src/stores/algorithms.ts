@@ -12,7 +12,7 @@ export const defaultAlgorithms: Algorithm[] = [
   { id: 'filetags', name: 'Filename Contains Tag', enabled: false },
   { id: 'invalidpath', name: 'Invalid Files', enabled: false },
   { id: 'duration', name: 'Minimum Duration', enabled: false, min_dur: 0.5 },
-  { id: 'dbcompare', name: 'Database Compare', enabled: false },
+  { id: 'dbcompare', name: 'Database Compare', enabled: false, compare_key: 'Filename' },
 ];
 
   export function getAlgoClass(algo: { id: string }, algorithms: any[]) {
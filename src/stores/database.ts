@@ -196,6 +196,31 @@ function addRecentDatabase(db: {name: string, path: string}) {
     });
 }
 
+  // Extra compare databases, searched alongside the one picked with getCompareDb
+  export async function addCompareDb() {
+    const compareDb = await openSqliteFile();
+    if (!compareDb) return;
+    preferencesStore.update((prefs) => ({
+      ...prefs,
+      algorithms: prefs.algorithms.map((algo) =>
+        algo.id === "dbcompare" && algo.db !== compareDb && !algo.dbs?.includes(compareDb)
+          ? { ...algo, enabled: true, dbs: [...(algo.dbs ?? []), compareDb] }
+          : algo
+      ),
+    }));
+  }
+
+  export function removeCompareDb(path: string) {
+    preferencesStore.update((prefs) => ({
+      ...prefs,
+      algorithms: prefs.algorithms.map((algo) =>
+        algo.id === "dbcompare"
+          ? { ...algo, dbs: (algo.dbs ?? []).filter((db) => db !== path) }
+          : algo
+      ),
+    }));
+  }
+
   export async function getCompareDb() {
     try {
       let compareDb = await openSqliteFile();
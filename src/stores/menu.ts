@@ -9,8 +9,9 @@ import {
 import { createLocalStore } from "./utils";
 import { openUrl } from "@tauri-apps/plugin-opener";
 import { writable, get } from 'svelte/store';
-import { preferencesStore, toggle_ignore_filetype, toggle_remove_records_from, updateEraseFiles, toggle_fetch_waveforms, toggle_store_waveforms, toggle_strip_dual_mono, updateWaveformSearchType } from './preferences';
+import { preferencesStore, toggle_ignore_filetype, toggle_remove_records_from, updateEraseFiles, chooseQuarantineFolder, toggle_fetch_waveforms, toggle_store_waveforms, toggle_strip_dual_mono, toggle_strip_quiet_channels, updateWaveformSearchType, mergeMode, updateMergeMetadata, toggle_embed_merged_metadata } from './preferences';
 import { presetsStore } from './presets';
+import { undoLastRemoval, restoreQuarantine, planRemoval } from './remove';
 import { openDatabase, closeDatabase, recentDbStore, setDatabase, databaseStore } from './database';
 import { WebviewWindow } from "@tauri-apps/api/webviewWindow";
 import { Window } from '@tauri-apps/api/window';
@@ -30,6 +31,10 @@ import { hotkeysStore, defaultHotKeys } from './hotkeys';
     checkSelected,
     uncheckSelected,
     toggleChecksSelected,
+    makeKeeper,
+    exportResults,
+    findSimilarSounds,
+    compareGroupMetadata,
     getTotalChecks,
     updateCurrentFilter,
     filterItems,
@@ -400,6 +405,14 @@ async function setupMenu() {
             checked: get(preferencesStore).erase_files === "Trash",
             action: async () => {await updateEraseFiles("Trash")},
           }),
+          await CheckMenuItem.new({
+            id: "quarantine-audio-files",
+            text: get(preferencesStore).quarantine_root
+              ? `Move to Quarantine: ${get(preferencesStore).quarantine_root}`
+              : "Move to Quarantine...",
+            checked: get(preferencesStore).erase_files === "Quarantine",
+            action: async () => {await chooseQuarantineFolder()},
+          }),
           await CheckMenuItem.new({
             id: "remove-audio-files",
             text: "Permanently Delete",
@@ -419,6 +432,43 @@ async function setupMenu() {
         action: async () => {await toggle_strip_dual_mono()},
 
       }),
+      await CheckMenuItem.new({
+        id: "strip-quiet-channels",
+        text: "Strip Quiet Channels Too",
+        checked: get(preferencesStore).strip_quiet_channels ?? false,
+        action: async () => {await toggle_strip_quiet_channels()},
+      }),
+      await Submenu.new({
+        text: "Merge Metadata into Keepers",
+        items: [
+          await CheckMenuItem.new({
+            id: "merge-off",
+            text: "Don't Merge",
+            checked: mergeMode(get(preferencesStore).merge_metadata) === "Off",
+            action: async () => {await updateMergeMetadata("Off")},
+          }),
+          await CheckMenuItem.new({
+            id: "merge-fill-empty",
+            text: "Fill Empty Fields",
+            checked: mergeMode(get(preferencesStore).merge_metadata) === "FillEmpty",
+            action: async () => {await updateMergeMetadata("FillEmpty")},
+          }),
+          await CheckMenuItem.new({
+            id: "merge-keywords",
+            text: "Fill Empty Fields and Pool Keywords",
+            checked: mergeMode(get(preferencesStore).merge_metadata) === "Keywords",
+            action: async () => {await updateMergeMetadata("Keywords")},
+          }),
+          separator,
+          await CheckMenuItem.new({
+            id: "merge-embed",
+            text: "Also Embed in Files (not with Safety Database)",
+            enabled: !!get(preferencesStore).merge_metadata,
+            checked: get(preferencesStore).merge_metadata?.embed ?? false,
+            action: async () => {await toggle_embed_merged_metadata()},
+          }),
+        ]
+      }),
       separator,
       await Submenu.new({
         text: "Audio Content Comparison",
@@ -435,6 +485,18 @@ async function setupMenu() {
             checked: get(preferencesStore).waveform_search_type === "Similar",
             action: async () => {await updateWaveformSearchType("Similar")},
           }),
+          await CheckMenuItem.new({
+            id: "checksum",
+            text: "Bit-exact Match",
+            checked: get(preferencesStore).waveform_search_type === "Checksum",
+            action: async () => {await updateWaveformSearchType("Checksum")},
+          }),
+          await CheckMenuItem.new({
+            id: "landmark",
+            text: "Landmark Match",
+            checked: get(preferencesStore).waveform_search_type === "Landmark",
+            action: async () => {await updateWaveformSearchType("Landmark")},
+          }),
 
         ]
 
@@ -620,6 +682,33 @@ const algoMenu = await Submenu.new({
         enabled: get(isSearching),
         action: () => {cancelSearch()},
       },
+      {
+        id: "planRemoval",
+        text: "Preview Removal...",
+        action: () => {planRemoval()},
+      },
+      {
+        id: "exportResults",
+        text: "Export Results...",
+        action: () => {exportResults()},
+      },
+      {
+        id: "findSimilarSounds",
+        text: "Find Similar Sounds...",
+        enabled: get(databaseStore) !== null,
+        action: () => {findSimilarSounds()},
+      },
+      {
+        id: "undoLastRemoval",
+        text: "Undo Last Removal",
+        enabled: get(databaseStore) !== null,
+        action: () => {undoLastRemoval()},
+      },
+      {
+        id: "restoreQuarantine",
+        text: "Restore from Quarantine...",
+        action: () => {restoreQuarantine()},
+      },
       separator,
       loadPresetMenu,
       separator,
@@ -649,6 +738,16 @@ const selectionMenu = await Submenu.new({
         accelerator: defaultHotKeys.toggleSelected,
         action: () => {toggleChecksSelected()}
       },
+      {
+        id: "makeKeeper",
+        text: "Make Keeper of Group",
+        action: () => {makeKeeper()}
+      },
+      {
+        id: "compareGroupMetadata",
+        text: "Compare Group Metadata",
+        action: () => {compareGroupMetadata()}
+      },
       separator,
       {
         id: "invertSelected",
@@ -1,12 +1,13 @@
 console.log('Loading module:', 'preferences.ts');  // Add to each file
 // src/stores/preferences.ts
-import type { Preferences } from './types';
+import type { Preferences, MergeOptions } from './types';
 import { defaultColors, terminalColors, applyColors } from './colors';
 import { defaultAlgorithms } from './algorithms';
 import { createLocalStore } from './utils';
 import { get } from "svelte/store";
 import { invoke } from "@tauri-apps/api/core";
 import { emit } from '@tauri-apps/api/event';
+import { open } from "@tauri-apps/plugin-dialog";
 import type { PreservationLogic } from './types';
 
 // Add version identifier - increment this when you change algorithm order
@@ -22,10 +23,15 @@ export const defaultPreferences: Preferences = {
     safety_db: true,
     safety_db_tag: "thinned",
     erase_files: "Keep",
+    quarantine_root: null,
     strip_dual_mono: false,
+    strip_quiet_channels: false,
+    merge_metadata: null,
     autoselects: [],
     waveform_search_type: "Exact",
     similarity_threshold: 80,
+    fast_similarity: false,
+    channel_tolerance_db: -60,
     store_waveforms: true,
     fetch_waveforms: true,
     firstOpen: true,
@@ -373,6 +379,43 @@ export async function toggle_remove_records_from() {
 export async function toggle_strip_dual_mono() {
     await updatePreference('strip_dual_mono', !get(preferencesStore).strip_dual_mono);
 }
+export async function toggle_strip_quiet_channels() {
+    await updatePreference('strip_quiet_channels', !get(preferencesStore).strip_quiet_channels);
+}
+
+export type MergeMode = "Off" | "FillEmpty" | "Keywords";
+
+/**
+ * What removing duplicates merges into the keeper of each group. Fields the
+ * keeper left empty are filled in; "Keywords" also pools the keywords of all.
+ */
+export function mergeMode(merge: MergeOptions | null | undefined): MergeMode {
+  if (!merge) return "Off";
+  return merge.policies["Keywords"] === "Keywords" ? "Keywords" : "FillEmpty";
+}
+
+export async function updateMergeMetadata(mode: MergeMode) {
+  const current = get(preferencesStore).merge_metadata;
+  const merge: MergeOptions | null =
+    mode === "Off"
+      ? null
+      : {
+          policies: mode === "Keywords" ? { Keywords: "Keywords" } : {},
+          default: "FillEmpty",
+          embed: current?.embed ?? false,
+        };
+  await updatePreference('merge_metadata', merge);
+}
+
+/**
+ * Also writes merged values into the keepers' files. Skipped when removing
+ * into a safety database copy, which shares its files with the original.
+ */
+export async function toggle_embed_merged_metadata() {
+  const current = get(preferencesStore).merge_metadata;
+  if (!current) return;
+  await updatePreference('merge_metadata', { ...current, embed: !current.embed });
+}
 
 // export function set_keep_audio_files() {
 //     updatePreference('erase_files', "Keep");
@@ -387,6 +430,31 @@ export async function toggle_strip_dual_mono() {
 export async function updateEraseFiles(value: string) {
   await updatePreference('erase_files', value);
 }
+
+/**
+ * Asks for the folder removed files are quarantined in and switches to
+ * quarantining. Returns false when the dialog is cancelled.
+ */
+export async function chooseQuarantineFolder(): Promise<boolean> {
+  const root = await open({
+    directory: true,
+    title: "Choose Quarantine Folder",
+    defaultPath: get(preferencesStore).quarantine_root ?? undefined,
+  });
+  if (typeof root !== "string") return false;
+  await updatePreference('quarantine_root', root);
+  await updatePreference('erase_files', "Quarantine");
+  return true;
+}
+
+/**
+ * The `delete` argument of remove_records. Quarantine carries its folder.
+ */
+export function deleteMode(prefs: Preferences): string | { Quarantine: { root: string } } {
+  if (prefs.erase_files !== "Quarantine") return prefs.erase_files;
+  if (!prefs.quarantine_root) throw new Error("No quarantine folder chosen");
+  return { Quarantine: { root: prefs.quarantine_root } };
+}
 export async function updateWaveformSearchType(value: string) {
   await updatePreference('waveform_search_type', value);
 }
@@ -1,10 +1,10 @@
 import { invoke } from "@tauri-apps/api/core";
-import { message } from "@tauri-apps/plugin-dialog";
-import { preferencesStore } from "./preferences";
+import { ask, message, open, save } from "@tauri-apps/plugin-dialog";
+import { preferencesStore, deleteMode } from "./preferences";
 import { filteredItemsStore, selectedItemsStore } from "./results";
-import { setDatabase } from "./database";
+import { setDatabase, databaseStore } from "./database";
 import { showSearchView } from "./menu";
-import type { FileRecord } from "./types";
+import type { FileRecord, UndoSummary, RestoreSummary, RemovalPlan } from "./types";
 import { get } from "svelte/store";
 
 /**
@@ -15,6 +15,21 @@ interface DualMonoFile {
   path: string;
 }
 
+/**
+ * Algorithms whose files have channels that can be stripped without losing anything
+ */
+const STRIPPABLE_CHANNELS = ["DualMono", "SilentChannel", "DuplicateChannels"];
+
+/**
+ * Files to strip channels from. The backend drops the channels each file's
+ * channel analysis found redundant.
+ */
+export function channelStripFiles(items: FileRecord[]): DualMonoFile[] {
+  return items
+    .filter((item) => STRIPPABLE_CHANNELS.some((a) => item.algorithm.includes(a)))
+    .map((item) => ({ id: item.id, path: item.path + "/" + item.filename }));
+}
+
 /**
  * Confirms with the user before removing files
  */
@@ -45,9 +60,7 @@ export async function removeFilteredRecords(): Promise<boolean> {
     .filter((item) => !item.algorithm.includes("Keep"))
     .map((item) => item.path + "/" + item.filename);
 
-  const dualMono = filteredItems
-    .filter((item) => item.algorithm.includes("DualMono"))
-    .map((item) => ({ id: item.id, path: item.path + "/" + item.filename }));
+  const dualMono = channelStripFiles(filteredItems);
 
   if (idsToRemove.length > 0 || dualMono.length > 0) {
     if (!(await confirmRemovalDialog())) return false;
@@ -58,15 +71,17 @@ export async function removeFilteredRecords(): Promise<boolean> {
         records: idsToRemove,
         clone: preferences.safety_db,
         cloneTag: preferences.safety_db_tag,
-        delete: preferences.erase_files,
+        delete: deleteMode(preferences),
         files: filesToRemove,
         dualMono: dualMono,
         stripDualMono: preferences.strip_dual_mono,
+        stripQuietChannels: preferences.strip_quiet_channels ?? false,
+        merge: preferences.merge_metadata ?? null,
       });
 
       if (dualMono.length > 0 && preferences.strip_dual_mono) {
         await message(
-          "Dual Mono files converted to Mono!\n\nRecords marked as dirty in Soundminer. For safety, open Soundminer and run the following:\n'Database -> Show Dirty'\nPress: 'CMD + A' to select all\n'Database -> Embed Selected'\n'Database -> Rebuild Waveforms for Selected'"
+          "Redundant channels stripped from multichannel files!\n\nRecords marked as dirty in Soundminer. For safety, open Soundminer and run the following:\n'Database -> Show Dirty'\nPress: 'CMD + A' to select all\n'Database -> Embed Selected'\n'Database -> Rebuild Waveforms for Selected'"
         );
       }
       
@@ -108,12 +123,9 @@ export async function removeSelectedRecords(): Promise<boolean> {
     )
     .map((item) => item.path + "/" + item.filename);
 
-  const dualMono = filteredItems
-    .filter(
-      (item) =>
-        item.algorithm.includes("DualMono") && selectedItems.has(item.id)
-    )
-    .map((item) => ({ id: item.id, path: item.path + "/" + item.filename }));
+  const dualMono = channelStripFiles(
+    filteredItems.filter((item) => selectedItems.has(item.id))
+  );
 
   if (idsToRemove.length > 0 || dualMono.length > 0) {
     if (!(await confirmRemovalDialog())) return false;
@@ -124,15 +136,17 @@ export async function removeSelectedRecords(): Promise<boolean> {
         records: idsToRemove,
         clone: preferences.safety_db,
         cloneTag: preferences.safety_db_tag,
-        delete: preferences.erase_files,
+        delete: deleteMode(preferences),
         files: filesToRemove,
         dualMono: dualMono,
         stripDualMono: preferences.strip_dual_mono,
+        stripQuietChannels: preferences.strip_quiet_channels ?? false,
+        merge: preferences.merge_metadata ?? null,
       });
 
       if (dualMono.length > 0 && preferences.strip_dual_mono) {
         await message(
-          "Dual Mono files converted to Mono!\n\nRecords marked as dirty in Soundminer. For safety, open Soundminer and run the following:\n'Database -> Show Dirty'\nPress: 'CMD + A' to select all\n'Database -> Embed Selected'\n'Database -> Rebuild Waveforms for Selected'"
+          "Redundant channels stripped from multichannel files!\n\nRecords marked as dirty in Soundminer. For safety, open Soundminer and run the following:\n'Database -> Show Dirty'\nPress: 'CMD + A' to select all\n'Database -> Embed Selected'\n'Database -> Rebuild Waveforms for Selected'"
         );
       }
       
@@ -153,6 +167,109 @@ export async function removeSelectedRecords(): Promise<boolean> {
   }
 }
 
+/**
+ * Shows what removing the filtered records would do, without touching
+ * anything, and offers to export the plan as CSV or JSON for sign-off
+ */
+export async function planRemoval(): Promise<boolean> {
+  const filteredItems = get(filteredItemsStore);
+  const preferences = get(preferencesStore);
+  const removed = filteredItems.filter((item) => !item.algorithm.includes("Keep"));
+
+  try {
+    const plan = await invoke<RemovalPlan>("plan_removal", {
+      records: removed.map((item) => item.id),
+      clone: preferences.safety_db,
+      cloneTag: preferences.safety_db_tag,
+      delete: deleteMode(preferences),
+      files: removed.map((item) => item.path + "/" + item.filename),
+      dualMono: channelStripFiles(filteredItems),
+      stripDualMono: preferences.strip_dual_mono,
+      stripQuietChannels: preferences.strip_quiet_channels ?? false,
+    });
+    const megabytes = (plan.bytes_freed / 1024 / 1024).toFixed(1);
+    const summary =
+      `Database: ${plan.database ?? "none"}${plan.clone ? " (safety copy)" : ""}\n` +
+      `Rows removed: ${plan.rows_affected} of ${plan.rows_requested}\n` +
+      `Files: ${plan.files.length}\n` +
+      `Disk space recovered: ${megabytes} MB\n` +
+      `Missing files: ${plan.missing}\n` +
+      `Permission denied: ${plan.permission_denied}\n\n` +
+      "Export this plan?";
+    const exportPlan = await ask(summary, {
+      title: "Removal Plan",
+      okLabel: "Export...",
+      cancelLabel: "Close",
+    });
+    if (!exportPlan) return true;
+
+    const path = await save({
+      title: "Export Removal Plan",
+      defaultPath: "removal_plan.csv",
+      filters: [
+        { name: "CSV", extensions: ["csv"] },
+        { name: "JSON", extensions: ["json"] },
+      ],
+    });
+    if (!path) return true;
+    await invoke("export_removal_plan", { plan, path });
+    return true;
+  } catch (error) {
+    console.error("Error planning removal:", error);
+    await message(String(error), { title: "Removal Plan", kind: "error" });
+    return false;
+  }
+}
+
+/**
+ * Puts back the rows and files of the last removal from the current database
+ */
+export async function undoLastRemoval(): Promise<boolean> {
+  try {
+    const summary = await invoke<UndoSummary>("undo_last_removal");
+    let text = `Restored ${summary.records} records and ${summary.files} files.`;
+    if (summary.put_back.length > 0) {
+      text += `\n\n${summary.put_back.length} files are in the Trash. Select them in the Finder and use 'File -> Put Back':\n${summary.put_back.join("\n")}`;
+    }
+    if (summary.failed.length > 0) {
+      text += `\n\n${summary.failed.length} files could not be restored:\n${summary.failed.join("\n")}`;
+    }
+    await message(text, { title: "Undo Last Removal" });
+    const db = get(databaseStore);
+    if (db?.path) await setDatabase(db.path, false);
+    return true;
+  } catch (error) {
+    console.error("Error undoing removal:", error);
+    await message(String(error), { title: "Undo Last Removal", kind: "error" });
+    return false;
+  }
+}
+
+/**
+ * Moves every file held in a quarantine folder back to where it came from
+ */
+export async function restoreQuarantine(): Promise<boolean> {
+  const root = await open({
+    directory: true,
+    title: "Restore from Quarantine Folder",
+    defaultPath: get(preferencesStore).quarantine_root ?? undefined,
+  });
+  if (typeof root !== "string") return false;
+  try {
+    const summary = await invoke<RestoreSummary>("restore_quarantine", { root, files: [] });
+    let text = `Restored ${summary.restored} files.`;
+    if (summary.failed.length > 0) {
+      text += `\n\n${summary.failed.length} files could not be restored:\n${summary.failed.join("\n")}`;
+    }
+    await message(text, { title: "Restore from Quarantine" });
+    return true;
+  } catch (error) {
+    console.error("Error restoring quarantine:", error);
+    await message(String(error), { title: "Restore from Quarantine", kind: "error" });
+    return false;
+  }
+}
+
 /**
  * Track an item to be removed (like a checkbox toggle)
  * If your application has specific UI for tracking items to remove
@@ -1,6 +1,8 @@
 console.log('Loading module:', 'results.ts');  // Fixed module name
 
-import type { FileRecord } from './types';
+import { invoke } from "@tauri-apps/api/core";
+import { ask, message, open, save } from "@tauri-apps/plugin-dialog";
+import type { FileRecord, MetadataDiff, SoundMatch } from './types';
 import { createSessionStore } from './utils';
 import { writable, derived, get } from 'svelte/store';
 import { preferencesStore } from './preferences';
@@ -67,7 +69,10 @@ export function filterItems(items: FileRecord[], filter: string): FileRecord[] {
     case "audiosuite":
       return items.filter((item) => item.algorithm.includes("Tags"));
     case "dual_mono":
-      return items.filter((item) => item.algorithm.includes("DualMono"));
+      return items.filter((item) =>
+        ["DualMono", "SilentChannel", "DuplicateChannels", "InvertedChannels", "NearIdenticalChannels"]
+          .some((algo) => item.algorithm.includes(algo))
+      );
     case "filename":
       return items.filter((item) => item.algorithm.includes("SimilarFilename"));
     case "waveform":
@@ -176,6 +181,146 @@ export function toggleSelect(item: FileRecord, event: MouseEvent): void {
   });
 }
 
+/**
+ * Makes the one selected record the keeper of its group; later searches keep the choice
+ */
+export async function makeKeeper(): Promise<boolean> {
+  const selected = Array.from(get(selectedItemsStore));
+  if (selected.length !== 1) {
+    await message("Select one record to keep from its group.", { title: "Make Keeper" });
+    return false;
+  }
+  try {
+    const results = await invoke<FileRecord[]>("set_keeper", { id: selected[0] });
+    resultsStore.set(results);
+    return true;
+  } catch (error) {
+    console.error("Error setting keeper:", error);
+    await message(String(error), { title: "Make Keeper", kind: "error" });
+    return false;
+  }
+}
+
+/**
+ * Writes the current results to CSV, JSON Lines or a plain path list, chosen by extension
+ */
+export async function exportResults(): Promise<boolean> {
+  const path = await save({
+    title: "Export Results",
+    defaultPath: "results.csv",
+    filters: [
+      { name: "CSV", extensions: ["csv"] },
+      { name: "JSON Lines", extensions: ["jsonl"] },
+      { name: "Path List", extensions: ["txt"] },
+    ],
+  });
+  if (!path) return false;
+
+  const format = path.endsWith(".jsonl") ? "JsonLines" : path.endsWith(".txt") ? "PathList" : "Csv";
+  const markedOnly = await ask("Export only the records marked for removal?", {
+    title: "Export Results",
+    okLabel: "Marked Only",
+    cancelLabel: "All Records",
+  });
+  try {
+    const count = await invoke<number>("export_results", {
+      path,
+      format,
+      columns: get(preferencesStore).match_criteria,
+      markedOnly,
+    });
+    await message(`Exported ${count} records to ${path}`, { title: "Export Results" });
+    return true;
+  } catch (error) {
+    console.error("Error exporting results:", error);
+    await message(String(error), { title: "Export Results", kind: "error" });
+    return false;
+  }
+}
+
+/**
+ * Fingerprints an audio file picked by the user and lists the library sounds closest to it
+ */
+export async function findSimilarSounds(): Promise<boolean> {
+  const path = await open({
+    title: "Find Similar Sounds",
+    multiple: false,
+    directory: false,
+    filters: [{ name: "Audio", extensions: ["wav", "aif", "aiff", "flac", "mp3", "m4a", "ogg"] }],
+  });
+  if (!path) return false;
+
+  try {
+    const matches = await invoke<SoundMatch[]>("find_similar_sounds", {
+      path,
+      pref: get(preferencesStore),
+    });
+    if (matches.length === 0) {
+      await message("No similar sounds found.", { title: "Find Similar Sounds" });
+      return true;
+    }
+    const shown = 25;
+    const lines = matches.slice(0, shown).map((match) => {
+      const score = `${Math.round(match.score * 100)}%`;
+      const relation = match.relation;
+      if (relation === "Similar") return `${score}  ${match.filename}`;
+      if ("QueryInside" in relation) {
+        const { start, end } = relation.QueryInside;
+        return `${score}  ${match.filename} (at ${start.toFixed(1)}-${end.toFixed(1)} s)`;
+      }
+      const { start, end } = relation.LibraryInside;
+      return `${score}  ${match.filename} (inside the query at ${start.toFixed(1)}-${end.toFixed(1)} s)`;
+    });
+    if (matches.length > shown) lines.push(`...and ${matches.length - shown} more`);
+    await message(lines.join("\n"), { title: "Find Similar Sounds" });
+    return true;
+  } catch (error) {
+    console.error("Error finding similar sounds:", error);
+    await message(String(error), { title: "Find Similar Sounds", kind: "error" });
+    return false;
+  }
+}
+
+/**
+ * Lists the metadata fields that differ between the records of the selected record's group
+ */
+export async function compareGroupMetadata(): Promise<boolean> {
+  const selected = Array.from(get(selectedItemsStore));
+  const results = get(resultsStore);
+  const record = results.find((item) => item.id === selected[0]);
+  if (selected.length !== 1 || record?.group == null) {
+    await message("Select one record of a duplicate group.", { title: "Compare Group Metadata" });
+    return false;
+  }
+  const ids = results
+    .filter((item) => item.group === record.group)
+    .map((item) => item.id);
+
+  try {
+    const diff = await invoke<MetadataDiff>("diff_group_metadata", { ids });
+    const names = diff.ids.map((id) => {
+      const name = results.find((item) => item.id === id)?.filename ?? String(id);
+      return id === diff.keeper ? `${name} (keeper)` : name;
+    });
+    const differing = diff.fields.filter((field) => field.differs);
+    if (differing.length === 0) {
+      await message(`All compared fields match:\n${names.join("\n")}`, { title: "Compare Group Metadata" });
+      return true;
+    }
+    const lines = differing.map((field) => {
+      const values = field.values.map((value, i) => `  ${names[i]}: ${value ?? "(none)"}`);
+      const missing = field.keeper_missing ? " - missing on the keeper" : "";
+      return `${field.source} ${field.field}${missing}\n${values.join("\n")}`;
+    });
+    await message(lines.join("\n\n"), { title: "Compare Group Metadata" });
+    return true;
+  } catch (error) {
+    console.error("Error comparing metadata:", error);
+    await message(String(error), { title: "Compare Group Metadata", kind: "error" });
+    return false;
+  }
+}
+
 // Check/Uncheck-related functions
 export function toggleChecked(item: FileRecord): void {
   const isKeeping = item.algorithm.includes("Keep");
@@ -132,17 +132,19 @@ export async function search(): Promise<boolean> {
     resultsStore.set([]);
 
     let algorithmState = algorithms.reduce(
-        (acc: Record<string, boolean | number | string>, algo: Algorithm) => {
+        (acc: Record<string, boolean | number | string | string[]>, algo: Algorithm) => {
             acc[algo.id] = algo.enabled;
             if (algo.id === "duration") {
                 acc["min_dur"] = algo.min_dur ?? 0;
             }
             if (algo.id === "dbcompare") {
                 acc["compare_db"] = algo.db ?? "";
+                acc["compare_dbs"] = algo.dbs ?? [];
+                acc["compare_key"] = algo.compare_key ?? "Filename";
             }
             return acc;
         },
-        {} as Record<string, boolean | number | string>
+        {} as Record<string, boolean | number | string | string[]>
     );
 
     if (!algorithmState.basic) {
@@ -19,7 +19,9 @@ export type Algorithm = {
   name: string; 
   enabled: boolean; 
   min_dur?: number;
-  db?: string | null 
+  db?: string | null;
+  dbs?: string[];
+  compare_key?: "Filename" | "Root" | "FilenameDuration" | "Checksum" | "Fingerprint";
 };
 
 export type Registration = { 
@@ -55,7 +57,84 @@ export type FileRecord = {
     samplerate: string; 
     bitdepth: string; 
     channels: string; 
-    description: string; };
+    description: string; 
+    group?: number | null; 
+    role?: "Keeper" | "Duplicate" | null;
+    subset?: { parent: number; start: number; end: number; score: number } | null;
+    compare_match?: { database: string; id: number; path: string; score: number } | null;
+    channel_analysis?: ChannelAnalysis | null; };
+
+export type ChannelAnalysis = {
+    channels: number;
+    tolerance_db: number;
+    silent: number[];
+    digital_silence?: number[];
+    identical: [number, number][];
+    inverted: [number, number][];
+    near_identical: [number, number][];
+    track_names?: string[];
+};
+
+export type DuplicateGroup = {
+    id: number;
+    keeper: number | null;
+    members: number[];
+};
+
+export type CompareSummary = {
+    database: string;
+    path: string;
+    records: number;
+    matched: number;
+};
+
+export type BextLoudness = {
+    loudness_value: number;
+    loudness_range: number;
+    max_true_peak_level: number;
+    max_momentary_loudness: number;
+    max_short_term_loudness: number;
+};
+
+export type Bext = {
+    description: string;
+    originator: string;
+    originator_reference: string;
+    origination_date: string;
+    origination_time: string;
+    time_reference: number;
+    version: number;
+    umid: number[];
+    loudness: BextLoudness | null;
+    reserved?: number[];
+    coding_history: string;
+};
+
+export type FieldDiff = {
+    source: "Database" | "Bext" | "IXml" | "Vorbis" | "Chunk";
+    field: string;
+    values: (string | null)[];
+    differs: boolean;
+    keeper_missing: boolean;
+};
+
+export type MetadataDiff = {
+    keeper: number | null;
+    ids: number[];
+    fields: FieldDiff[];
+};
+
+export type SoundMatch = {
+    id: number;
+    path: string;
+    filename: string;
+    duration: string;
+    score: number;
+    relation:
+        | "Similar"
+        | { QueryInside: { start: number; end: number } }
+        | { LibraryInside: { start: number; end: number } };
+};
 
 
 export type Database = {
@@ -81,9 +160,14 @@ export type Preferences = {
   safety_db: boolean;
   safety_db_tag: string;
   erase_files: string;
+  quarantine_root?: string | null;
   strip_dual_mono: boolean;
+  strip_quiet_channels?: boolean;
+  merge_metadata?: MergeOptions | null;
   waveform_search_type: string;
   similarity_threshold: number;
+  fast_similarity?: boolean;
+  channel_tolerance_db?: number;
   store_waveforms: boolean;
   fetch_waveforms: boolean;
   colors: Colors;
@@ -92,6 +176,46 @@ export type Preferences = {
   batch_size: number;
 };
 
+export type UndoSummary = {
+  records: number;
+  files: number;
+  failed: string[];
+  put_back: string[];
+};
+
+export type PlannedFile = {
+  path: string;
+  action: "Keep" | "Trash" | "Delete" | "Quarantine" | "StripDualMono";
+  destination: string | null;
+  size: number;
+  status: "Ok" | "Missing" | "PermissionDenied";
+  reason: string | null;
+};
+
+export type RemovalPlan = {
+  database: string | null;
+  clone: boolean;
+  rows_requested: number;
+  rows_affected: number;
+  files: PlannedFile[];
+  bytes_freed: number;
+  missing: number;
+  permission_denied: number;
+};
+
+export type RestoreSummary = {
+  restored: number;
+  failed: string[];
+};
+
+export type MergePolicy = "Keep" | "FillEmpty" | "Keywords" | "Longest";
+
+export type MergeOptions = {
+  policies: Record<string, MergePolicy>;
+  default: MergePolicy;
+  embed: boolean;
+};
+
 export type Preset = { 
   name: string; 
   pref: Preferences 